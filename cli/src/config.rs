@@ -0,0 +1,222 @@
+use newsapi::{Article, Country};
+use newsapi::filter::FilterRule;
+use newsapi::language::{LibreTranslate, NoTranslator, Translator};
+use newsapi::rank::{Preferences, SortMode};
+use crate::layout::Density;
+use crate::mail::SmtpConfig;
+use crate::theme::SkinDef;
+use crate::webhook::Sink;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub filters: Vec<FilterRule>,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    #[serde(default)]
+    pub summary: SummaryConfig,
+    /// Name of the skin to render with.
+    pub theme: Option<String>,
+    /// How much of each article the listing shows.
+    #[serde(default)]
+    pub density: Density,
+    /// Order of the listing.
+    #[serde(default)]
+    pub sort: SortMode,
+    /// What the "for you" order goes by.
+    #[serde(default)]
+    pub ranking: Preferences,
+    #[serde(default)]
+    pub skins: HashMap<String, SkinDef>,
+    #[serde(default)]
+    pub digest: DigestConfig,
+    #[serde(default)]
+    pub serve: ServeConfig,
+    #[serde(default)]
+    pub translate: TranslateConfig
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    None,
+    LibreTranslate
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TranslateConfig {
+    pub provider: Provider,
+    /// Address of the LibreTranslate server.
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+    /// Language `--translate` translates into.
+    pub target: String
+}
+
+impl Default for TranslateConfig {
+    fn default() -> Self {
+        TranslateConfig { provider: Provider::None, url: None, api_key: None, target: "en".to_string() }
+    }
+}
+
+impl TranslateConfig {
+    pub fn translator(&self) -> Result<Box<dyn Translator>, Box<dyn Error>> {
+        match (self.provider, &self.url) {
+            (Provider::None, _) => Ok(Box::new(NoTranslator)),
+            (Provider::LibreTranslate, Some(url)) => {
+                let mut translator = LibreTranslate::new(url);
+                translator.api_key(self.api_key.clone());
+                Ok(Box::new(translator))
+            },
+            (Provider::LibreTranslate, None) => Err("translate.url is needed for libretranslate".into()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    /// Address and port `clinews serve` listens on.
+    pub address: String,
+    /// How long a newsapi response is reused for.
+    pub cache_secs: u64,
+    /// Countries clients may ask for, the first one by default.
+    pub countries: Vec<Country>,
+    /// Source ids clients may ask for. None when empty.
+    pub sources: Vec<String>
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            address: "127.0.0.1:8080".to_string(),
+            cache_secs: 300,
+            countries: vec![Country::Us],
+            sources: vec![]
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    pub subject: String,
+    /// Stories in a digest, at most.
+    pub max_stories: usize,
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub smtp: Option<SmtpConfig>
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        DigestConfig {
+            subject: "Headlines digest".to_string(),
+            max_stories: 20,
+            from: None,
+            to: vec![],
+            smtp: None
+        }
+    }
+}
+
+/// Summaries download every article page, so they are off unless asked for.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SummaryConfig {
+    /// Sentences in the summary under each headline, 0 to turn them off.
+    pub sentences: usize
+}
+
+/// Keyword alert for `clinews watch`. Fires when any of the keywords shows up
+/// in the title or description of a new article.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Alert {
+    pub name: String,
+    pub keywords: Vec<String>
+}
+
+impl Alert {
+    pub fn matches(&self, article: &Article) -> bool {
+        let text = format!(
+            "{}\n{}",
+            article.title(),
+            article.description().map(|s| s.as_str()).unwrap_or_default()
+        ).to_lowercase();
+        self.keywords.iter().any(|k| text.contains(&k.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    pub interval_secs: u64,
+    /// Requests per day allowed by the newsapi plan (100 on the free tier).
+    pub daily_quota: u32,
+    pub log_file: Option<PathBuf>,
+    pub alerts: Vec<Alert>,
+    /// Where matches are posted, besides the desktop notification.
+    pub webhooks: Vec<Sink>
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            interval_secs: 15 * 60,
+            daily_quota: 100,
+            log_file: dirs::data_dir().map(|d| d.join("clinews").join("watch.log")),
+            alerts: vec![],
+            webhooks: vec![]
+        }
+    }
+}
+
+/// `$CLINEWS_CONFIG` if set, otherwise `<config dir>/clinews/config.toml`.
+pub fn path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("CLINEWS_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    dirs::config_dir().map(|d| d.join("clinews").join("config.toml"))
+}
+
+/// Loads the config file, falling back to the defaults if it doesn't exist.
+pub fn load() -> Result<Config, Box<dyn Error>> {
+    match path() {
+        Some(path) if path.exists() => {
+            let contents = std::fs::read_to_string(&path)?;
+            Ok(toml::from_str(&contents)?)
+        },
+        _ => Ok(Config::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use newsapi::filter::{Action, Matcher};
+
+    #[test]
+    fn parses_filter_rules() {
+        let config: Config = toml::from_str(r#"
+            [[filters]]
+            name = "no gossip"
+            action = "exclude"
+            keyword = "kardashian"
+
+            [[filters]]
+            action = "include"
+            domain = "bbc.co.uk"
+        "#).unwrap();
+
+        assert_eq!(config.filters.len(), 2);
+        assert_eq!(config.filters[0].action, Action::Exclude);
+        assert_eq!(config.filters[0].matcher, Matcher::Keyword("kardashian".to_string()));
+        assert_eq!(config.filters[1].matcher, Matcher::Domain("bbc.co.uk".to_string()));
+        assert_eq!(config.filters[1].label(), "include domain \"bbc.co.uk\"");
+    }
+}
@@ -0,0 +1,193 @@
+//! `clinews digest`: the stories of the last day (or any other window) from
+//! the local archive, ready to be mailed.
+//!
+//! Articles about the same story are clustered by their titles. Stories are
+//! ranked by how many different sources covered them, plus up to one point
+//! for how recent they are.
+
+use crate::layout::relative_time;
+use crate::store::Archived;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use newsapi::cluster::cluster;
+use newsapi::export::escape;
+use std::collections::HashSet;
+
+/// Parses a time window like `90m`, `24h`, `7d` or `2w`.
+pub fn parse_since(since: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {:?}, expected something like 24h, 90m or 7d", since);
+    let since = since.trim();
+    let unit = since.chars().last().ok_or_else(invalid)?;
+    let n: i64 = since[..since.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    let duration = match unit {
+        'm' => Duration::try_minutes(n),
+        'h' => Duration::try_hours(n),
+        'd' => Duration::try_days(n),
+        'w' => Duration::try_weeks(n),
+        _ => None,
+    };
+    duration.ok_or_else(invalid)
+}
+
+pub struct Story<'a> {
+    /// Newest first.
+    pub articles: Vec<&'a Archived>,
+    pub sources: usize,
+    pub score: f64
+}
+
+impl<'a> Story<'a> {
+    pub fn title(&self) -> &str {
+        self.articles[0].article.title()
+    }
+
+    /// When the newest article came out.
+    fn newest(&self) -> i64 {
+        published(self.articles[0])
+    }
+}
+
+/// Publication time, or when the article was first seen if it has none.
+fn published(archived: &Archived) -> i64 {
+    archived
+        .article
+        .published_at()
+        .and_then(|p| DateTime::parse_from_rfc3339(p).ok())
+        .map(|p| p.timestamp())
+        .unwrap_or(archived.seen_at)
+}
+
+/// The stories published within `since` before `now`, best first.
+pub fn stories(archive: &[Archived], now: DateTime<Utc>, since: Duration) -> Vec<Story<'_>> {
+    let start = (now - since).timestamp();
+    let recent: Vec<&Archived> = archive.iter().filter(|a| published(a) >= start).collect();
+    let titles: Vec<&str> = recent.iter().map(|a| a.article.title()).collect();
+
+    let mut stories: Vec<Story> = cluster(&titles)
+        .into_iter()
+        .map(|members| {
+            let mut articles: Vec<&Archived> = members.iter().map(|&i| recent[i]).collect();
+            articles.sort_by_key(|a| std::cmp::Reverse(published(a)));
+            let sources = articles
+                .iter()
+                .map(|a| a.article.source().name().to_lowercase())
+                .collect::<HashSet<_>>()
+                .len();
+            let age = (now.timestamp() - published(articles[0])) as f64;
+            let recency = (1. - age / since.num_seconds().max(1) as f64).clamp(0., 1.);
+            Story { articles, sources, score: sources as f64 + recency }
+        })
+        .collect();
+
+    stories.sort_by(|a, b| b.score.total_cmp(&a.score).then(b.newest().cmp(&a.newest())));
+    stories
+}
+
+fn age(archived: &Archived, now: DateTime<Utc>) -> String {
+    match Utc.timestamp_opt(published(archived), 0).single() {
+        Some(published) => relative_time(&published.to_rfc3339(), now).unwrap_or_default(),
+        None => String::new(),
+    }
+}
+
+fn sources_label(story: &Story) -> String {
+    match story.sources {
+        1 => "1 source".to_string(),
+        n => format!("{} sources", n),
+    }
+}
+
+pub fn render_text(title: &str, stories: &[Story], now: DateTime<Utc>) -> String {
+    let mut out = format!("{}\n{}\n", title, "=".repeat(title.chars().count()));
+    for (i, story) in stories.iter().enumerate() {
+        out.push_str(&format!(
+            "\n{}. {}\n   {} · {}\n",
+            i + 1,
+            story.title(),
+            sources_label(story),
+            age(story.articles[0], now)
+        ));
+        for a in &story.articles {
+            out.push_str(&format!("   - {} ({})\n     {}\n", a.article.title(), a.article.source().name(), a.article.url()));
+        }
+    }
+    out
+}
+
+pub fn render_html(title: &str, stories: &[Story], now: DateTime<Utc>) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n\
+         <body style=\"font-family: Helvetica, Arial, sans-serif; max-width: 40em;\">\n<h1>{title}</h1>\n<ol>\n",
+        title = escape(title)
+    );
+    for story in stories {
+        out.push_str(&format!(
+            "<li>\n<h3 style=\"margin-bottom: 0;\">{}</h3>\n<p style=\"color: #666; margin-top: 0;\">{} · {}</p>\n<ul>\n",
+            escape(story.title()),
+            sources_label(story),
+            escape(&age(story.articles[0], now))
+        ));
+        for a in &story.articles {
+            out.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> <span style=\"color: #666;\">{}</span></li>\n",
+                escape(a.article.url()),
+                escape(a.article.title()),
+                escape(a.article.source().name())
+            ));
+        }
+        out.push_str("</ul>\n</li>\n");
+    }
+    out.push_str("</ol>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archived(title: &str, source: &str, hours_ago: i64, now: DateTime<Utc>) -> Archived {
+        let article = serde_json::from_value(serde_json::json!({
+            "source": { "id": null, "name": source },
+            "title": title,
+            "url": format!("https://{}.example/{}", source.to_lowercase(), title.len()),
+            "description": null,
+            "publishedAt": (now - Duration::hours(hours_ago)).to_rfc3339()
+        })).unwrap();
+        Archived { seen_at: now.timestamp(), article }
+    }
+
+    #[test]
+    fn parses_time_windows() {
+        assert_eq!(parse_since("24h"), Ok(Duration::hours(24)));
+        assert_eq!(parse_since("90m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_since("7d"), Ok(Duration::days(7)));
+        assert!(parse_since("h").is_err());
+        assert!(parse_since("3y").is_err());
+        assert!(parse_since("9223372036854775807w").is_err());
+    }
+
+    #[test]
+    fn ranks_stories_by_sources_and_recency() {
+        let now = Utc.timestamp_opt(1_654_858_800, 0).unwrap();
+        let archive = vec![
+            archived("Storm warning for the east coast", "Gazette", 1, now),
+            archived("Fed raises interest rates by half a point", "Wire", 5, now),
+            archived("Interest rates: what the Fed raise means for you", "Gazette", 3, now),
+            archived("Championship final goes to extra time", "Wire", 2, now),
+            archived("Fed raises rates again", "Wire", 4, now),
+            archived("Last week's news", "Wire", 30, now),
+        ];
+        let stories = stories(&archive, now, Duration::hours(24));
+
+        let summary: Vec<(&str, usize, usize)> = stories.iter().map(|s| (s.title(), s.articles.len(), s.sources)).collect();
+        assert_eq!(summary, vec![
+            ("Interest rates: what the Fed raise means for you", 3, 2),
+            ("Storm warning for the east coast", 1, 1),
+            ("Championship final goes to extra time", 1, 1),
+        ]);
+
+        let text = render_text("Digest", &stories, now);
+        assert!(text.starts_with("Digest\n======\n\n1. Interest rates: what the Fed raise means for you\n   2 sources · 3h ago\n"));
+        let html = render_html("Digest", &stories, now);
+        assert!(html.contains("<h3 style=\"margin-bottom: 0;\">Storm warning for the east coast</h3>"));
+    }
+}
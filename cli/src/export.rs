@@ -0,0 +1,84 @@
+//! `clinews export`: the top headlines as a digest file.
+
+use newsapi::export::{Digest, Format, GroupBy, Item};
+use newsapi::filter::FilterEngine;
+use newsapi::{extract, Category, NewsAPI};
+use std::error::Error;
+
+/// Fetches the articles for the digest. Grouping by category takes one
+/// request per category, since newsapi doesn't say which one an article is in.
+pub fn collect(newsapi: &mut NewsAPI, filters: &FilterEngine, group_by: GroupBy) -> Result<Vec<Item>, Box<dyn Error>> {
+    let categories: Vec<Option<Category>> = match group_by {
+        GroupBy::Source => vec![None],
+        GroupBy::Category => Category::ALL.iter().map(|c| Some(*c)).collect(),
+    };
+
+    let mut items: Vec<Item> = vec![];
+    for category in categories {
+        let mut response = newsapi.category(category).fetch()?;
+        filters.apply(&mut response.articles);
+        for article in response.articles() {
+            // Top stories tend to show up in "general" and their own category.
+            if !items.iter().any(|i| i.url == article.url()) {
+                items.push(Item::from_article(article, category.map(|c| c.name())));
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Pages downloaded at once when extracting the full text.
+const MAX_IN_FLIGHT: usize = 8;
+
+/// Downloads and extracts every article, a few at a time. Pages that can't
+/// be read keep just their description.
+pub async fn add_full_text(items: &mut [Item]) {
+    for batch in items.chunks_mut(MAX_IN_FLIGHT) {
+        let tasks: Vec<_> = batch
+            .iter()
+            .map(|item| {
+                let url = item.url.to_string();
+                tokio::task::spawn_blocking(move || extract::fetch(&url).ok())
+            })
+            .collect();
+
+        for (item, task) in batch.iter_mut().zip(tasks) {
+            item.text = task.await.ok().flatten();
+        }
+    }
+}
+
+pub fn render(title: &str, items: Vec<Item>, format: Format, group_by: GroupBy) -> Vec<u8> {
+    let mut digest = Digest::new(title, items);
+    digest.group_by(group_by);
+    digest.render(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{articles_json, MockServer};
+
+    #[test]
+    fn groups_by_category_without_duplicates() {
+        let general = articles_json(&[("Rates rise", "https://wire.example/rates"), ("Cup final", "https://gazette.example/cup")]);
+        let business = articles_json(&[("Rates rise", "https://wire.example/rates"), ("Chips", "https://wire.example/chips")]);
+        let server = MockServer::start(vec![(200, business), (200, articles_json(&[])), (200, general)]);
+        let mut newsapi = NewsAPI::new("key");
+        newsapi.base_url(&server.url);
+        let filters = FilterEngine::new(&[]).unwrap();
+
+        let items = collect(&mut newsapi, &filters, GroupBy::Category).unwrap();
+        assert_eq!(server.requests().len(), Category::ALL.len());
+        let titles: Vec<(&str, Option<&str>)> = items.iter().map(|i| (i.title.as_str(), i.category.as_deref())).collect();
+        assert_eq!(titles, vec![
+            ("Rates rise", Some("Business")),
+            ("Chips", Some("Business")),
+            ("Cup final", Some("General")),
+        ]);
+
+        let markdown = String::from_utf8(render("Digest", items, Format::Markdown, GroupBy::Category)).unwrap();
+        assert!(markdown.contains("## Business\n\n### [Rates rise](https://wire.example/rates)"));
+        assert!(markdown.contains("## General\n\n### [Cup final]"));
+    }
+}
@@ -0,0 +1,131 @@
+//! RSS and Atom feeds of the headlines, written by `clinews feed` and served
+//! by `clinews serve`.
+
+use crate::store::Archived;
+use newsapi::export::Item;
+use newsapi::feed::{Feed, Format};
+use newsapi::filter::FilterEngine;
+use newsapi::{Article, Category, NewsAPI};
+use std::error::Error;
+
+/// A category by its newsapi name, in any case.
+pub fn parse_category(name: &str) -> Result<Category, String> {
+    Category::ALL
+        .iter()
+        .find(|c| c.to_string() == name.to_lowercase())
+        .copied()
+        .ok_or_else(|| format!("unknown category {:?}", name))
+}
+
+/// Whether every word of `query` is in the title or description.
+pub fn matches(article: &Article, query: Option<&str>) -> bool {
+    let query = match query {
+        Some(query) => query.to_lowercase(),
+        None => return true,
+    };
+    let text = format!(
+        "{}\n{}",
+        article.title(),
+        article.description().map(|s| s.as_str()).unwrap_or_default()
+    ).to_lowercase();
+    query.split_whitespace().all(|w| text.contains(w))
+}
+
+/// "Headlines", narrowed down by category and query.
+pub fn title(category: Option<Category>, query: Option<&str>) -> String {
+    let mut title = "Headlines".to_string();
+    if let Some(category) = category {
+        title.push_str(&format!(": {}", category.name()));
+    }
+    if let Some(query) = query {
+        title.push_str(&format!(" matching \"{}\"", query));
+    }
+    title
+}
+
+/// A feed of `articles`, which are all in `category` if it's given, updated
+/// at `updated` (Unix time), or now.
+pub fn render(
+    articles: &[Article],
+    category: Option<Category>,
+    query: Option<&str>,
+    format: Format,
+    self_link: Option<&str>,
+    updated: Option<i64>
+) -> String {
+    let items = articles.iter().map(|a| Item::from_article(a, category.map(|c| c.name()))).collect();
+    let mut feed = Feed::new(&title(category, query), items);
+    feed.self_link(self_link);
+    if let Some(updated) = updated {
+        feed.updated(updated.max(0) as u64);
+    }
+    if let Some(link) = self_link {
+        feed.link(link);
+    }
+    feed.render(format)
+}
+
+/// The current headlines of `category`, filtered and matching `query`.
+pub fn fetch(
+    newsapi: &mut NewsAPI,
+    filters: &FilterEngine,
+    category: Option<Category>,
+    query: Option<&str>
+) -> Result<Vec<Article>, Box<dyn Error>> {
+    let mut response = newsapi.category(category).fetch()?;
+    filters.apply(&mut response.articles);
+    response.articles.retain(|a| matches(a, query));
+    Ok(response.articles)
+}
+
+/// The archived articles first seen after `since` (Unix time) that pass the
+/// filters and match `query`, newest first.
+pub fn from_archive(archive: Vec<Archived>, filters: &FilterEngine, since: i64, query: Option<&str>) -> Vec<Article> {
+    let mut articles: Vec<Article> = archive
+        .into_iter()
+        .rev()
+        .filter(|a| a.seen_at >= since)
+        .map(|a| a.article)
+        .filter(|a| matches(a, query))
+        .collect();
+    filters.apply(&mut articles);
+    articles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use newsapi::filter::{Action, FilterRule, Matcher};
+
+    fn archived(title: &str, seen_at: i64) -> Archived {
+        let article = serde_json::from_value(serde_json::json!({
+            "source": { "id": null, "name": "Wire" },
+            "title": title,
+            "url": format!("https://wire.example/{}", seen_at),
+            "description": "Markets & more"
+        })).unwrap();
+        Archived { seen_at, article }
+    }
+
+    #[test]
+    fn feeds_archived_articles_by_query() {
+        let archive = vec![
+            archived("Old rates news", 10),
+            archived("Rates rise", 100),
+            archived("Gossip about rates", 200),
+            archived("Cup final", 300),
+        ];
+        let rules = vec![FilterRule { name: None, action: Action::Exclude, matcher: Matcher::Keyword("gossip".to_string()) }];
+        let articles = from_archive(archive, &FilterEngine::new(&rules).unwrap(), 50, Some("RATES"));
+        let titles: Vec<&str> = articles.iter().map(|a| a.title()).collect();
+        assert_eq!(titles, vec!["Rates rise"]);
+
+        assert_eq!(title(Some(Category::Business), Some("rates")), "Headlines: Business matching \"rates\"");
+        let atom = render(&articles, Some(Category::Business), Some("rates"), Format::Atom, Some("https://example.com/rates.atom"), None);
+        assert!(atom.contains("<id>https://example.com/rates.atom</id>\n<title>Headlines: Business matching &quot;rates&quot;</title>"));
+        assert!(atom.contains("<category term=\"Business\"/>\n<summary type=\"text\">Markets &amp; more</summary>"));
+        let rss = render(&articles, None, None, Format::Rss, None, Some(1654858800));
+        assert!(rss.contains("<title>Headlines</title>\n<link>https://newsapi.org/</link>"));
+        assert!(rss.contains("<lastBuildDate>Fri, 10 Jun 2022 11:00:00 +0000</lastBuildDate>"));
+    }
+}
@@ -0,0 +1,299 @@
+//! How a listing of articles is laid out in the terminal.
+
+use chrono::{DateTime, Utc};
+use newsapi::Article;
+use serde::Deserialize;
+use termimad::MadSkin;
+use crate::links;
+
+/// Lines never get wider than this, even on very wide terminals.
+const MAX_WIDTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    /// One line per article: number, title, source and age
+    Compact,
+    /// Adds the author, the summary or description and the url
+    #[default]
+    Normal,
+    /// Adds the description and the start of the article text as well
+    Expanded
+}
+
+/// How long ago `published` (RFC 3339) was, like "3h ago". Older articles
+/// get their date instead.
+pub fn relative_time(published: &str, now: DateTime<Utc>) -> Option<String> {
+    let published = DateTime::parse_from_rfc3339(published).ok()?.with_timezone(&Utc);
+    let age = now.signed_duration_since(published);
+    Some(if age.num_minutes() < 1 {
+        "just now".to_string()
+    } else if age.num_hours() < 1 {
+        format!("{}m ago", age.num_minutes())
+    } else if age.num_days() < 1 {
+        format!("{}h ago", age.num_hours())
+    } else if age.num_days() < 7 {
+        format!("{}d ago", age.num_days())
+    } else {
+        published.format("%Y-%m-%d").to_string()
+    })
+}
+
+/// The content the API sends without its `[+1234 chars]` marker.
+pub fn content_snippet(content: &str) -> &str {
+    let content = content.trim_end();
+    let content = match content.rfind("[+") {
+        Some(i) if content.ends_with("chars]") => &content[..i],
+        _ => content,
+    };
+    content.trim_end()
+}
+
+/// Source, age and, unless compact, author, separated by dots.
+fn meta_line(article: &Article, density: Density, now: DateTime<Utc>) -> String {
+    let mut parts = vec![];
+    if !article.source().name().is_empty() {
+        parts.push(article.source().name().to_string());
+    }
+    if let Some(language) = article.language() {
+        parts.push(language.to_uppercase());
+    }
+    if let Some(age) = article.published_at().and_then(|p| relative_time(p, now)) {
+        parts.push(age);
+    }
+    if density != Density::Compact {
+        if let Some(author) = article.author().filter(|a| !a.trim().is_empty()) {
+            parts.push(format!("by {}", author.trim()));
+        }
+    }
+    parts.join(" · ")
+}
+
+/// `text` on one line, with what termimad reads as styling escaped: it
+/// can't start a heading or a quote, nor turn the rest of the line bold.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    if text.starts_with(['#', '>']) {
+        // A zero width space, since termimad only escapes styling marks.
+        escaped.push('\u{200b}');
+    }
+    for c in text.chars() {
+        match c {
+            '\\' | '*' | '~' | '|' | '`' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `title` for inline code, where nothing can be escaped: the backticks
+/// that would end it early become quotes.
+fn code_title(title: &str) -> String {
+    title.replace('`', "'").replace(['\n', '\r'], " ")
+}
+
+/// The markdown for article number `n`, without its url. Translated
+/// articles show their translation.
+fn article_markdown(n: usize, article: &Article, summary: &[String], density: Density, now: DateTime<Utc>) -> String {
+    let (title, description) = match article.translation() {
+        Some(translation) => (translation.title.as_str(), translation.description.as_ref()),
+        None => (article.title(), article.description()),
+    };
+    let title = code_title(title);
+    let meta = escape_markdown(&meta_line(article, density, now));
+    if density == Density::Compact {
+        return match meta.is_empty() {
+            true => format!("**{}.** `{}`", n, title),
+            false => format!("**{}.** `{}` *{}*", n, title, meta),
+        };
+    }
+
+    let mut lines = vec![format!("**{}.** `{}`", n, title)];
+    if !meta.is_empty() {
+        lines.push(format!("*{}*", meta));
+    }
+    let description = description.map(|d| d.trim()).filter(|d| !d.is_empty());
+    match density {
+        Density::Expanded => {
+            lines.extend(description.map(escape_markdown));
+            if !summary.is_empty() {
+                lines.push(escape_markdown(&summary.join(" ")));
+            }
+            let snippet = article.content().map(|c| content_snippet(c)).filter(|c| !c.is_empty());
+            lines.extend(snippet.map(|c| format!("> {}", escape_markdown(c))));
+        },
+        _ => match summary.is_empty() {
+            true => lines.extend(description.map(escape_markdown)),
+            false => lines.push(escape_markdown(&summary.join(" "))),
+        },
+    }
+    lines.join("\n")
+}
+
+/// The `--explain` line under an article.
+fn explanation_markdown(explanation: &str) -> String {
+    format!("`score {}`", explanation)
+}
+
+/// Prints the listing, wrapped to the terminal width.
+pub fn render_articles(
+    theme: &MadSkin,
+    articles: &[Article],
+    summaries: &[Vec<String>],
+    explanations: &[String],
+    density: Density
+) {
+    let width = (termimad::terminal_size().0 as usize).clamp(20, MAX_WIDTH);
+    let hyperlinks = links::supported();
+    let now = Utc::now();
+
+    print!("{}", theme.text("# Top headlines", Some(width)));
+    for (i, a) in articles.iter().enumerate() {
+        let summary = summaries.get(i).map(|s| s.as_slice()).unwrap_or_default();
+        print!("{}", theme.text(&article_markdown(i + 1, a, summary, density, now), Some(width)));
+        if let Some(explanation) = explanations.get(i) {
+            print!("{}", theme.text(&explanation_markdown(explanation), Some(width)));
+        }
+        if density == Density::Compact {
+            continue;
+        }
+        if hyperlinks {
+            println!("{}", links::hyperlink(a.url(), a.url()));
+        } else {
+            print!("{}", theme.text(&format!("> *{}*", a.url()), Some(width)));
+        }
+        print!("{}", theme.text("---", Some(width)));
+    }
+}
+
+/// The listing as markdown for the pager, numbered from `first`. Urls stay
+/// plain text there, the view can't measure link escape sequences.
+pub fn listing_markdown(
+    articles: &[Article],
+    summaries: &[Vec<String>],
+    explanations: &[String],
+    density: Density,
+    first: usize
+) -> String {
+    let now = Utc::now();
+    let mut entries = vec![];
+    if first == 1 {
+        entries.push("# Top headlines".to_string());
+    }
+    for (i, a) in articles.iter().enumerate() {
+        let summary = summaries.get(i).map(|s| s.as_slice()).unwrap_or_default();
+        let mut entry = article_markdown(first + i, a, summary, density, now);
+        if let Some(explanation) = explanations.get(i) {
+            entry.push('\n');
+            entry.push_str(&explanation_markdown(explanation));
+        }
+        if density != Density::Compact {
+            entry.push_str(&format!("\n> *{}*\n---", a.url()));
+        }
+        entries.push(entry);
+    }
+    entries.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use newsapi::language::Translator;
+    use newsapi::NewsAPIError;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2022-06-10T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn formats_relative_times() {
+        assert_eq!(relative_time("2022-06-10T11:59:30Z", now()).as_deref(), Some("just now"));
+        assert_eq!(relative_time("2022-06-10T11:15:00Z", now()).as_deref(), Some("45m ago"));
+        assert_eq!(relative_time("2022-06-10T09:00:00Z", now()).as_deref(), Some("3h ago"));
+        assert_eq!(relative_time("2022-06-10T13:00:00+02:00", now()).as_deref(), Some("1h ago"));
+        assert_eq!(relative_time("2022-06-08T12:00:00Z", now()).as_deref(), Some("2d ago"));
+        assert_eq!(relative_time("2022-05-01T12:00:00Z", now()).as_deref(), Some("2022-05-01"));
+        assert_eq!(relative_time("2022-06-10T12:30:00Z", now()).as_deref(), Some("just now"));
+        assert_eq!(relative_time("yesterday", now()), None);
+    }
+
+    #[test]
+    fn strips_the_truncation_marker() {
+        assert_eq!(content_snippet("The council met on Tuesday… [+2817 chars]"), "The council met on Tuesday…");
+        assert_eq!(content_snippet("Nothing cut off"), "Nothing cut off");
+    }
+
+    #[test]
+    fn densities_show_more_or_less() {
+        let article: Article = serde_json::from_str(r#"{
+            "source": {"id": null, "name": "The Gazette"},
+            "author": "Ada Lovelace",
+            "title": "Bridge reopens",
+            "url": "https://gazette.example/bridge",
+            "description": "Traffic is back on the old bridge.",
+            "publishedAt": "2022-06-10T09:00:00Z",
+            "content": "After two years of repairs the bridge reopened… [+1200 chars]"
+        }"#).unwrap();
+        let summary = vec!["The bridge reopened after repairs.".to_string()];
+
+        assert_eq!(
+            article_markdown(1, &article, &summary, Density::Compact, now()),
+            "**1.** `Bridge reopens` *The Gazette · EN · 3h ago*"
+        );
+        assert_eq!(
+            article_markdown(1, &article, &[], Density::Normal, now()),
+            "**1.** `Bridge reopens`\n*The Gazette · EN · 3h ago · by Ada Lovelace*\nTraffic is back on the old bridge."
+        );
+        assert_eq!(
+            article_markdown(1, &article, &summary, Density::Normal, now()),
+            "**1.** `Bridge reopens`\n*The Gazette · EN · 3h ago · by Ada Lovelace*\nThe bridge reopened after repairs."
+        );
+        assert_eq!(
+            article_markdown(1, &article, &summary, Density::Expanded, now()),
+            "**1.** `Bridge reopens`\n*The Gazette · EN · 3h ago · by Ada Lovelace*\n\
+             Traffic is back on the old bridge.\nThe bridge reopened after repairs.\n\
+             > After two years of repairs the bridge reopened…"
+        );
+    }
+
+    #[test]
+    fn escapes_markdown_in_articles() {
+        let article: Article = serde_json::from_str(r##"{
+            "source": {"id": null, "name": "*Wire*"},
+            "title": "`rm -rf` | *Markets* [live] # 5",
+            "url": "https://wire.example/markets",
+            "description": "# Stocks **soar**\n> or | sink"
+        }"##).unwrap();
+        assert_eq!(
+            article_markdown(1, &article, &[], Density::Normal, now()),
+            "**1.** `'rm -rf' | *Markets* [live] # 5`\n*\\*Wire\\**\n\u{200b}# Stocks \\*\\*soar\\*\\* > or \\| sink"
+        );
+    }
+
+    struct Shouting;
+
+    impl Translator for Shouting {
+        fn translate(&self, text: &str, _from: Option<&str>, _to: &str) -> Result<String, NewsAPIError> {
+            Ok(text.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn translations_replace_the_title_and_description() {
+        let mut article: Article = serde_json::from_str(r#"{
+            "source": {"id": null, "name": "Le Monde"},
+            "title": "Le pont rouvre",
+            "url": "https://lemonde.example/pont",
+            "description": "La circulation reprend sur le vieux pont."
+        }"#).unwrap();
+        article.translate(&Shouting, "en").unwrap();
+        assert_eq!(
+            article_markdown(1, &article, &[], Density::Normal, now()),
+            "**1.** `LE PONT ROUVRE`\n*Le Monde · FR*\nLA CIRCULATION REPREND SUR LE VIEUX PONT."
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Clickable links, and handing urls over to the browser or the clipboard.
+
+use crossterm::tty::IsTty;
+use std::error::Error;
+use std::process::{Command, Stdio};
+
+/// Whether stdout is a terminal known to understand OSC 8 hyperlinks.
+/// `FORCE_HYPERLINK=1` or `=0` overrides the guess.
+pub fn supported() -> bool {
+    let var = |name: &str| std::env::var(name).ok();
+    std::io::stdout().is_tty() && supported_by(&var)
+}
+
+fn supported_by(var: &dyn Fn(&str) -> Option<String>) -> bool {
+    if let Some(force) = var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    if var("TERM").as_deref() == Some("dumb") {
+        return false;
+    }
+    if var("DOMTERM").is_some() || var("KITTY_WINDOW_ID").is_some() || var("WT_SESSION").is_some() {
+        return true;
+    }
+    if let Some(program) = var("TERM_PROGRAM") {
+        if ["iTerm.app", "WezTerm", "vscode", "Hyper"].contains(&program.as_str()) {
+            return true;
+        }
+    }
+    // VTE based terminals (GNOME Terminal, Tilix, ...) since 0.50.
+    if let Some(version) = var("VTE_VERSION").and_then(|v| v.parse::<u32>().ok()) {
+        return version >= 5000;
+    }
+    var("TERM").map(|t| t.contains("kitty") || t.contains("alacritty") || t.contains("foot")).unwrap_or(false)
+}
+
+/// `text` linking to `url`, as an OSC 8 escape sequence. A `url` that
+/// doesn't parse is left out, and control characters in one that does come
+/// out percent-encoded, so it can't end the sequence early.
+pub fn hyperlink(url: &str, text: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(url) => format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text),
+        Err(_) => text.to_string(),
+    }
+}
+
+/// The command opening `url`: the first entry of `$BROWSER` (where `%s`
+/// stands for the url), or the platform's opener.
+fn browser_command(browser: Option<&str>, url: &str) -> (String, Vec<String>) {
+    if let Some(browser) = browser.and_then(|b| b.split(':').find(|b| !b.trim().is_empty())) {
+        let mut parts = browser.split_whitespace().map(|p| p.replace("%s", url));
+        let program = parts.next().unwrap_or_default();
+        let mut args: Vec<String> = parts.collect();
+        if !browser.contains("%s") {
+            args.push(url.to_string());
+        }
+        return (program, args);
+    }
+
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    (program.to_string(), vec![url.to_string()])
+}
+
+pub fn open_in_browser(url: &str) -> Result<(), Box<dyn Error>> {
+    let browser = std::env::var("BROWSER").ok();
+    let (program, args) = browser_command(browser.as_deref(), url);
+    Command::new(&program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", program, e))?;
+    Ok(())
+}
+
+/// Puts `text` on the clipboard. On Linux the clipboard belongs to the
+/// process that set it, so the usual command line tools are tried first:
+/// they stay around to serve it after clinews exits.
+pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn Error>> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::io::Write;
+        for (program, args) in [
+            ("wl-copy", &[][..]),
+            ("xclip", &["-selection", "clipboard"][..]),
+            ("xsel", &["--clipboard", "--input"][..]),
+        ] {
+            let child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            if let Ok(mut child) = child {
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(text.as_bytes())?;
+                }
+                if child.wait()?.success() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    arboard::Clipboard::new()?.set_text(text.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn detects_hyperlink_support() {
+        assert!(supported_by(&env(&[("TERM_PROGRAM", "iTerm.app")])));
+        assert!(supported_by(&env(&[("VTE_VERSION", "6003")])));
+        assert!(!supported_by(&env(&[("VTE_VERSION", "4802")])));
+        assert!(!supported_by(&env(&[("TERM", "xterm-256color")])));
+        assert!(supported_by(&env(&[("TERM", "xterm"), ("FORCE_HYPERLINK", "1")])));
+        assert!(!supported_by(&env(&[("TERM_PROGRAM", "WezTerm"), ("FORCE_HYPERLINK", "0")])));
+    }
+
+    #[test]
+    fn formats_osc8_links() {
+        assert_eq!(hyperlink("https://a.example/", "a"), "\x1b]8;;https://a.example/\x1b\\a\x1b]8;;\x1b\\");
+    }
+
+    #[test]
+    fn keeps_escapes_out_of_links() {
+        assert_eq!(
+            hyperlink("https://a.example/x\x1b]8;;https://b.example\x07/y", "a"),
+            "\x1b]8;;https://a.example/x%1B]8;;https://b.example%07/y\x1b\\a\x1b]8;;\x1b\\"
+        );
+        assert_eq!(hyperlink("not a url", "a"), "a");
+    }
+
+    #[test]
+    fn uses_browser_variable() {
+        let url = "https://a.example/x";
+        assert_eq!(browser_command(Some("firefox"), url), ("firefox".to_string(), vec![url.to_string()]));
+        assert_eq!(
+            browser_command(Some("chromium --incognito %s:firefox"), url),
+            ("chromium".to_string(), vec!["--incognito".to_string(), url.to_string()])
+        );
+        assert_eq!(browser_command(Some(""), url).1, vec![url.to_string()]);
+    }
+}
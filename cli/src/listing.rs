@@ -0,0 +1,271 @@
+//! The top headlines listing, fetched one API page at a time.
+
+use newsapi::{ Article, NewsAPI };
+use newsapi::filter::{ FilterEngine, FilterReport };
+use newsapi::language::Translator;
+use newsapi::rank::{ Candidate, Ranker, SortMode };
+use newsapi::{ extract, summary };
+use std::error::Error;
+use std::ops::Range;
+
+/// Translation requests sent at once.
+const TRANSLATIONS_IN_FLIGHT: usize = 8;
+
+/// Pages downloaded at once for summaries.
+const SUMMARIES_IN_FLIGHT: usize = 8;
+
+pub struct Listing<'a> {
+    newsapi: NewsAPI,
+    filters: &'a FilterEngine,
+    sentences: usize,
+    /// Translator and the language to translate foreign articles into.
+    translation: Option<(Box<dyn Translator>, String)>,
+    ranker: Option<Ranker<'a>>,
+    sort: SortMode,
+    explain: bool,
+    /// Next page to fetch, starting at 1.
+    page: u32,
+    /// Articles received so far, hidden ones included.
+    received: u32,
+    total: Option<u32>,
+    pub articles: Vec<Article>,
+    /// One per article, empty when summaries are turned off.
+    pub summaries: Vec<Vec<String>>,
+    /// How each article scored, when asked for.
+    pub explanations: Vec<String>,
+    pub report: FilterReport,
+    /// Why translating stopped, if it did.
+    pub translation_error: Option<String>
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(newsapi: NewsAPI, filters: &'a FilterEngine, sentences: usize) -> Listing<'a> {
+        Listing {
+            newsapi,
+            filters,
+            sentences,
+            translation: None,
+            ranker: None,
+            sort: SortMode::Api,
+            explain: false,
+            page: 1,
+            received: 0,
+            total: None,
+            articles: vec![],
+            summaries: vec![],
+            explanations: vec![],
+            report: FilterReport::default(),
+            translation_error: None
+        }
+    }
+
+    /// Translates the title and description of articles in another language
+    /// than `target`.
+    pub fn translate_to(&mut self, translator: Box<dyn Translator>, target: &str) -> &mut Self {
+        self.translation = Some((translator, target.to_string()));
+        self
+    }
+
+    /// Orders every page by `sort`, and explains the scores of `ranker` if
+    /// `explain` is set.
+    pub fn sort(&mut self, ranker: Ranker<'a>, sort: SortMode, explain: bool) -> &mut Self {
+        self.ranker = Some(ranker);
+        self.sort = sort;
+        self.explain = explain;
+        self
+    }
+
+    /// Whether the API has articles beyond the pages fetched so far.
+    pub fn has_more(&self) -> bool {
+        self.total.map(|total| self.received < total).unwrap_or(true)
+    }
+
+    /// Fetches, filters and summarises the next page, and returns where its
+    /// articles ended up in `articles`. Blocks until the page and every
+    /// summary are in, so the pager can call it between key presses.
+    pub fn next_page(&mut self) -> Result<Range<usize>, Box<dyn Error>> {
+        let mut response = self.newsapi.page(self.page).fetch()?;
+        self.page += 1;
+        self.received += response.articles().len() as u32;
+        self.total = Some(match response.articles().is_empty() {
+            true => self.received,
+            false => response.total_results(),
+        });
+
+        let report = self.filters.apply(&mut response.articles);
+        self.report.merge(&report);
+        self.translate(&mut response.articles);
+        let mut explanations = self.rank(&mut response.articles);
+
+        let mut summaries = summarize_articles(response.articles(), self.sentences);
+        summaries.resize(response.articles().len(), vec![]);
+
+        let start = self.articles.len();
+        self.articles.append(&mut response.articles);
+        self.summaries.append(&mut summaries);
+        self.explanations.append(&mut explanations);
+        Ok(start..self.articles.len())
+    }
+
+    /// Puts `articles` in order, and returns their explanations.
+    fn rank(&self, articles: &mut Vec<Article>) -> Vec<String> {
+        let ranker = match &self.ranker {
+            Some(ranker) => ranker,
+            None => return vec![],
+        };
+        let candidates: Vec<Candidate> = articles.iter().map(Candidate::from).collect();
+        let order = ranker.order(&candidates, self.sort);
+        let explanations = match self.explain {
+            true => {
+                let scores = ranker.scores(&candidates);
+                order.iter().map(|&i| scores[i].explain(ranker.weights())).collect()
+            },
+            false => vec![],
+        };
+        *articles = order.iter().map(|&i| articles[i].clone()).collect();
+        explanations
+    }
+
+    /// Translates a few articles at a time. Stops after the first failure,
+    /// since the next ones would most likely fail the same way.
+    fn translate(&mut self, articles: &mut [Article]) {
+        let (translator, target) = match (&self.translation, &self.translation_error) {
+            (Some(translation), None) => translation,
+            _ => return,
+        };
+        for batch in articles.chunks_mut(TRANSLATIONS_IN_FLIGHT) {
+            let error = std::thread::scope(|scope| {
+                let threads: Vec<_> = batch
+                    .iter_mut()
+                    .map(|article| scope.spawn(|| article.translate(translator.as_ref(), target)))
+                    .collect();
+                threads.into_iter().find_map(|t| t.join().ok()?.err())
+            });
+            if let Some(e) = error {
+                self.translation_error = Some(e.to_string());
+                return;
+            }
+        }
+    }
+}
+
+/// Summarises every article from its full text, downloading a few pages at
+/// a time. Articles whose page can't be read are summarised from their
+/// description instead.
+fn summarize_articles(articles: &[Article], sentences: usize) -> Vec<Vec<String>> {
+    if sentences == 0 {
+        return vec![];
+    }
+
+    let mut summaries = Vec::with_capacity(articles.len());
+    for batch in articles.chunks(SUMMARIES_IN_FLIGHT) {
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = batch
+                .iter()
+                .map(|a| scope.spawn(move || {
+                    let description = a.description().cloned().unwrap_or_default();
+                    let text = extract::fetch(a.url()).map(|doc| doc.text()).unwrap_or(description);
+                    summary::summarize(&text, sentences)
+                }))
+                .collect();
+            summaries.extend(threads.into_iter().map(|t| t.join().unwrap_or_default()));
+        });
+    }
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{ articles_json, MockServer };
+    use newsapi::language::LibreTranslate;
+    use newsapi::rank::Preferences;
+
+    fn page_json(total: u32, articles: &[(&str, &str)]) -> String {
+        articles_json(articles).replacen(
+            &format!(r#""totalResults":{}"#, articles.len()),
+            &format!(r#""totalResults":{}"#, total),
+            1
+        )
+    }
+
+    #[test]
+    fn fetches_pages_until_the_total_is_reached() {
+        let server = MockServer::start(vec![
+            (200, page_json(3, &[("One", "https://a.example/1"), ("Two", "https://a.example/2")])),
+            (200, page_json(3, &[("Three", "https://a.example/3")])),
+        ]);
+        let mut newsapi = NewsAPI::new("key");
+        newsapi.base_url(&server.url).page_size(2);
+        let filters = FilterEngine::new(&[]).unwrap();
+        let mut listing = Listing::new(newsapi, &filters, 0);
+
+        assert!(listing.has_more());
+        assert_eq!(listing.next_page().unwrap(), 0..2);
+        assert!(listing.has_more());
+        assert_eq!(listing.next_page().unwrap(), 2..3);
+        assert!(!listing.has_more());
+
+        assert_eq!(listing.articles[2].title(), "Three");
+        assert_eq!(listing.summaries.len(), 3);
+        let requests = server.requests();
+        assert!(requests[0].path.ends_with("pageSize=2&page=1"));
+        assert!(requests[1].path.ends_with("pageSize=2&page=2"));
+    }
+
+    #[test]
+    fn sorts_and_explains_pages() {
+        let server = MockServer::start(vec![(200, articles_json(&[
+            ("Cup final tonight", "https://a.example/1"),
+            ("Rust 2.0 released", "https://a.example/2"),
+        ]))]);
+        let mut newsapi = NewsAPI::new("key");
+        newsapi.base_url(&server.url);
+        let filters = FilterEngine::new(&[]).unwrap();
+        let preferences = Preferences { keywords: vec!["rust".to_string()], ..Preferences::default() };
+        let mut listing = Listing::new(newsapi, &filters, 0);
+        listing.sort(Ranker::new(&preferences, &[], 0), SortMode::ForYou, true);
+
+        listing.next_page().unwrap();
+        let titles: Vec<&str> = listing.articles.iter().map(|a| a.title()).collect();
+        assert_eq!(titles, vec!["Rust 2.0 released", "Cup final tonight"]);
+        assert_eq!(
+            listing.explanations[0],
+            "0.50 = recency 0.00×1 + source 0.00×1 + keywords 0.33×1.5 + clicks 0.00×1 + cluster 0.00×0.5"
+        );
+        assert_eq!(listing.explanations.len(), 2);
+    }
+
+    #[test]
+    fn translates_foreign_articles() {
+        let server = MockServer::start(vec![(200, articles_json(&[
+            ("Le gouvernement annonce une baisse des impôts", "https://a.example/1"),
+            ("The government cuts taxes", "https://a.example/2"),
+        ]))]);
+        let libretranslate = MockServer::start(vec![
+            (200, r#"{"translatedText": "The government announces a tax cut"}"#.to_string()),
+            (500, r#"{"error": "overloaded"}"#.to_string()),
+        ]);
+        let mut newsapi = NewsAPI::new("key");
+        newsapi.base_url(&server.url);
+        let filters = FilterEngine::new(&[]).unwrap();
+        let mut listing = Listing::new(newsapi, &filters, 0);
+        listing.translate_to(Box::new(LibreTranslate::new(&libretranslate.url)), "en");
+
+        listing.next_page().unwrap();
+        let translated: Vec<Option<&str>> = listing.articles.iter().map(|a| a.translation().map(|t| t.title.as_str())).collect();
+        assert_eq!(translated, vec![Some("The government announces a tax cut"), None]);
+        assert_eq!(listing.articles[0].title(), "Le gouvernement annonce une baisse des impôts");
+        assert!(listing.translation_error.is_none());
+        let requests = libretranslate.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/translate");
+        assert!(requests[0].body.contains(r#""source":"fr""#));
+
+        // A failure is kept, and translating stops.
+        listing.next_page().unwrap();
+        assert_eq!(listing.translation_error.as_deref(), Some("Translation failed: overloaded"));
+        listing.next_page().unwrap();
+        assert_eq!(libretranslate.requests().len(), 2);
+    }
+}
@@ -0,0 +1,319 @@
+//! Email: multipart messages with a text and an HTML part, and a small SMTP
+//! client to send them with.
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Message {
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: String,
+    pub html: String
+}
+
+impl Message {
+    /// The message in MIME format, with CRLF line endings.
+    pub fn to_mime(&self, date: DateTime<Local>) -> String {
+        let boundary = format!("clinews-{:x}-{:x}", date.timestamp(), std::process::id());
+        let mut out = String::new();
+        for (name, value) in [
+            ("From", self.from.to_string()),
+            ("To", self.to.join(", ")),
+            ("Subject", encode_header(&self.subject)),
+            ("Date", date.to_rfc2822()),
+            ("Message-ID", format!("<{}@clinews>", boundary)),
+            ("MIME-Version", "1.0".to_string()),
+            ("Content-Type", format!("multipart/alternative; boundary=\"{}\"", boundary)),
+        ] {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        out.push_str("\r\n");
+
+        for (content_type, body) in [("text/plain", &self.text), ("text/html", &self.html)] {
+            out.push_str(&format!(
+                "--{}\r\nContent-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n",
+                boundary,
+                content_type
+            ));
+            out.push_str(&quoted_printable(body));
+        }
+        out.push_str(&format!("--{}--\r\n", boundary));
+        out
+    }
+}
+
+/// RFC 2047 encoding for header values that aren't plain ASCII.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", base64::encode(value))
+    }
+}
+
+/// Quoted-printable encoding, with lines kept under 76 characters.
+fn quoted_printable(text: &str) -> String {
+    let mut out = String::new();
+    for line in text.lines() {
+        let bytes = line.as_bytes();
+        let mut width = 0;
+        for (i, &b) in bytes.iter().enumerate() {
+            let last = i + 1 == bytes.len();
+            let encoded = match b {
+                // Trailing whitespace would get stripped on the way.
+                b' ' | b'\t' if !last => (b as char).to_string(),
+                b'=' => "=3D".to_string(),
+                b'!'..=b'~' => (b as char).to_string(),
+                _ => format!("={:02X}", b),
+            };
+            if width + encoded.len() > 75 {
+                out.push_str("=\r\n");
+                width = 0;
+            }
+            out.push_str(&encoded);
+            width += encoded.len();
+        }
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// `name@host` out of `Some Name <name@host>`.
+fn address(mailbox: &str) -> &str {
+    match (mailbox.find('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encryption {
+    /// Plain text, only for servers on the local machine or network. The
+    /// password is only sent to servers on the local machine.
+    None,
+    /// Upgrade the connection with STARTTLS, usually on port 587.
+    StartTls,
+    /// TLS from the start, usually on port 465.
+    Tls
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_tls")]
+    pub tls: Encryption,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Environment variable to read the password from instead, to keep it
+    /// out of the config file.
+    pub password_env: Option<String>
+}
+
+fn default_port() -> u16 {
+    587
+}
+
+fn default_tls() -> Encryption {
+    Encryption::StartTls
+}
+
+impl SmtpConfig {
+    fn password(&self) -> Option<String> {
+        match &self.password_env {
+            Some(var) => std::env::var(var).ok(),
+            None => self.password.clone(),
+        }
+    }
+}
+
+/// Sends `message`, already in MIME format, to every recipient of `envelope`.
+pub fn send(config: &SmtpConfig, envelope: &Message, message: &str) -> Result<(), Box<dyn Error>> {
+    let addrs: Vec<SocketAddr> = (config.host.as_str(), config.port).to_socket_addrs()?.collect();
+    if config.tls == Encryption::None && config.username.is_some() && !addrs.iter().all(|a| a.ip().is_loopback()) {
+        return Err(format!(
+            "refusing to send the SMTP password to {} in plain text, set tls to \"starttls\" or \"tls\"",
+            config.host
+        ).into());
+    }
+    let tcp = TcpStream::connect(&addrs[..])?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+
+    match config.tls {
+        Encryption::None => {
+            let mut smtp = Smtp::greet(tcp)?;
+            smtp.deliver(config, envelope, message)
+        },
+        Encryption::StartTls => {
+            let mut smtp = Smtp::greet(tcp)?;
+            smtp.command("STARTTLS", 220)?;
+            let tls = native_tls::TlsConnector::new()?.connect(&config.host, smtp.stream)?;
+            let mut smtp = Smtp { stream: tls };
+            smtp.command("EHLO clinews", 250)?;
+            smtp.deliver(config, envelope, message)
+        },
+        Encryption::Tls => {
+            let tls = native_tls::TlsConnector::new()?.connect(&config.host, tcp)?;
+            let mut smtp = Smtp::greet(tls)?;
+            smtp.deliver(config, envelope, message)
+        },
+    }
+}
+
+struct Smtp<S: Read + Write> {
+    stream: S
+}
+
+impl<S: Read + Write> Smtp<S> {
+    /// Waits for the server's greeting and introduces ourselves.
+    fn greet(stream: S) -> Result<Smtp<S>, Box<dyn Error>> {
+        let mut smtp = Smtp { stream };
+        smtp.expect(220)?;
+        smtp.command("EHLO clinews", 250)?;
+        Ok(smtp)
+    }
+
+    /// Reads a possibly multi-line reply and checks its code.
+    fn expect(&mut self, code: u16) -> Result<(), Box<dyn Error>> {
+        let mut lines = vec![];
+        loop {
+            let mut line = vec![];
+            let mut byte = [0];
+            while self.stream.read(&mut byte)? == 1 && byte[0] != b'\n' {
+                line.push(byte[0]);
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            if line.is_empty() {
+                return Err("the SMTP server closed the connection".into());
+            }
+            let done = line.as_bytes().get(3) != Some(&b'-');
+            lines.push(line);
+            if done {
+                break;
+            }
+        }
+
+        let last = lines.last().unwrap();
+        if last.get(..3) == Some(code.to_string().as_str()) {
+            Ok(())
+        } else {
+            Err(format!("the SMTP server answered: {}", lines.join(" / ")).into())
+        }
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> Result<(), Box<dyn Error>> {
+        write!(self.stream, "{}\r\n", command)?;
+        self.stream.flush()?;
+        self.expect(code)
+    }
+
+    fn deliver(&mut self, config: &SmtpConfig, envelope: &Message, message: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(username) = &config.username {
+            let password = config.password().ok_or("no SMTP password configured")?;
+            let credentials = base64::encode(format!("\0{}\0{}", username, password));
+            self.command(&format!("AUTH PLAIN {}", credentials), 235)?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", address(&envelope.from)), 250)?;
+        for to in &envelope.to {
+            self.command(&format!("RCPT TO:<{}>", address(to)), 250)?;
+        }
+        self.command("DATA", 354)?;
+        for line in message.lines() {
+            // Lines starting with a dot get another one, or the server would
+            // take a lone "." for the end of the message.
+            if line.starts_with('.') {
+                self.stream.write_all(b".")?;
+            }
+            write!(self.stream, "{}\r\n", line)?;
+        }
+        self.command(".", 250)?;
+        let _ = self.command("QUIT", 221);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::SmtpServer;
+    use chrono::TimeZone;
+
+    fn message() -> Message {
+        Message {
+            from: "clinews <news@example.com>".to_string(),
+            to: vec!["team@example.com".to_string(), "Ada <ada@example.com>".to_string()],
+            subject: "Headlines – Friday".to_string(),
+            text: "1. Rates rise\n.hidden dot\n".to_string(),
+            html: "<p style=\"margin:0\">Rates rise</p>".to_string()
+        }
+    }
+
+    #[test]
+    fn encodes_quoted_printable() {
+        assert_eq!(quoted_printable("café = 1 \nok"), "caf=C3=A9 =3D 1=20\r\nok\r\n");
+        let long = quoted_printable(&"x".repeat(100));
+        assert!(long.lines().all(|l| l.len() <= 76));
+        assert_eq!(long.replace("=\r\n", "").trim_end(), "x".repeat(100));
+    }
+
+    #[test]
+    fn builds_multipart_messages() {
+        let date = Local.timestamp_opt(1_654_858_800, 0).unwrap();
+        let mime = message().to_mime(date);
+        assert!(mime.contains("To: team@example.com, Ada <ada@example.com>\r\n"));
+        assert!(mime.contains("Subject: =?utf-8?B?SGVhZGxpbmVzIOKAkyBGcmlkYXk=?=\r\n"));
+        assert!(mime.contains("Content-Type: multipart/alternative; boundary=\"clinews-"));
+        assert!(mime.contains("Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n1. Rates rise\r\n"));
+        assert!(mime.contains("<p style=3D\"margin:0\">Rates rise</p>"));
+        assert!(mime.trim_end().ends_with("--"));
+    }
+
+    #[test]
+    fn sends_over_smtp() {
+        let server = SmtpServer::start();
+        let config = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: server.port,
+            tls: Encryption::None,
+            username: Some("bot".to_string()),
+            password: Some("secret".to_string()),
+            password_env: None
+        };
+        let message = message();
+        let mime = message.to_mime(Local::now());
+        send(&config, &message, &mime).unwrap();
+
+        let mail = server.mail();
+        assert_eq!(mail.len(), 1);
+        assert_eq!(mail[0].auth.as_deref(), Some(base64::encode("\0bot\0secret").as_str()));
+        assert_eq!(mail[0].from, "news@example.com");
+        assert_eq!(mail[0].to, vec!["team@example.com", "ada@example.com"]);
+        // Dot-stuffing is undone by the server.
+        assert_eq!(mail[0].data.replace("\r\n", "\n"), mime.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn keeps_passwords_off_plain_remote_connections() {
+        let config = SmtpConfig {
+            host: "192.0.2.1".to_string(),
+            port: 25,
+            tls: Encryption::None,
+            username: Some("bot".to_string()),
+            password: Some("secret".to_string()),
+            password_env: None
+        };
+        let message = message();
+        let error = send(&config, &message, &message.to_mime(Local::now())).unwrap_err();
+        assert!(error.to_string().starts_with("refusing to send the SMTP password to 192.0.2.1"));
+    }
+}
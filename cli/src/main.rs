@@ -1,21 +1,223 @@
+mod config;
+mod digest;
+mod export;
+mod feed;
+mod layout;
+mod listing;
+mod links;
+mod mail;
+mod pager;
+mod serve;
+mod store;
+#[cfg(test)]
+mod testutil;
 mod theme;
+mod trends;
+mod watch;
+mod webhook;
 
 use std::error::Error;
+use clap::{ Parser, Subcommand };
 use dotenv::dotenv;
-use newsapi::{ NewsAPIResponse, NewsAPI, Endpoint, Country, Article };
+use newsapi::{ NewsAPIResponse, NewsAPI, Endpoint, Country };
+use newsapi::filter::{ Action, FilterEngine, FilterReport, FilterRule, Matcher };
+use newsapi::extract;
+use newsapi::export::{ Format, GroupBy };
+use newsapi::rank::{ Ranker, SortMode };
+use newsapi::sentiment::Tone;
+use std::io::Write;
+use std::path::PathBuf;
+use termimad::MadSkin;
 
-async fn render_articles(articles: &Vec<Article>) {
+fn render_filter_report(theme: &MadSkin, report: &FilterReport) {
+    if report.total() == 0 {
+        return;
+    }
 
-    let theme = theme::default();
+    for (rule, hidden) in &report.hidden {
+        if *hidden > 0 {
+            theme.print_text(&format!("*{} hidden by* `{}`", hidden, rule));
+        }
+    }
+    if report.unmatched > 0 {
+        theme.print_text(&format!("*{} hidden by no include rule matching*", report.unmatched));
+    }
+}
 
-    theme.print_text("# Top headlines");
+/// Remembers the listing for `clinews open` and `clinews read`, and
+/// archives it for `clinews digest`.
+fn remember(articles: &[newsapi::Article]) {
+    let result = store::Store::open().and_then(|store| {
+        store.save_last(articles)?;
+        store.record(articles, chrono::Utc::now().timestamp())
+    });
+    if let Err(e) = result {
+        eprintln!("Could not remember the articles for clinews open: {}", e);
+    }
+}
 
-    for a in articles {
-        theme.print_text(&format!("`{}`", a.title()));
-        theme.print_text(&format!("> *{}*", a.url()));
-        theme.print_text("---")
+/// Remembers that the reader opened `article`, for the "for you" ranking.
+fn clicked(store: &store::Store, article: &newsapi::Article) {
+    if let Err(e) = store.record_click(article, chrono::Utc::now().timestamp()) {
+        eprintln!("Could not remember the opened article: {}", e);
     }
+}
 
+/// Resolves the argument of `clinews read`: either a url, or the number of
+/// an article in the last listing.
+fn article_url(article: &str) -> Result<String, Box<dyn Error>> {
+    match article.parse::<usize>() {
+        Ok(n) => {
+            let store = store::Store::open()?;
+            let article = store.last_article(n)?;
+            clicked(&store, &article);
+            Ok(article.url().to_string())
+        },
+        Err(_) => Ok(article.to_string()),
+    }
+}
+
+#[derive(Parser)]
+#[clap(version, about = "Top headlines in your terminal")]
+struct Cli {
+    /// Sentences in the summary under each headline, 0 to turn them off.
+    /// Overrides `summary.sentences`
+    #[clap(long)]
+    summary: Option<usize>,
+
+    /// Skin to render with, overrides `theme`
+    #[clap(long)]
+    theme: Option<String>,
+
+    /// How much of each article to show, overrides `density`
+    #[clap(long, value_enum)]
+    density: Option<layout::Density>,
+
+    /// Articles fetched at a time, 1 to 100. In a terminal, scrolling past
+    /// the end fetches the next ones
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..=100))]
+    limit: Option<u32>,
+
+    /// Translate the headlines in other languages into `translate.target`
+    #[clap(long)]
+    translate: bool,
+
+    /// api, newest or for-you, overrides `sort`
+    #[clap(long)]
+    sort: Option<SortMode>,
+
+    /// Show how each article scores in the "for you" ranking
+    #[clap(long)]
+    explain: bool,
+
+    /// Only list positive, negative or neutral headlines
+    #[clap(long)]
+    sentiment: Option<Tone>,
+
+    #[clap(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Keep polling for news and send a desktop notification when a new
+    /// article matches one of the configured alerts
+    Watch {
+        /// Seconds between polls, overrides `watch.interval_secs`
+        #[clap(long)]
+        interval: Option<u64>
+    },
+    /// Read the full text of an article in the terminal
+    Read {
+        /// Number of the article in the last listing, or its url
+        article: String
+    },
+    /// Open an article of the last listing in the browser
+    Open {
+        /// Number of the article in the last listing
+        n: usize,
+        /// Copy the url to the clipboard instead
+        #[clap(long)]
+        copy: bool
+    },
+    /// Write the top headlines into a reading digest
+    Export {
+        /// markdown, html, epub or json
+        #[clap(long, default_value = "markdown")]
+        format: Format,
+        /// Group the articles by source or category
+        #[clap(long, default_value = "source")]
+        group: GroupBy,
+        /// Include the full text of every article
+        #[clap(long)]
+        full_text: bool,
+        /// Title of the digest, "Headlines" and the date by default
+        #[clap(long)]
+        title: Option<String>,
+        /// File to write to, standard output by default
+        #[clap(short, long)]
+        output: Option<PathBuf>
+    },
+    /// Mail a digest of the stories collected by earlier runs and by
+    /// clinews watch
+    Digest {
+        /// How far back to look, like 24h, 90m or 7d
+        #[clap(long, default_value = "24h", value_parser = digest::parse_since)]
+        since: chrono::Duration,
+        /// Write the message to a .eml file instead of sending it
+        #[clap(long)]
+        dry_run: bool,
+        /// Where --dry-run writes the message, clinews-digest-<date>.eml
+        /// by default
+        #[clap(short, long)]
+        output: Option<PathBuf>
+    },
+    /// Write the headlines, or the articles collected so far, into an RSS
+    /// or Atom feed
+    Feed {
+        /// rss or atom
+        #[clap(long, default_value = "atom")]
+        format: newsapi::feed::Format,
+        /// Only the headlines of this category
+        #[clap(long, value_parser = feed::parse_category)]
+        category: Option<newsapi::Category>,
+        /// Only articles with all of these words in the title or description
+        #[clap(short, long)]
+        query: Option<String>,
+        /// One feed per category, written into the --output directory
+        #[clap(long, conflicts_with = "category")]
+        per_category: bool,
+        /// Take the articles collected by earlier runs and by clinews watch
+        /// instead of the current headlines
+        #[clap(long, conflicts_with_all = &["category", "per-category"])]
+        archive: bool,
+        /// How far back --archive looks, like 24h, 90m or 7d
+        #[clap(long, default_value = "24h", value_parser = digest::parse_since)]
+        since: chrono::Duration,
+        /// Where the feed will be published, for its self link. With
+        /// --per-category, where the directory will be
+        #[clap(long)]
+        url: Option<String>,
+        /// File to write to, standard output by default
+        #[clap(short, long)]
+        output: Option<PathBuf>
+    },
+    /// Serve the filtered headlines as JSON over HTTP
+    Serve {
+        /// Address to listen on, overrides `serve.address`
+        #[clap(long)]
+        address: Option<String>
+    },
+    /// Show the terms standing out in today's articles, collected by earlier
+    /// runs and by `clinews watch`
+    Trends {
+        /// Days in the sparklines
+        #[clap(long, default_value = "7")]
+        days: i64,
+        /// Terms shown
+        #[clap(long, default_value = "15")]
+        top: usize
+    }
 }
 
 #[tokio::main]
@@ -23,16 +225,202 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     dotenv()?;
 
+    let cli = Cli::parse();
     let api_key = std::env::var("API_KEY")?;
-    
+    let mut config = config::load()?;
+    let filters = FilterEngine::new(&config.filters)?;
+    let skin = theme::skin(cli.theme.as_deref().or(config.theme.as_deref()), &config.skins)?;
 
     let mut newsapi = NewsAPI::new(&api_key);
     newsapi.endpoint(Endpoint::TopHeadlines).country(Country::Us);
 
-    // let articles = newsapi.fetch_async().await?;
-    let articles = newsapi.fetch()?;
-    
-    render_articles(articles.articles()).await;
+    match cli.command {
+        Some(Command::Watch { interval }) => {
+            if let Some(interval) = interval {
+                config.watch.interval_secs = interval;
+            }
+            let interval = watch::interval(&config.watch);
+            let mut watcher = watch::Watcher::new(newsapi, filters, &config.watch, watch::DesktopNotifier);
+            match store::Store::open() {
+                Ok(store) => {
+                    watcher.archive_to(store);
+                },
+                Err(e) => eprintln!("Could not open the archive for clinews digest: {}", e),
+            }
+            let mut webhooks = webhook::Webhooks::new(std::mem::take(&mut config.watch.webhooks));
+            if !webhooks.is_empty() {
+                webhooks.check()?;
+                webhooks.persist_to(store::Store::open()?)?;
+                watcher.post_to(webhooks);
+            }
+            watcher.run(interval).await;
+        },
+        Some(Command::Read { article }) => {
+            let url = article_url(&article)?;
+            let doc = extract::fetch(&url)?;
+            pager::page(skin, doc.to_markdown())?;
+        },
+        Some(Command::Open { n, copy }) => {
+            let store = store::Store::open()?;
+            let article = store.last_article(n)?;
+            clicked(&store, &article);
+            if copy {
+                links::copy_to_clipboard(article.url())?;
+                skin.print_text(&format!("Copied `{}`", article.url()));
+            } else {
+                links::open_in_browser(article.url())?;
+            }
+        },
+        Some(Command::Export { format, group, full_text, title, output }) => {
+            if format == Format::Epub && output.is_none() && pager::is_tty() {
+                return Err("an EPUB can't be shown in the terminal, pass --output".into());
+            }
+            if let Some(limit) = cli.limit {
+                newsapi.page_size(limit);
+            }
+            let mut items = export::collect(&mut newsapi, &filters, group)?;
+            if full_text {
+                export::add_full_text(&mut items).await;
+            }
+            let title = title.unwrap_or_else(|| format!("Headlines {}", chrono::Local::now().format("%Y-%m-%d")));
+            let digest = export::render(&title, items, format, group);
+            match output {
+                Some(path) => std::fs::write(&path, digest)?,
+                None => std::io::stdout().write_all(&digest)?,
+            }
+        },
+        Some(Command::Digest { since, dry_run, output }) => {
+            let archive = store::Store::open()?.archive()?;
+            let now = chrono::Utc::now();
+            let mut stories = digest::stories(&archive, now, since);
+            if stories.is_empty() {
+                return Err("no articles collected in that time, run clinews or clinews watch first".into());
+            }
+            stories.truncate(config.digest.max_stories);
+
+            let date = chrono::Local::now();
+            let subject = format!("{}, {}", config.digest.subject, date.format("%Y-%m-%d"));
+            let message = mail::Message {
+                from: config.digest.from.clone().unwrap_or_else(|| "clinews <clinews@localhost>".to_string()),
+                to: config.digest.to.clone(),
+                text: digest::render_text(&subject, &stories, now),
+                html: digest::render_html(&subject, &stories, now),
+                subject
+            };
+            let mime = message.to_mime(date);
+
+            if dry_run {
+                let path = output.unwrap_or_else(|| PathBuf::from(format!("clinews-digest-{}.eml", date.format("%Y-%m-%d"))));
+                std::fs::write(&path, &mime)?;
+                skin.print_text(&format!("Wrote `{}`", path.display()));
+            } else {
+                let smtp = config.digest.smtp.as_ref().ok_or("no [digest.smtp] server configured, or use --dry-run")?;
+                if config.digest.from.is_none() || message.to.is_empty() {
+                    return Err("set digest.from and digest.to in the config, or use --dry-run".into());
+                }
+                mail::send(smtp, &message, &mime)?;
+                skin.print_text(&format!("Sent {} stories to {}", stories.len(), message.to.join(", ")));
+            }
+        },
+        Some(Command::Feed { format, category, query, per_category, archive, since, url, output }) => {
+            let query = query.as_deref();
+            newsapi.page_size(cli.limit.unwrap_or(100));
+            if per_category {
+                let dir = output.ok_or("--per-category needs an --output directory")?;
+                std::fs::create_dir_all(&dir)?;
+                for category in newsapi::Category::ALL {
+                    let articles = feed::fetch(&mut newsapi, &filters, Some(category), query)?;
+                    let name = format!("{}.{}", category, format.extension());
+                    let self_link = url.as_ref().map(|url| format!("{}/{}", url.trim_end_matches('/'), name));
+                    std::fs::write(dir.join(&name), feed::render(&articles, Some(category), query, format, self_link.as_deref(), None))?;
+                }
+                skin.print_text(&format!("Wrote {} feeds into `{}`", newsapi::Category::ALL.len(), dir.display()));
+            } else {
+                let articles = match archive {
+                    true => {
+                        let since = (chrono::Utc::now() - since).timestamp();
+                        feed::from_archive(store::Store::open()?.archive()?, &filters, since, query)
+                    },
+                    false => feed::fetch(&mut newsapi, &filters, category, query)?,
+                };
+                let document = feed::render(&articles, category, query, format, url.as_deref(), None);
+                match output {
+                    Some(path) => std::fs::write(&path, document)?,
+                    None => std::io::stdout().write_all(document.as_bytes())?,
+                }
+            }
+        },
+        Some(Command::Serve { address }) => {
+            let address = address.unwrap_or_else(|| config.serve.address.clone());
+            let listener = tokio::net::TcpListener::bind(&address).await?;
+            skin.print_text(&format!("Serving on `http://{}/articles`", listener.local_addr()?));
+            let server = serve::Server::new(newsapi, filters, &config.serve);
+            std::sync::Arc::new(server).run(listener).await;
+        },
+        Some(Command::Trends { days, top }) => {
+            let archive = store::Store::open()?.archive()?;
+            let offset = chrono::Local::now().offset().local_minus_utc() as i64;
+            let corpus = newsapi::archive::corpus(&archive, &filters, offset);
+            let today = newsapi::archive::day(chrono::Utc::now().timestamp(), offset);
+            skin.print_text(&trends::markdown(&corpus, today, days.max(1), top));
+        },
+        None => {
+            if let Some(limit) = cli.limit {
+                newsapi.page_size(limit);
+            }
+            let density = cli.density.unwrap_or(config.density);
+            let sentences = match density {
+                layout::Density::Compact => 0,
+                _ => cli.summary.unwrap_or(config.summary.sentences),
+            };
+            let mut rules = config.filters.clone();
+            if let Some(tone) = cli.sentiment {
+                // Hiding the other tones keeps the include rules of the config
+                // working as before, and shows up in the filter report.
+                rules.extend(Tone::ALL.iter().filter(|t| **t != tone).map(|t| {
+                    FilterRule { name: None, action: Action::Exclude, matcher: Matcher::Sentiment(*t) }
+                }));
+            }
+            let filters = FilterEngine::new(&rules)?;
+            let mut listing = listing::Listing::new(newsapi, &filters, sentences);
+            if cli.translate {
+                listing.translate_to(config.translate.translator()?, &config.translate.target);
+            }
+            let sort = cli.sort.unwrap_or(config.sort);
+            if sort != SortMode::Api || cli.explain {
+                let clicks = store::Store::open()?.clicks()?;
+                let ranker = Ranker::new(&config.ranking, &clicks, chrono::Utc::now().timestamp());
+                listing.sort(ranker, sort, cli.explain);
+            }
+            listing.next_page()?;
+            remember(&listing.articles);
+
+            if pager::is_tty() {
+                let markdown = layout::listing_markdown(&listing.articles, &listing.summaries, &listing.explanations, density, 1);
+                pager::page_more(skin.clone(), markdown, &mut || {
+                    if !listing.has_more() {
+                        return Ok(None);
+                    }
+                    let added = listing.next_page()?;
+                    remember(&listing.articles);
+                    let first = added.start + 1;
+                    Ok(Some(layout::listing_markdown(
+                        &listing.articles[added.clone()],
+                        &listing.summaries[added.clone()],
+                        listing.explanations.get(added).unwrap_or_default(),
+                        density,
+                        first
+                    )))
+                })?;
+            } else {
+                layout::render_articles(&skin, &listing.articles, &listing.summaries, &listing.explanations, density);
+            }
+            render_filter_report(&skin, &listing.report);
+            if let Some(error) = &listing.translation_error {
+                skin.print_text(&format!("*Some articles were not translated:* {}", error));
+            }
+        }
+    }
 
     Ok(())
 }
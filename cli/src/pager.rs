@@ -0,0 +1,120 @@
+use crossterm::cursor::{Hide, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::tty::IsTty;
+use crossterm::queue;
+use std::error::Error;
+use std::io::{stdout, Write};
+use termimad::{Area, MadSkin, MadView};
+
+/// Lines are wrapped at this width on wide terminals, to keep them readable.
+const MAX_WIDTH: u16 = 100;
+
+fn view_area() -> Area {
+    let mut area = Area::full_screen();
+    area.pad_for_max_width(MAX_WIDTH);
+    area
+}
+
+/// Shown at the bottom while `more` may still have something to add.
+const MORE_HINT: &str = "*Scroll down for more…*";
+
+/// Markdown to add at the end of the view, `None` once there's nothing left.
+pub type More<'a> = dyn FnMut() -> Result<Option<String>, Box<dyn Error>> + 'a;
+
+pub fn is_tty() -> bool {
+    stdout().is_tty()
+}
+
+/// Shows `markdown` in a scrollable full screen view until `q` or `Esc`.
+/// When stdout isn't a terminal the text is printed as is.
+pub fn page(skin: MadSkin, markdown: String) -> Result<(), Box<dyn Error>> {
+    page_more(skin, markdown, &mut || Ok(None))
+}
+
+/// Like `page`, but scrolling down past the end asks `more` for the next
+/// part of the text. `more` isn't called when stdout isn't a terminal.
+pub fn page_more(skin: MadSkin, markdown: String, more: &mut More) -> Result<(), Box<dyn Error>> {
+    let mut w = stdout();
+    if !w.is_tty() {
+        skin.print_text(&markdown);
+        return Ok(());
+    }
+
+    queue!(w, EnterAlternateScreen, Hide)?;
+    terminal::enable_raw_mode()?;
+    let result = run(&mut w, skin, markdown, more);
+    terminal::disable_raw_mode()?;
+    queue!(w, Show, LeaveAlternateScreen)?;
+    w.flush()?;
+
+    result
+}
+
+fn build_view(skin: &MadSkin, markdown: &str, has_more: bool) -> MadView {
+    let markdown = match has_more {
+        true => format!("{}\n\n{}", markdown, MORE_HINT),
+        false => markdown.to_string(),
+    };
+    MadView::from(markdown, view_area(), skin.clone())
+}
+
+fn run<W: Write>(w: &mut W, skin: MadSkin, mut markdown: String, more: &mut More) -> Result<(), Box<dyn Error>> {
+    let mut has_more = true;
+    let mut view = build_view(&skin, &markdown, has_more);
+    loop {
+        view.write_on(w)?;
+        w.flush()?;
+
+        let scroll = view.scroll;
+        let down = match event::read()? {
+            Event::Key(KeyEvent { code, modifiers, .. }) => match code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Up | KeyCode::Char('k') => {
+                    view.try_scroll_lines(-1);
+                    false
+                },
+                KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => {
+                    view.try_scroll_lines(1);
+                    true
+                },
+                KeyCode::PageUp | KeyCode::Char('b') => {
+                    view.try_scroll_pages(-1);
+                    false
+                },
+                KeyCode::PageDown | KeyCode::Char(' ') => {
+                    view.try_scroll_pages(1);
+                    true
+                },
+                _ => false,
+            },
+            Event::Resize(..) => {
+                queue!(w, Clear(ClearType::All))?;
+                view.resize(&view_area());
+                false
+            },
+            _ => false,
+        };
+
+        // Trying to scroll down without moving means the end is on screen.
+        if down && has_more && view.scroll == scroll {
+            match more() {
+                Ok(Some(next)) => {
+                    markdown.push('\n');
+                    markdown.push_str(&next);
+                },
+                Ok(None) => has_more = false,
+                Err(e) => {
+                    markdown.push_str(&format!("\n\n*Could not load more: {}*", e));
+                    has_more = false;
+                },
+            }
+            view = build_view(&skin, &markdown, has_more);
+            view.scroll = scroll;
+            view.try_scroll_lines(1);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,689 @@
+//! `clinews serve`: the filtered headlines over HTTP, as JSON and as RSS and
+//! Atom feeds, so that other tools can show them without a newsapi key of
+//! their own.
+//!
+//! `GET /articles`, `GET /feed.rss` and `GET /feed.atom` take `country`,
+//! `category` and `sources`, which are passed on to newsapi, and `q`,
+//! `domain`, `limit` and `offset`, which are applied here. Only the
+//! countries and sources of the config can be asked for, since every one
+//! costs newsapi requests. Every newsapi response is cached for
+//! `cache_secs`, and shared by all the requests asking for the same country,
+//! category and sources. `GET /health` says whether the server is up and
+//! when newsapi last answered.
+
+use crate::config::ServeConfig;
+use crate::feed;
+use newsapi::feed::Format;
+use newsapi::filter::FilterEngine;
+use newsapi::{Article, Category, Country, NewsAPI};
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::OnceCell;
+
+const DEFAULT_LIMIT: usize = 20;
+/// Feeds have everything, unless asked otherwise.
+const FEED_LIMIT: usize = 100;
+/// Longest request head accepted, request line and headers together.
+const MAX_HEAD: usize = 8 * 1024;
+/// How long a client gets to send its request, and to take the response.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Newsapi responses kept at most. The least recently used go first.
+const MAX_CACHED: usize = 64;
+
+/// What newsapi is asked for. Everything else is done on the cached copy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Upstream {
+    country: Country,
+    category: Option<Category>,
+    sources: Option<String>
+}
+
+struct Cached {
+    articles: Vec<Article>,
+    /// Articles hidden by the filter rules.
+    hidden: usize,
+    fetched_at: chrono::DateTime<chrono::Utc>,
+    at: Instant
+}
+
+struct Slot {
+    cached: Arc<Cached>,
+    /// `Server::uses` when last read, to tell which went unused the longest.
+    used: u64
+}
+
+/// A newsapi request under way, shared by everyone waiting for it.
+type Flight = Arc<OnceCell<Result<Arc<Cached>, String>>>;
+
+#[derive(Default)]
+struct Health {
+    last_fetch: Option<chrono::DateTime<chrono::Utc>>,
+    last_error: Option<String>
+}
+
+pub struct Server {
+    /// Asked for every country, category and sources in turn.
+    newsapi: NewsAPI,
+    filters: FilterEngine,
+    cache_for: Duration,
+    countries: Vec<Country>,
+    sources: Vec<String>,
+    cache: Mutex<HashMap<Upstream, Slot>>,
+    uses: AtomicU64,
+    in_flight: Mutex<HashMap<Upstream, Flight>>,
+    health: Mutex<Health>,
+    started: Instant
+}
+
+struct Request {
+    method: String,
+    /// Path and query, as requested.
+    target: String,
+    path: String,
+    query: HashMap<String, String>,
+    host: Option<String>,
+    if_none_match: Option<String>
+}
+
+/// The articles a request asks for.
+struct Selection {
+    cached: Arc<Cached>,
+    category: Option<Category>,
+    query: Option<String>,
+    /// All the matching articles, before `offset` and `limit`.
+    matching: Vec<Article>,
+    offset: usize,
+    limit: usize
+}
+
+impl Selection {
+    fn page(&self) -> &[Article] {
+        let start = self.offset.min(self.matching.len());
+        &self.matching[start..(start + self.limit).min(self.matching.len())]
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String
+}
+
+impl Response {
+    fn json(status: u16, value: serde_json::Value) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            headers: vec![("Cache-Control", "no-store".to_string())],
+            body: value.to_string()
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status, json!({ "status": "error", "message": message }))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            502 => "Bad Gateway",
+            _ => "Error",
+        }
+    }
+
+    async fn write_to(&self, stream: &mut TcpStream, head_only: bool) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, self.reason());
+        head.push_str(&format!("Content-Type: {}; charset=utf-8\r\n", self.content_type));
+        head.push_str("Access-Control-Allow-Origin: *\r\n");
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len()));
+        stream.write_all(head.as_bytes()).await?;
+        if !head_only {
+            stream.write_all(self.body.as_bytes()).await?;
+        }
+        stream.flush().await
+    }
+}
+
+impl Server {
+    pub fn new(newsapi: NewsAPI, filters: FilterEngine, config: &ServeConfig) -> Server {
+        let mut countries = config.countries.clone();
+        if countries.is_empty() {
+            countries.push(Country::Us);
+        }
+        Server {
+            newsapi,
+            filters,
+            cache_for: Duration::from_secs(config.cache_secs),
+            countries,
+            sources: config.sources.iter().map(|s| s.trim().to_lowercase()).collect(),
+            cache: Mutex::new(HashMap::new()),
+            uses: AtomicU64::new(0),
+            in_flight: Mutex::new(HashMap::new()),
+            health: Mutex::new(Health::default()),
+            started: Instant::now()
+        }
+    }
+
+    /// Answers requests on `listener` until the process ends.
+    pub async fn run(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("failed accepting a connection: {}", e);
+                    continue;
+                },
+            };
+            let server = self.clone();
+            tokio::spawn(async move {
+                let (response, head_only) = match tokio::time::timeout(TIMEOUT, read_request(&mut stream)).await {
+                    Ok(Ok(request)) => (server.handle(&request).await, request.method == "HEAD"),
+                    Ok(Err(e)) => (Response::error(400, &e.to_string()), false),
+                    Err(_) => (Response::error(408, "took too long to send the request"), false),
+                };
+                let _ = tokio::time::timeout(TIMEOUT, response.write_to(&mut stream, head_only)).await;
+            });
+        }
+    }
+
+    async fn handle(self: &Arc<Self>, request: &Request) -> Response {
+        if request.method != "GET" && request.method != "HEAD" {
+            let mut response = Response::error(405, "only GET and HEAD are supported");
+            response.headers.push(("Allow", "GET, HEAD".to_string()));
+            return response;
+        }
+        match request.path.as_str() {
+            "/health" => self.health(),
+            "/articles" => self.articles(request).await.unwrap_or_else(|error| error),
+            "/feed.rss" => self.feed(request, Format::Rss).await.unwrap_or_else(|error| error),
+            "/feed.atom" => self.feed(request, Format::Atom).await.unwrap_or_else(|error| error),
+            _ => Response::error(404, "no such endpoint, try /articles, /feed.rss, /feed.atom or /health"),
+        }
+    }
+
+    fn health(&self) -> Response {
+        let health = self.health.lock().unwrap();
+        Response::json(200, json!({
+            "status": if health.last_error.is_some() { "degraded" } else { "ok" },
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "cached_feeds": self.cache.lock().unwrap().len(),
+            "last_fetch": health.last_fetch.map(|t| t.to_rfc3339()),
+            "last_error": health.last_error
+        }))
+    }
+
+    async fn articles(self: &Arc<Self>, request: &Request) -> Result<Response, Response> {
+        let selection = self.select(request, DEFAULT_LIMIT).await?;
+        let body = json!({
+            "status": "ok",
+            "total": selection.matching.len(),
+            "offset": selection.offset,
+            "limit": selection.limit,
+            "hidden_by_filters": selection.cached.hidden,
+            "fetched_at": selection.cached.fetched_at.to_rfc3339(),
+            "articles": selection.page()
+        }).to_string();
+        Ok(self.cacheable(request, &selection.cached, "application/json", body))
+    }
+
+    async fn feed(self: &Arc<Self>, request: &Request, format: Format) -> Result<Response, Response> {
+        let selection = self.select(request, FEED_LIMIT).await?;
+        let self_link = request.host.as_ref().map(|host| format!("http://{}{}", host, request.target));
+        let body = feed::render(
+            selection.page(),
+            selection.category,
+            selection.query.as_deref(),
+            format,
+            self_link.as_deref(),
+            // The cached fetch's time, so the ETag only changes with the articles.
+            Some(selection.cached.fetched_at.timestamp())
+        );
+        Ok(self.cacheable(request, &selection.cached, format.content_type(), body))
+    }
+
+    /// Reads the parameters of `request` and picks the articles matching them.
+    async fn select(self: &Arc<Self>, request: &Request, default_limit: usize) -> Result<Selection, Response> {
+        let query = &request.query;
+        let param = |name: &str| query.get(name).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let number = |name: &str, default: usize| match param(name) {
+            Some(v) => v.parse::<usize>().map_err(|_| Response::error(400, &format!("{} must be a number", name))),
+            None => Ok(default),
+        };
+
+        let country = match param("country") {
+            Some(v) => *self
+                .countries
+                .iter()
+                .find(|c| c.to_string() == v.to_lowercase())
+                .ok_or_else(|| Response::error(400, &format!("country {:?} isn't served, see serve.countries", v)))?,
+            None => self.countries[0],
+        };
+        let category = match param("category") {
+            Some(v) => Some(feed::parse_category(v).map_err(|e| Response::error(400, &e))?),
+            None => None,
+        };
+        let sources = match param("sources") {
+            Some(v) => Some(self.allowed_sources(v)?),
+            None => None,
+        };
+        let upstream = Upstream { country, category, sources };
+        let limit = number("limit", default_limit)?.min(100);
+        let offset = number("offset", 0)?;
+        let words = param("q").map(|q| q.to_string());
+        let domain = param("domain").map(|d| d.to_lowercase());
+
+        let cached = self.cached(upstream).await.map_err(|e| Response::error(502, &format!("newsapi failed: {}", e)))?;
+        let matching: Vec<Article> = cached
+            .articles
+            .iter()
+            .filter(|a| feed::matches(a, words.as_deref()))
+            .filter(|a| match &domain {
+                Some(domain) => in_domain(a.url(), domain),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        Ok(Selection { cached, category, query: words, matching, offset, limit })
+    }
+
+    /// `sources` in a canonical order, so that every way of asking for the
+    /// same sources shares a cache entry. All of them have to be allowed.
+    fn allowed_sources(&self, sources: &str) -> Result<String, Response> {
+        let mut ids: Vec<String> = sources.split(',').map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty()).collect();
+        if let Some(id) = ids.iter().find(|id| !self.sources.contains(id)) {
+            return Err(Response::error(400, &format!("source {:?} isn't served, see serve.sources", id)));
+        }
+        ids.sort();
+        ids.dedup();
+        Ok(ids.join(","))
+    }
+
+    /// A 200 response with cache headers, or a 304 when the client's copy
+    /// is still the same.
+    fn cacheable(&self, request: &Request, cached: &Cached, content_type: &'static str, body: String) -> Response {
+        let etag = etag(&body);
+        let max_age = self.cache_for.saturating_sub(cached.at.elapsed()).as_secs();
+        let headers = vec![
+            ("Cache-Control", format!("public, max-age={}", max_age)),
+            ("ETag", etag.clone()),
+            ("Last-Modified", cached.fetched_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()),
+        ];
+        if let Some(tags) = &request.if_none_match {
+            if tags.split(',').any(|t| t.trim() == etag || t.trim() == "*") {
+                return Response { status: 304, content_type, headers, body: String::new() };
+            }
+        }
+        Response { status: 200, content_type, headers, body }
+    }
+
+    /// The filtered articles for `upstream`, fetched again once they're
+    /// older than `cache_secs`. Requests missing the cache together share
+    /// one newsapi request.
+    async fn cached(self: &Arc<Self>, upstream: Upstream) -> Result<Arc<Cached>, Box<dyn Error + Send + Sync>> {
+        if let Some(cached) = self.fresh(&upstream) {
+            return Ok(cached);
+        }
+
+        let flight = self.in_flight.lock().unwrap().entry(upstream.clone()).or_default().clone();
+        let result = flight.get_or_init(|| self.refresh(upstream.clone())).await.clone();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(&upstream).map(|f| Arc::ptr_eq(f, &flight)).unwrap_or(false) {
+            in_flight.remove(&upstream);
+        }
+        result.map_err(|e| e.into())
+    }
+
+    /// The cached articles for `upstream`, if they're recent enough.
+    fn fresh(&self, upstream: &Upstream) -> Option<Arc<Cached>> {
+        let mut cache = self.cache.lock().unwrap();
+        let slot = cache.get_mut(upstream).filter(|slot| slot.cached.at.elapsed() < self.cache_for)?;
+        slot.used = self.uses.fetch_add(1, Ordering::Relaxed);
+        Some(slot.cached.clone())
+    }
+
+    async fn refresh(self: &Arc<Self>, upstream: Upstream) -> Result<Arc<Cached>, String> {
+        // Another request may have just fetched them.
+        if let Some(cached) = self.fresh(&upstream) {
+            return Ok(cached);
+        }
+
+        let server = self.clone();
+        let key = upstream.clone();
+        let result = tokio::task::spawn_blocking(move || server.fetch(&key))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+
+        let mut health = self.health.lock().unwrap();
+        match result {
+            Ok(cached) => {
+                let cached = Arc::new(cached);
+                health.last_fetch = Some(cached.fetched_at);
+                health.last_error = None;
+                self.store(upstream, cached.clone());
+                Ok(cached)
+            },
+            Err(e) => {
+                health.last_error = Some(e.clone());
+                Err(e)
+            },
+        }
+    }
+
+    /// Caches `cached`, dropping what expired and, past `MAX_CACHED`, what
+    /// was used the longest ago.
+    fn store(&self, upstream: Upstream, cached: Arc<Cached>) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, slot| slot.cached.at.elapsed() < self.cache_for);
+        while cache.len() >= MAX_CACHED {
+            let oldest = cache.iter().min_by_key(|(_, slot)| slot.used).map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => cache.remove(&key),
+                None => break,
+            };
+        }
+        cache.insert(upstream, Slot { cached, used: self.uses.fetch_add(1, Ordering::Relaxed) });
+    }
+
+    fn fetch(&self, upstream: &Upstream) -> Result<Cached, String> {
+        let mut newsapi = self.newsapi.clone();
+        newsapi
+            .country(upstream.country)
+            .category(upstream.category)
+            .sources(upstream.sources.clone())
+            .page_size(100);
+        let mut response = newsapi.fetch().map_err(|e| e.to_string())?;
+        let report = self.filters.apply(&mut response.articles);
+        Ok(Cached {
+            articles: response.articles,
+            hidden: report.total(),
+            fetched_at: chrono::Utc::now(),
+            at: Instant::now()
+        })
+    }
+}
+
+/// Whether `url` is on `domain` or one of its subdomains.
+fn in_domain(url: &str, domain: &str) -> bool {
+    match url::Url::parse(url).ok().as_ref().and_then(|u| u.host_str()) {
+        Some(host) => {
+            let host = host.to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        },
+        None => false,
+    }
+}
+
+fn etag(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, Box<dyn Error + Send + Sync>> {
+    let mut reader = BufReader::new(stream);
+    let mut lines = vec![];
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await?;
+        size += n;
+        if size > MAX_HEAD {
+            return Err("request head too large".into());
+        }
+        let line = line.trim_end().to_string();
+        if n == 0 || line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines.first().ok_or("empty request")?.split_whitespace();
+    let method = request_line.next().ok_or("no method")?.to_string();
+    let target = request_line.next().ok_or("no path")?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let header = |wanted: &str| {
+        lines[1..]
+            .iter()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.trim().to_string())
+    };
+
+    Ok(Request {
+        method,
+        target: target.to_string(),
+        path: path.to_string(),
+        query: url::form_urlencoded::parse(query.as_bytes()).into_owned().collect(),
+        host: header("host"),
+        if_none_match: header("if-none-match")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{articles_json, MockServer};
+    use newsapi::filter::{Action, FilterRule, Matcher};
+    use std::io::{Read, Write};
+
+    struct Reply {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: String
+    }
+
+    impl Reply {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+        }
+
+        fn json(&self) -> serde_json::Value {
+            serde_json::from_str(&self.body).unwrap()
+        }
+    }
+
+    async fn start(newsapi: &MockServer, filters: &[FilterRule]) -> String {
+        let config = ServeConfig { sources: vec!["wire".to_string(), "gazette".to_string()], ..ServeConfig::default() };
+        start_with(newsapi, filters, &config).await
+    }
+
+    async fn start_with(newsapi: &MockServer, filters: &[FilterRule], config: &ServeConfig) -> String {
+        let mut api = NewsAPI::new("key");
+        api.base_url(&newsapi.url);
+        let server = Server::new(api, FilterEngine::new(filters).unwrap(), config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(Arc::new(server).run(listener));
+        address
+    }
+
+    async fn get(address: &str, target: &str, headers: &str) -> Reply {
+        let (address, request) = (address.to_string(), format!("GET {} HTTP/1.1\r\nHost: test\r\n{}\r\n", target, headers));
+        tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let mut lines = head.lines();
+            let status = lines.next().unwrap().split_whitespace().nth(1).unwrap().parse().unwrap();
+            let headers = lines
+                .filter_map(|l| l.split_once(": "))
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect();
+            Reply { status, headers, body: body.to_string() }
+        }).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serves_filtered_cached_articles() {
+        let newsapi = MockServer::start(vec![(200, articles_json(&[
+            ("Rates rise again", "https://www.wire.example/rates"),
+            ("Celebrity gossip", "https://gossip.example/1"),
+            ("Rates: what it means", "https://gazette.example/rates"),
+            ("Cup final", "https://news.wire.example/cup"),
+        ]))]);
+        let rules = vec![FilterRule { name: None, action: Action::Exclude, matcher: Matcher::Keyword("gossip".to_string()) }];
+        let address = start(&newsapi, &rules).await;
+
+        let all = get(&address, "/articles?category=business", "").await;
+        assert_eq!(all.status, 200);
+        assert_eq!(all.json()["total"], 3);
+        assert_eq!(all.json()["hidden_by_filters"], 1);
+        assert!(all.header("cache-control").unwrap().starts_with("public, max-age="));
+
+        let rates = get(&address, "/articles?category=Business&q=RATES&limit=1&offset=1", "").await;
+        assert_eq!(rates.json()["total"], 2);
+        assert_eq!(rates.json()["articles"].as_array().unwrap().len(), 1);
+        assert_eq!(rates.json()["articles"][0]["url"], "https://gazette.example/rates");
+
+        let wire = get(&address, "/articles?category=business&domain=wire.example", "").await;
+        let urls: Vec<String> = wire.json()["articles"].as_array().unwrap().iter().map(|a| a["url"].as_str().unwrap().to_string()).collect();
+        assert_eq!(urls, vec!["https://www.wire.example/rates", "https://news.wire.example/cup"]);
+
+        // Same country, category and sources: answered from the cache.
+        let requests = newsapi.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].path.contains("category=business"));
+        assert!(requests[0].path.contains("pageSize=100"));
+
+        let etag = all.header("etag").unwrap();
+        let again = get(&address, "/articles?category=business", &format!("If-None-Match: {}\r\n", etag)).await;
+        assert_eq!(again.status, 304);
+        assert!(again.body.is_empty());
+        assert_eq!(again.header("etag"), Some(etag));
+
+        // Feeds carry the fetch's time, not the time they're rendered at.
+        let feed = get(&address, "/feed.rss?category=business", "").await;
+        let etag = feed.header("etag").unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let again = get(&address, "/feed.rss?category=business", &format!("If-None-Match: {}\r\n", etag)).await;
+        assert_eq!(again.status, 304);
+        assert_eq!(newsapi.requests().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serves_feeds_per_category_and_query() {
+        let newsapi = MockServer::start(vec![(200, articles_json(&[
+            ("Rates rise", "https://wire.example/rates"),
+            ("Cup final", "https://wire.example/cup"),
+        ]))]);
+        let address = start(&newsapi, &[]).await;
+
+        let atom = get(&address, "/feed.atom?category=business&q=rates", "").await;
+        assert_eq!(atom.status, 200);
+        assert_eq!(atom.header("content-type"), Some("application/atom+xml; charset=utf-8"));
+        assert!(atom.body.contains("<title>Headlines: Business matching &quot;rates&quot;</title>"));
+        assert!(atom.body.contains("<link rel=\"self\" type=\"application/atom+xml\" href=\"http://test/feed.atom?category=business&amp;q=rates\"/>"));
+        assert_eq!(atom.body.matches("<entry>").count(), 1);
+
+        let rss = get(&address, "/feed.rss?category=business", "").await;
+        assert_eq!(rss.header("content-type"), Some("application/rss+xml; charset=utf-8"));
+        assert_eq!(rss.body.matches("<item>").count(), 2);
+        assert!(rss.header("etag").is_some());
+        assert_eq!(newsapi.requests().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_health_and_bad_requests() {
+        let newsapi = MockServer::start(vec![(401, r#"{"status":"error","code":"apiKeyInvalid"}"#.to_string())]);
+        let address = start(&newsapi, &[]).await;
+
+        let health = get(&address, "/health", "").await;
+        assert_eq!(health.status, 200);
+        assert_eq!(health.json()["status"], "ok");
+        assert_eq!(health.header("cache-control"), Some("no-store"));
+
+        assert_eq!(get(&address, "/articles?category=weather", "").await.status, 400);
+        assert_eq!(get(&address, "/articles?limit=lots", "").await.status, 400);
+        assert_eq!(get(&address, "/nothing", "").await.status, 404);
+        assert!(newsapi.requests().is_empty());
+
+        let failed = get(&address, "/articles", "").await;
+        assert_eq!(failed.status, 502);
+        assert_eq!(get(&address, "/health", "").await.json()["status"], "degraded");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_asks_newsapi_for_what_is_configured() {
+        let newsapi = MockServer::start(vec![(200, articles_json(&[("Rates rise", "https://wire.example/rates")]))]);
+        let config = ServeConfig {
+            countries: vec![Country::Gb, Country::Us],
+            sources: vec!["wire".to_string(), "Gazette".to_string()],
+            ..ServeConfig::default()
+        };
+        let address = start_with(&newsapi, &[], &config).await;
+
+        assert_eq!(get(&address, "/articles?country=fr", "").await.status, 400);
+        assert_eq!(get(&address, "/articles?sources=wire,elsewhere", "").await.status, 400);
+        assert!(newsapi.requests().is_empty());
+
+        // Concurrent misses share one request, whatever the order of sources.
+        let targets: [&'static str; 3] = ["/articles?sources=gazette,wire", "/articles?sources=wire,gazette", "/articles?sources=Wire,%20gazette,wire"];
+        let gets = targets.into_iter().cycle().take(9).map(|target| {
+            let address = address.clone();
+            tokio::spawn(async move { get(&address, target, "").await })
+        });
+        let mut replies = vec![];
+        for get in gets.collect::<Vec<_>>() {
+            replies.push(get.await.unwrap());
+        }
+        assert!(replies.iter().all(|r| r.status == 200));
+        let requests = newsapi.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].path.contains("sources=gazette%2Cwire"));
+
+        // The first country is the default.
+        assert_eq!(get(&address, "/articles", "").await.status, 200);
+        assert!(newsapi.requests()[1].path.contains("country=gb"));
+    }
+
+    #[tokio::test]
+    async fn times_out_silent_clients() {
+        tokio::time::pause();
+        let newsapi = MockServer::start(vec![]);
+        let address = start(&newsapi, &[]).await;
+        let mut stream = TcpStream::connect(&address).await.unwrap();
+        stream.write_all(b"GET /articles HTTP/1.1\r\n").await.unwrap();
+        tokio::time::advance(TIMEOUT).await;
+
+        let mut response = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let server = Server::new(NewsAPI::new("key"), FilterEngine::new(&[]).unwrap(), &ServeConfig::default());
+        let upstream = |n: usize| Upstream { country: Country::Us, category: None, sources: Some(n.to_string()) };
+        let cached = || Arc::new(Cached { articles: vec![], hidden: 0, fetched_at: chrono::Utc::now(), at: Instant::now() });
+        for n in 0..MAX_CACHED {
+            server.store(upstream(n), cached());
+        }
+        assert!(server.fresh(&upstream(0)).is_some());
+        server.store(upstream(MAX_CACHED), cached());
+
+        let cache = server.cache.lock().unwrap();
+        assert_eq!(cache.len(), MAX_CACHED);
+        assert!(cache.contains_key(&upstream(0)));
+        assert!(!cache.contains_key(&upstream(1)));
+    }
+}
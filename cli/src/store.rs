@@ -0,0 +1,178 @@
+//! Local state kept between runs, in `<data dir>/clinews/` or the directory
+//! pointed to by `$CLINEWS_DATA_DIR`.
+
+use newsapi::Article;
+use newsapi::archive;
+use newsapi::rank::{Click, CLICKS_KEPT};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+pub use newsapi::archive::Archived;
+
+pub struct Store {
+    dir: PathBuf
+}
+
+impl Store {
+    pub fn open() -> Result<Store, Box<dyn Error>> {
+        let dir = match std::env::var("CLINEWS_DATA_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => dirs::data_dir().ok_or("no data directory on this system")?.join("clinews"),
+        };
+        Ok(Store::at(dir))
+    }
+
+    pub fn at(dir: PathBuf) -> Store {
+        Store { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Replaces file `name` in one go: `clinews` and `clinews watch` may
+    /// run at the same time, and neither should read a half written file.
+    fn write(&self, name: &str, contents: String) -> Result<(), Box<dyn Error>> {
+        Ok(archive::replace(&self.path(name), &contents)?)
+    }
+
+    /// Remembers the articles just shown, so that `clinews open <n>` and
+    /// `clinews read <n>` can refer to them by number.
+    pub fn save_last(&self, articles: &[Article]) -> Result<(), Box<dyn Error>> {
+        self.write("last.json", serde_json::to_string(articles)?)
+    }
+
+    /// The articles last shown, empty if there are none yet.
+    pub fn last(&self) -> Result<Vec<Article>, Box<dyn Error>> {
+        match std::fs::read_to_string(self.path("last.json")) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Article number `n` (starting at 1) of the last listing.
+    pub fn last_article(&self, n: usize) -> Result<Article, Box<dyn Error>> {
+        let mut last = self.last()?;
+        if last.is_empty() {
+            return Err("no articles listed yet, run clinews first".into());
+        }
+        match n.checked_sub(1).filter(|&i| i < last.len()) {
+            Some(i) => Ok(last.swap_remove(i)),
+            None => Err(format!("there is no article {}, pick one of 1 to {}", n, last.len()).into()),
+        }
+    }
+
+    /// Every archived article, oldest first. The headlines app shares the
+    /// archive.
+    pub fn archive(&self) -> Result<Vec<Archived>, Box<dyn Error>> {
+        Ok(archive::load(&self.path("archive.json"))?)
+    }
+
+    /// Adds the articles not archived yet, as seen at `now` (Unix time), and
+    /// forgets those older than a month.
+    pub fn record(&self, articles: &[Article], now: i64) -> Result<(), Box<dyn Error>> {
+        Ok(archive::record(&self.path("archive.json"), articles, now)?)
+    }
+
+    /// The article urls each webhook already posted, by webhook name.
+    pub fn webhook_posts(&self) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+        match std::fs::read_to_string(self.path("webhooks.json")) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save_webhook_posts(&self, posted: &HashMap<String, Vec<String>>) -> Result<(), Box<dyn Error>> {
+        self.write("webhooks.json", serde_json::to_string(posted)?)
+    }
+
+    /// The articles opened with `clinews open` and `clinews read`, oldest
+    /// first.
+    pub fn clicks(&self) -> Result<Vec<Click>, Box<dyn Error>> {
+        match std::fs::read_to_string(self.path("clicks.json")) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remembers that `article` was opened at `now` (Unix time).
+    pub fn record_click(&self, article: &Article, now: i64) -> Result<(), Box<dyn Error>> {
+        let mut clicks = self.clicks()?;
+        clicks.push(Click {
+            url: article.url().to_string(),
+            title: article.title().to_string(),
+            source: article.source().name().to_string(),
+            at: now
+        });
+        let excess = clicks.len().saturating_sub(CLICKS_KEPT);
+        clicks.drain(..excess);
+        self.write("clicks.json", serde_json::to_string(&clicks)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{articles_json, tempdir};
+    use newsapi::NewsAPIResponse;
+
+    #[test]
+    fn last_listing_round_trips() {
+        let dir = tempdir();
+        let store = Store::at(dir.path().to_path_buf());
+        assert!(store.last().unwrap().is_empty());
+        assert!(store.last_article(1).is_err());
+
+        let json = articles_json(&[("First", "https://a.example/1"), ("Second", "https://b.example/2")]);
+        let response: NewsAPIResponse = serde_json::from_str(&json).unwrap();
+        store.save_last(response.articles()).unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(files, vec!["last.json"]);
+        assert_eq!(store.last_article(2).unwrap().url(), "https://b.example/2");
+        assert!(store.last_article(0).is_err());
+        assert!(store.last_article(3).is_err());
+    }
+
+    #[test]
+    fn archive_keeps_first_sightings_for_a_month() {
+        let dir = tempdir();
+        let store = Store::at(dir.path().to_path_buf());
+        let articles = |json: &str| serde_json::from_str::<NewsAPIResponse>(json).unwrap().articles;
+
+        let day = 86_400;
+        store.record(&articles(&articles_json(&[("Old", "https://a.example/old")])), 0).unwrap();
+        store.record(&articles(&articles_json(&[("First", "https://a.example/1")])), 20 * day).unwrap();
+        store.record(&articles(&articles_json(&[("First", "https://a.example/1"), ("Second", "https://a.example/2")])), 31 * day).unwrap();
+
+        let archive: Vec<(String, i64)> = store.archive().unwrap().iter().map(|a| (a.article.title().to_string(), a.seen_at)).collect();
+        assert_eq!(archive, vec![("First".to_string(), 20 * day), ("Second".to_string(), 31 * day)]);
+    }
+
+    #[test]
+    fn clicks_keep_the_latest() {
+        let dir = tempdir();
+        let store = Store::at(dir.path().to_path_buf());
+        assert!(store.clicks().unwrap().is_empty());
+
+        let json = articles_json(&[("First", "https://a.example/1")]);
+        let article = &serde_json::from_str::<NewsAPIResponse>(&json).unwrap().articles[0];
+        store.record_click(article, 0).unwrap();
+        let mut clicks = store.clicks().unwrap();
+        clicks = (0..CLICKS_KEPT as i64).map(|at| Click { at, ..clicks[0].clone() }).collect();
+        std::fs::write(dir.path().join("clicks.json"), serde_json::to_string(&clicks).unwrap()).unwrap();
+
+        store.record_click(article, 5000).unwrap();
+        let clicks = store.clicks().unwrap();
+        assert_eq!(clicks.len(), CLICKS_KEPT);
+        assert_eq!(clicks[0].at, 1);
+        assert_eq!(
+            (clicks[CLICKS_KEPT - 1].at, clicks[CLICKS_KEPT - 1].source.as_str(), clicks[CLICKS_KEPT - 1].title.as_str()),
+            (5000, "Test", "First")
+        );
+    }
+}
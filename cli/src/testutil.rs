@@ -0,0 +1,128 @@
+//! Local stand-ins used by the tests in place of newsapi.org and other
+//! remote services.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub use newsapi::testutil::MockServer;
+
+/// A message as received by `SmtpServer`.
+#[derive(Debug, Clone, Default)]
+pub struct Mail {
+    /// The `AUTH PLAIN` credentials, still base64 encoded.
+    pub auth: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Everything sent after `DATA`, with the dot-stuffing undone.
+    pub data: String
+}
+
+/// Local SMTP stand-in, accepting every message it's sent.
+pub struct SmtpServer {
+    pub port: u16,
+    mail: Arc<Mutex<Vec<Mail>>>
+}
+
+impl SmtpServer {
+    pub fn start() -> SmtpServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mail = Arc::new(Mutex::new(Vec::new()));
+        let mail_ = mail.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let _ = write!(stream, "220 localhost ESMTP\r\n");
+                let mut current = Mail::default();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        break;
+                    }
+                    let line = line.trim_end();
+                    let reply = if line.starts_with("EHLO") {
+                        "250-localhost\r\n250 AUTH PLAIN"
+                    } else if let Some(auth) = line.strip_prefix("AUTH PLAIN ") {
+                        current.auth = Some(auth.to_string());
+                        "235 accepted"
+                    } else if let Some(from) = line.strip_prefix("MAIL FROM:") {
+                        current.from = from.trim_matches(|c| c == '<' || c == '>').to_string();
+                        "250 ok"
+                    } else if let Some(to) = line.strip_prefix("RCPT TO:") {
+                        current.to.push(to.trim_matches(|c| c == '<' || c == '>').to_string());
+                        "250 ok"
+                    } else if line == "DATA" {
+                        let _ = write!(stream, "354 go ahead\r\n");
+                        loop {
+                            let mut line = String::new();
+                            if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end() == "." {
+                                break;
+                            }
+                            current.data.push_str(line.strip_prefix('.').unwrap_or(&line));
+                        }
+                        mail_.lock().unwrap().push(std::mem::take(&mut current));
+                        "250 queued"
+                    } else if line == "QUIT" {
+                        let _ = write!(stream, "221 bye\r\n");
+                        break;
+                    } else {
+                        "502 not implemented"
+                    };
+                    let _ = write!(stream, "{}\r\n", reply);
+                }
+            }
+        });
+
+        SmtpServer { port, mail }
+    }
+
+    pub fn mail(&self) -> Vec<Mail> {
+        self.mail.lock().unwrap().clone()
+    }
+}
+
+/// A newsapi response body holding the given `(title, url)` pairs.
+pub fn articles_json(articles: &[(&str, &str)]) -> String {
+    let articles: Vec<String> = articles
+        .iter()
+        .map(|(title, url)| format!(
+            r#"{{"source":{{"id":"test","name":"Test"}},"title":"{}","url":"{}","description":null}}"#,
+            title,
+            url
+        ))
+        .collect();
+    format!(r#"{{"status":"ok","totalResults":{},"articles":[{}]}}"#, articles.len(), articles.join(","))
+}
+
+/// A fresh directory under the system's temporary one, removed on drop.
+/// Unique per test, so tests running in parallel don't share files.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn tempdir() -> TempDir {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::SeqCst);
+    let dir = std::env::temp_dir().join(format!("clinews-test-{}-{}", std::process::id(), n));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
@@ -1,33 +1,285 @@
-use crossterm::style::Color::{AnsiValue, Green, Rgb, Yellow};
-use termimad::{rgb, MadSkin, StyledChar};
+//! Skins for the terminal output. A skin says which colours and characters
+//! termimad uses for headers, emphasis, code, quotes and bullets. A few are
+//! built in and more can be defined under `[skins.<name>]` in the config.
+//!
+//! Colours are written as `#rrggbb`, as a 256 colour palette index, or as
+//! one of the 16 terminal colour names, and are brought down to what the
+//! terminal can show. `NO_COLOR` turns all styling off.
 
-pub fn default() -> MadSkin {
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use termimad::{MadSkin, StyledChar};
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("default", r##"
+        headers = { fg = "yellow" }
+        bold = { fg = "yellow" }
+        italic = { fg = "#d7ff00", bg = "#1c1c1c" }
+        inline_code = { fg = "#ff00c8" }
+        quote = { fg = "#d7ff87", char = "▐" }
+        bullet = { fg = "yellow", char = "⟡" }
+    "##),
+    ("solarized", r##"
+        headers = { fg = "#b58900" }
+        bold = { fg = "#cb4b16" }
+        italic = { fg = "#2aa198" }
+        inline_code = { fg = "#d33682", bg = "#073642" }
+        quote = { fg = "#586e75", char = "▌" }
+        bullet = { fg = "#268bd2", char = "•" }
+    "##),
+    ("gruvbox", r##"
+        headers = { fg = "#fabd2f" }
+        bold = { fg = "#fe8019" }
+        italic = { fg = "#8ec07c" }
+        inline_code = { fg = "#d3869b", bg = "#3c3836" }
+        quote = { fg = "#928374", char = "▌" }
+        bullet = { fg = "#b8bb26", char = "•" }
+    "##),
+    ("plain", r##"
+        quote = { char = "|" }
+        bullet = { char = "*" }
+    "##),
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StyleDef {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    /// Only used for quotes and bullets.
+    pub char: Option<char>
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SkinDef {
+    pub headers: StyleDef,
+    pub bold: StyleDef,
+    pub italic: StyleDef,
+    pub inline_code: StyleDef,
+    pub quote: StyleDef,
+    pub bullet: StyleDef
+}
+
+/// How many colours the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor
+}
+
+impl ColorDepth {
+    pub fn detect() -> ColorDepth {
+        Self::from_env(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref()
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> ColorDepth {
+        if matches!(colorterm, Some("truecolor") | Some("24bit")) {
+            ColorDepth::TrueColor
+        } else if term.map(|t| t.contains("256color")).unwrap_or(false) {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+const NAMED: &[(&str, Color, (u8, u8, u8))] = &[
+    ("black", Color::Black, (0, 0, 0)),
+    ("dark_red", Color::DarkRed, (128, 0, 0)),
+    ("dark_green", Color::DarkGreen, (0, 128, 0)),
+    ("dark_yellow", Color::DarkYellow, (128, 128, 0)),
+    ("dark_blue", Color::DarkBlue, (0, 0, 128)),
+    ("dark_magenta", Color::DarkMagenta, (128, 0, 128)),
+    ("dark_cyan", Color::DarkCyan, (0, 128, 128)),
+    ("grey", Color::Grey, (192, 192, 192)),
+    ("dark_grey", Color::DarkGrey, (128, 128, 128)),
+    ("red", Color::Red, (255, 0, 0)),
+    ("green", Color::Green, (0, 255, 0)),
+    ("yellow", Color::Yellow, (255, 255, 0)),
+    ("blue", Color::Blue, (0, 0, 255)),
+    ("magenta", Color::Magenta, (255, 0, 255)),
+    ("cyan", Color::Cyan, (0, 255, 255)),
+    ("white", Color::White, (255, 255, 255)),
+];
+
+/// Parses a colour and brings it down to `depth`.
+pub fn parse_color(spec: &str, depth: ColorDepth) -> Result<Color, String> {
+    let spec = spec.trim().to_lowercase();
+    if let Some((_, color, _)) = NAMED.iter().find(|(name, _, _)| *name == spec) {
+        return Ok(*color);
+    }
+    if let Ok(index) = spec.parse::<u8>() {
+        return Ok(match depth {
+            ColorDepth::Ansi16 => nearest_named(ansi_to_rgb(index)),
+            _ => Color::AnsiValue(index),
+        });
+    }
+
+    let invalid = || format!("invalid colour {:?}, expected #rrggbb, 0-255 or a colour name", spec);
+    let hex = spec.strip_prefix('#').filter(|h| h.len() == 6).ok_or_else(invalid)?;
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(invalid)
+    };
+    let rgb = (channel(0)?, channel(2)?, channel(4)?);
+
+    Ok(match depth {
+        ColorDepth::TrueColor => Color::Rgb { r: rgb.0, g: rgb.1, b: rgb.2 },
+        ColorDepth::Ansi256 => Color::AnsiValue(rgb_to_ansi(rgb)),
+        ColorDepth::Ansi16 => nearest_named(rgb),
+    })
+}
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Closest entry of the 256 colour palette, from the 6x6x6 cube or the
+/// grey ramp.
+fn rgb_to_ansi(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..6).min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs()).unwrap()
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+    let cube_rgb = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let grey_index = ((average.saturating_sub(3)) / 10).min(23) as u8;
+    let grey_level = 8 + 10 * grey_index;
+
+    if distance(rgb, (grey_level, grey_level, grey_level)) < distance(rgb, cube_rgb) {
+        232 + grey_index
+    } else {
+        cube
+    }
+}
+
+fn ansi_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => NAMED[index as usize].2,
+        16..=231 => {
+            let i = (index - 16) as usize;
+            (CUBE_LEVELS[i / 36], CUBE_LEVELS[(i / 6) % 6], CUBE_LEVELS[i % 6])
+        },
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        },
+    }
+}
+
+fn nearest_named(rgb: (u8, u8, u8)) -> Color {
+    NAMED.iter().min_by_key(|(_, _, named)| distance(rgb, *named)).unwrap().1
+}
+
+/// Builds a termimad skin. Anything the definition leaves out keeps
+/// termimad's default style.
+pub fn build(def: &SkinDef, depth: ColorDepth) -> Result<MadSkin, String> {
     let mut skin = MadSkin::default();
+    let color = |c: &Option<String>| c.as_deref().map(|c| parse_color(c, depth)).transpose();
+
+    if let Some(fg) = color(&def.headers.fg)? {
+        skin.set_headers_fg(fg);
+    }
+    if let Some(bg) = color(&def.headers.bg)? {
+        skin.set_headers_bg(bg);
+    }
+    for (style, compound) in [
+        (&def.bold, &mut skin.bold),
+        (&def.italic, &mut skin.italic),
+        (&def.inline_code, &mut skin.inline_code),
+    ] {
+        if let Some(fg) = color(&style.fg)? {
+            compound.set_fg(fg);
+        }
+        if let Some(bg) = color(&style.bg)? {
+            compound.set_bg(bg);
+        }
+    }
+    for (style, styled_char) in [(&def.quote, &mut skin.quote_mark), (&def.bullet, &mut skin.bullet)] {
+        if let Some(c) = style.char {
+            *styled_char = StyledChar::nude(c);
+        }
+        if let Some(fg) = color(&style.fg)? {
+            styled_char.set_fg(fg);
+        }
+        if let Some(bg) = color(&style.bg)? {
+            styled_char.set_bg(bg);
+        }
+    }
+
+    Ok(skin)
+}
+
+/// The skin called `name` (or "default"), looked up first among the user's
+/// skins and then the built-in ones. Unstyled if `NO_COLOR` is set.
+pub fn skin(name: Option<&str>, custom: &HashMap<String, SkinDef>) -> Result<MadSkin, Box<dyn Error>> {
+    let name = name.unwrap_or("default");
+    let def = match custom.get(name) {
+        Some(def) => def.clone(),
+        None => match BUILTIN.iter().find(|(n, _)| *n == name) {
+            Some((_, def)) => toml::from_str(def)?,
+            None => {
+                let mut names: Vec<&str> = BUILTIN.iter().map(|(n, _)| *n).chain(custom.keys().map(|n| n.as_str())).collect();
+                names.sort_unstable();
+                return Err(format!("unknown theme {:?}, pick one of {}", name, names.join(", ")).into());
+            },
+        },
+    };
+
+    if std::env::var("NO_COLOR").map(|v| !v.is_empty()).unwrap_or(false) {
+        return Ok(MadSkin::no_style());
+    }
+    Ok(build(&def, ColorDepth::detect())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_adapt_to_the_terminal() {
+        assert_eq!(parse_color("#ff00c8", ColorDepth::TrueColor), Ok(Color::Rgb { r: 255, g: 0, b: 200 }));
+        assert_eq!(parse_color("#ff00c8", ColorDepth::Ansi256), Ok(Color::AnsiValue(200)));
+        assert_eq!(parse_color("#ff00c8", ColorDepth::Ansi16), Ok(Color::Magenta));
+        assert_eq!(parse_color("#1c1c1c", ColorDepth::Ansi256), Ok(Color::AnsiValue(234)));
+        assert_eq!(parse_color("#1c1c1c", ColorDepth::Ansi16), Ok(Color::Black));
+        assert_eq!(parse_color("208", ColorDepth::Ansi256), Ok(Color::AnsiValue(208)));
+        assert_eq!(parse_color("Yellow", ColorDepth::Ansi16), Ok(Color::Yellow));
+        assert!(parse_color("#12345", ColorDepth::TrueColor).is_err());
+        assert!(parse_color("#ééé", ColorDepth::TrueColor).is_err());
+        assert!(parse_color("chartreuse", ColorDepth::TrueColor).is_err());
+    }
+
+    #[test]
+    fn colour_depth_comes_from_the_environment() {
+        assert_eq!(ColorDepth::from_env(Some("truecolor"), Some("xterm-256color")), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env(None, Some("xterm-256color")), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::from_env(None, Some("linux")), ColorDepth::Ansi16);
+        assert_eq!(ColorDepth::from_env(None, None), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn builtin_and_custom_skins_build() {
+        for (_, def) in BUILTIN {
+            let def: SkinDef = toml::from_str(def).unwrap();
+            assert!(build(&def, ColorDepth::Ansi16).is_ok());
+        }
 
-    skin.bold.set_fg(Yellow);
-    skin.italic.set_bg(Rgb {
-        r: 28,
-        g: 28,
-        b: 28,
-    });
-    skin.bullet = StyledChar::from_fg_char(Yellow, '⟡');
-    skin.set_headers_fg(Yellow);
-    skin.quote_mark = StyledChar::from_fg_char(Yellow, '▐');
-    skin.quote_mark.set_fg(Rgb {
-        r: 215,
-        g: 255,
-        b: 135,
-    });
-    skin.inline_code.set_fg(Rgb {
-        r: 255,
-        g: 0,
-        b: 200,
-    });
-    skin.italic.set_fg(Rgb {
-        r: 215,
-        g: 255,
-        b: 0,
-    });
-
-    skin    
+        let def: SkinDef = toml::from_str(r#"bullet = { fg = "nope" }"#).unwrap();
+        assert!(build(&def, ColorDepth::TrueColor).is_err());
+        assert!(skin(Some("nope"), &HashMap::new()).is_err());
+    }
 }
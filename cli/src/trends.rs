@@ -0,0 +1,70 @@
+//! `clinews trends`: the terms standing out in the articles collected today,
+//! against the rest of the archive.
+
+use newsapi::keywords::{sparkline, Corpus};
+
+/// The `top` trending terms of `today`, with their articles over the last
+/// `days` days.
+pub fn markdown(corpus: &Corpus, today: i64, days: i64, top: usize) -> String {
+    let trends = corpus.trending(today, top);
+    let mut lines = vec![
+        "# Trending today".to_string(),
+        format!("*in {} articles, against the ones before*", corpus.documents(today)),
+    ];
+    if trends.is_empty() {
+        lines.push("Nothing stands out yet.".to_string());
+        return lines.join("\n");
+    }
+    lines.push(format!("|term|today|last {} days|", days));
+    lines.push("|:-|-:|:-|".to_string());
+    for trend in trends {
+        let history = corpus.history(&trend.term, today - days + 1..=today);
+        lines.push(format!("|{}|{}|`{}`|", trend.term, trend.articles, sparkline(&history)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Archived;
+    use newsapi::archive::{corpus, day};
+    use newsapi::filter::{Action, FilterEngine, FilterRule, Matcher};
+
+    fn archived(n: i64, title: &str, seen_at: i64) -> Archived {
+        let article = serde_json::from_value(serde_json::json!({
+            "source": { "id": null, "name": "Wire" },
+            "title": title,
+            "url": format!("https://wire.example/{}", n),
+            "description": null
+        })).unwrap();
+        Archived { seen_at, article }
+    }
+
+    #[test]
+    fn lists_terms_with_their_history() {
+        let day_secs = 86_400;
+        // 23:00 UTC is already the next day an hour ahead.
+        assert_eq!(day(day_secs - 3600, 3600), 1);
+        let archive = vec![
+            archived(1, "Storm hits the coast", day_secs),
+            archived(2, "Storm damage counted", 2 * day_secs),
+            archived(3, "Election results are in", 3 * day_secs),
+            archived(4, "Election: a recount in the north", 3 * day_secs),
+            archived(5, "Storm clean-up starts", 3 * day_secs),
+            archived(6, "Storm gossip", 3 * day_secs),
+            archived(7, "Storm over the election", 3 * day_secs),
+        ];
+        let rules = vec![FilterRule { name: None, action: Action::Exclude, matcher: Matcher::Keyword("gossip".to_string()) }];
+        let corpus = corpus(&archive, &FilterEngine::new(&rules).unwrap(), 0);
+
+        assert_eq!(markdown(&corpus, 3, 4, 5), "\
+            # Trending today\n\
+            *in 4 articles, against the ones before*\n\
+            |term|today|last 4 days|\n\
+            |:-|-:|:-|\n\
+            |election|3|`   █`|\n\
+            |storm|2|` ▄▄█`|");
+        assert!(markdown(&corpus, 4, 4, 5).ends_with("Nothing stands out yet."));
+    }
+}
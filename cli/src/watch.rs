@@ -0,0 +1,257 @@
+use crate::config::{Alert, WatchConfig};
+use crate::store::Store;
+use crate::webhook::Webhooks;
+use newsapi::filter::FilterEngine;
+use newsapi::{Article, NewsAPI};
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// Urls remembered as seen. Older ones are long out of the headlines.
+const SEEN_KEPT: usize = 5000;
+
+pub trait Notifier {
+    fn notify(&self, alert: &Alert, article: &Article) -> Result<(), Box<dyn Error>>;
+}
+
+/// Sends freedesktop notifications over D-Bus.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, alert: &Alert, article: &Article) -> Result<(), Box<dyn Error>> {
+        notify_rust::Notification::new()
+            .appname("clinews")
+            .summary(&format!("[{}] {}", alert.name, article.title()))
+            .body(article.url())
+            .show()?;
+        Ok(())
+    }
+}
+
+/// Polling interval for the watcher. Never polls more often than the daily
+/// request quota allows.
+pub fn interval(config: &WatchConfig) -> Duration {
+    let min_secs = SECONDS_PER_DAY / config.daily_quota.max(1) as u64;
+    Duration::from_secs(config.interval_secs.max(min_secs))
+}
+
+pub struct Watcher<N: Notifier> {
+    newsapi: NewsAPI,
+    filters: FilterEngine,
+    alerts: Vec<Alert>,
+    log_file: Option<PathBuf>,
+    notifier: N,
+    /// Where every article fetched is archived, for `clinews digest`.
+    archive: Option<Store>,
+    webhooks: Option<Webhooks>,
+    seen: HashSet<String>,
+    /// The urls of `seen`, oldest first, to forget them in that order.
+    seen_order: VecDeque<String>,
+    primed: bool
+}
+
+impl<N: Notifier> Watcher<N> {
+    pub fn new(newsapi: NewsAPI, filters: FilterEngine, config: &WatchConfig, notifier: N) -> Watcher<N> {
+        Watcher {
+            newsapi,
+            filters,
+            alerts: config.alerts.clone(),
+            log_file: config.log_file.clone(),
+            notifier,
+            archive: None,
+            webhooks: None,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            primed: false
+        }
+    }
+
+    pub fn archive_to(&mut self, store: Store) -> &mut Self {
+        self.archive = Some(store);
+        self
+    }
+
+    pub fn post_to(&mut self, webhooks: Webhooks) -> &mut Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Fetches once and notifies about every unseen article matching an
+    /// alert. The first poll only records what is already out there.
+    /// Returns the number of notifications sent.
+    pub fn poll(&mut self) -> Result<usize, Box<dyn Error>> {
+        let mut response = self.newsapi.fetch()?;
+        self.filters.apply(&mut response.articles);
+        if let Some(store) = &self.archive {
+            if let Err(e) = store.record(response.articles(), chrono::Utc::now().timestamp()) {
+                eprintln!("failed archiving articles: {}", e);
+            }
+        }
+
+        let mut notified = 0;
+        for article in response.articles() {
+            if !self.see(article.url()) || !self.primed {
+                continue;
+            }
+            for alert in self.alerts.iter().filter(|a| a.matches(article)) {
+                if let Err(e) = self.log_match(alert, article) {
+                    eprintln!("failed logging the match: {}", e);
+                }
+                if let Err(e) = self.notifier.notify(alert, article) {
+                    eprintln!("failed sending notification: {}", e);
+                }
+                if let Some(webhooks) = &mut self.webhooks {
+                    for e in webhooks.post(alert, article) {
+                        eprintln!("{}", e);
+                    }
+                }
+                notified += 1;
+            }
+        }
+        self.primed = true;
+
+        Ok(notified)
+    }
+
+    /// Records `url` as seen. Returns whether it wasn't already.
+    fn see(&mut self, url: &str) -> bool {
+        if !self.seen.insert(url.to_string()) {
+            return false;
+        }
+        self.seen_order.push_back(url.to_string());
+        if self.seen_order.len() > SEEN_KEPT {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    fn log_match(&self, alert: &Alert, article: &Article) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.log_file {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}",
+                chrono::Local::now().to_rfc3339(),
+                alert.name,
+                article.title(),
+                article.url()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<N: Notifier + Send + 'static> Watcher<N> {
+    /// Polls every `interval`. Polls fetch, notify and post over blocking
+    /// connections, so they run on the blocking thread pool.
+    pub async fn run(mut self, interval: Duration) {
+        loop {
+            let polled = tokio::task::spawn_blocking(move || {
+                let result = self.poll().map_err(|e| e.to_string());
+                (self, result)
+            }).await;
+            let result;
+            (self, result) = match polled {
+                Ok(polled) => polled,
+                Err(e) => {
+                    eprintln!("the watcher stopped: {}", e);
+                    return;
+                },
+            };
+            match result {
+                Ok(n) if n > 0 => println!("{} new matching articles", n),
+                Ok(_) => {},
+                Err(e) => eprintln!("failed polling for news: {}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{articles_json, tempdir, MockServer};
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct StubNotifier {
+        sent: RefCell<Vec<(String, String)>>
+    }
+
+    impl Notifier for &StubNotifier {
+        fn notify(&self, alert: &Alert, article: &Article) -> Result<(), Box<dyn Error>> {
+            self.sent.borrow_mut().push((alert.name.to_string(), article.url().to_string()));
+            Ok(())
+        }
+    }
+
+    fn config(log_file: PathBuf) -> WatchConfig {
+        WatchConfig {
+            alerts: vec![Alert { name: "rust".to_string(), keywords: vec!["Rust".to_string()] }],
+            log_file: Some(log_file),
+            ..WatchConfig::default()
+        }
+    }
+
+    #[test]
+    fn notifies_only_new_matching_articles() {
+        let server = MockServer::start(vec![
+            (200, articles_json(&[("Rust 1.62 released", "https://a.com/1"), ("Weather", "https://a.com/2")])),
+            (200, articles_json(&[
+                ("Rust 1.62 released", "https://a.com/1"),
+                ("Rust in the kernel", "https://a.com/3"),
+                ("Elections", "https://a.com/4")
+            ])),
+        ]);
+        let mut newsapi = NewsAPI::new("key");
+        newsapi.base_url(&server.url);
+
+        let dir = tempdir();
+        let log_file = dir.path().join("matches.log");
+        let notifier = StubNotifier::default();
+        let mut watcher = Watcher::new(newsapi, FilterEngine::new(&[]).unwrap(), &config(log_file.clone()), &notifier);
+
+        assert_eq!(watcher.poll().unwrap(), 0);
+        assert_eq!(watcher.poll().unwrap(), 1);
+        assert_eq!(*notifier.sent.borrow(), vec![("rust".to_string(), "https://a.com/3".to_string())]);
+
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        assert!(log.contains("rust\tRust in the kernel\thttps://a.com/3"));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert!(requests[0].path.starts_with("/top-headlines?"));
+        assert!(requests[0].body.is_empty());
+        assert!(requests[0].headers.contains(&("authorization".to_string(), "key".to_string())));
+    }
+
+    #[test]
+    fn forgets_the_oldest_urls() {
+        let mut watcher = Watcher::new(NewsAPI::new("key"), FilterEngine::new(&[]).unwrap(), &WatchConfig::default(), DesktopNotifier);
+        for n in 0..=SEEN_KEPT {
+            assert!(watcher.see(&format!("https://a.com/{}", n)));
+        }
+        assert_eq!(watcher.seen.len(), SEEN_KEPT);
+        assert!(!watcher.see(&format!("https://a.com/{}", SEEN_KEPT)));
+        assert!(watcher.see("https://a.com/0"));
+    }
+
+    #[test]
+    fn interval_respects_quota() {
+        let config = WatchConfig { interval_secs: 60, daily_quota: 100, ..WatchConfig::default() };
+        assert_eq!(interval(&config), Duration::from_secs(864));
+
+        let config = WatchConfig { interval_secs: 3600, daily_quota: 100, ..WatchConfig::default() };
+        assert_eq!(interval(&config), Duration::from_secs(3600));
+    }
+}
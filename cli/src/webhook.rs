@@ -0,0 +1,408 @@
+//! Webhook sinks for `clinews watch`: alerts posted to Slack, Discord or any
+//! other HTTP endpoint taking JSON.
+//!
+//! Every sink posts a given article at most once, across restarts too: the
+//! urls already posted are kept in the data directory.
+
+use crate::config::Alert;
+use crate::store::Store;
+use newsapi::Article;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Urls remembered per sink. Older ones are long out of the headlines.
+const REMEMBERED: usize = 1000;
+/// Discord rejects messages and embed fields past these many characters.
+const DISCORD_CONTENT: usize = 2000;
+const DISCORD_TITLE: usize = 256;
+const DISCORD_DESCRIPTION: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// `{"text": ...}`, for Slack incoming webhooks and the many chat apps
+    /// copying them (Mattermost, Rocket.Chat, ...).
+    Slack,
+    /// `{"content": ..., "embeds": [...]}`, for Discord webhooks.
+    Discord,
+    /// The alert and article fields as a flat JSON object.
+    Generic,
+    /// The `template` of the sink, with its placeholders filled in.
+    Template
+}
+
+/// A webhook from `[[watch.webhooks]]` in the config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sink {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_format")]
+    pub format: Format,
+    /// JSON payload for the template format. `{{title}}`, `{{url}}`,
+    /// `{{source}}`, `{{description}}`, `{{author}}`, `{{published_at}}`
+    /// and `{{alert}}` are replaced with the article's, JSON-escaped.
+    pub template: Option<String>,
+    /// Only post for these alerts. All of them when empty.
+    #[serde(default)]
+    pub alerts: Vec<String>,
+    /// Extra request headers, for tokens and the like.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Further attempts after a failed post.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Seconds before the first retry, doubling for every one after.
+    #[serde(default = "default_retry_secs")]
+    pub retry_secs: u64
+}
+
+fn default_format() -> Format {
+    Format::Generic
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_secs() -> u64 {
+    2
+}
+
+impl Sink {
+    fn wants(&self, alert: &Alert) -> bool {
+        self.alerts.is_empty() || self.alerts.iter().any(|a| a.eq_ignore_ascii_case(&alert.name))
+    }
+
+    /// The JSON body posted for `article`.
+    pub fn payload(&self, alert: &Alert, article: &Article) -> Result<String, Box<dyn Error>> {
+        let description = article.description().map(|s| s.as_str()).unwrap_or_default();
+        let value = match self.format {
+            Format::Slack => json!({
+                "text": format!(
+                    "*[{}]* <{}|{}>\n{}",
+                    slack_escape(&alert.name),
+                    slack_escape(article.url()),
+                    slack_escape(article.title()),
+                    slack_escape(description)
+                ).trim_end().to_string()
+            }),
+            Format::Discord => json!({
+                "content": truncate(&format!("[{}] {}", alert.name, article.title()), DISCORD_CONTENT),
+                "embeds": [{
+                    "title": truncate(article.title(), DISCORD_TITLE),
+                    "url": article.url(),
+                    "description": truncate(description, DISCORD_DESCRIPTION),
+                    "footer": { "text": article.source().name() }
+                }]
+            }),
+            Format::Generic => json!({
+                "alert": alert.name,
+                "title": article.title(),
+                "url": article.url(),
+                "source": article.source().name(),
+                "description": article.description(),
+                "author": article.author(),
+                "published_at": article.published_at()
+            }),
+            Format::Template => {
+                let template = self.template.as_deref().ok_or_else(|| format!("webhook {:?} has no template", self.name))?;
+                let body = fill(template, alert, article);
+                serde_json::from_str::<Value>(&body)
+                    .map_err(|e| format!("the template of webhook {:?} isn't valid JSON: {}", self.name, e))?;
+                return Ok(body);
+            },
+        };
+        Ok(value.to_string())
+    }
+
+    /// Posts `body`, trying again after server errors and failed connections.
+    /// Blocks for the retries too.
+    fn post(&self, body: &str) -> Result<(), Box<dyn Error>> {
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let mut delay = Duration::from_secs(self.retry_secs);
+        let mut attempt = 0;
+        loop {
+            let mut request = agent.post(&self.url).set("Content-Type", "application/json");
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            let error = match request.send_string(body) {
+                Ok(_) => return Ok(()),
+                // Other client errors won't go away by asking again.
+                Err(ureq::Error::Status(code, _)) if code != 429 && code < 500 => {
+                    return Err(format!("webhook {:?} answered {}", self.name, code).into());
+                },
+                Err(e) => e,
+            };
+            if attempt == self.retries {
+                return Err(format!("posting to webhook {:?} failed: {}", self.name, error).into());
+            }
+            attempt += 1;
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+}
+
+/// `<`, `>` and `&` are control characters in Slack messages.
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// `text` cut down to `max` characters, ending with an ellipsis if cut.
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some(_) => text.chars().take(max - 1).chain(['…']).collect(),
+        None => text.to_string(),
+    }
+}
+
+fn fill(template: &str, alert: &Alert, article: &Article) -> String {
+    let optional = |field: Option<&String>| field.map(|s| s.to_string()).unwrap_or_default();
+    let fields = [
+        ("title", article.title().to_string()),
+        ("url", article.url().to_string()),
+        ("source", article.source().name().to_string()),
+        ("description", optional(article.description())),
+        ("author", optional(article.author())),
+        ("published_at", optional(article.published_at())),
+        ("alert", alert.name.to_string()),
+    ];
+    // One pass over the template, so placeholders in the values are left
+    // as they are.
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        let field = rest.find("}}").and_then(|end| {
+            let (_, value) = fields.iter().find(|(name, _)| *name == &rest[..end])?;
+            Some((end + 2, value))
+        });
+        match field {
+            Some((len, value)) => {
+                // The value as a JSON string, without its quotes.
+                let escaped = Value::String(value.to_string()).to_string();
+                out.push_str(&escaped[1..escaped.len() - 1]);
+                rest = &rest[len..];
+            },
+            None => out.push_str("{{"),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The configured sinks, and what each of them already posted.
+pub struct Webhooks {
+    sinks: Vec<Sink>,
+    store: Option<Store>,
+    posted: HashMap<String, Vec<String>>
+}
+
+impl Webhooks {
+    pub fn new(sinks: Vec<Sink>) -> Webhooks {
+        Webhooks { sinks, store: None, posted: HashMap::new() }
+    }
+
+    /// Keeps the urls posted in `store`, loading those posted before.
+    pub fn persist_to(&mut self, store: Store) -> Result<&mut Self, Box<dyn Error>> {
+        self.posted = store.webhook_posts()?;
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Posts `article` to every sink taking `alert` that hasn't posted it yet.
+    /// Failures are reported, and don't stop the other sinks. Blocks, retries
+    /// and all, so keep it off the async runtime as `Watcher::run` does.
+    pub fn post(&mut self, alert: &Alert, article: &Article) -> Vec<Box<dyn Error>> {
+        let mut errors = vec![];
+        let mut changed = false;
+        for sink in self.sinks.iter().filter(|s| s.wants(alert)) {
+            let posted = self.posted.entry(sink.name.to_string()).or_default();
+            if posted.iter().any(|url| url == article.url()) {
+                continue;
+            }
+            match sink.payload(alert, article).and_then(|body| sink.post(&body)) {
+                Ok(()) => {
+                    posted.push(article.url().to_string());
+                    let excess = posted.len().saturating_sub(REMEMBERED);
+                    posted.drain(..excess);
+                    changed = true;
+                },
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if changed {
+            if let Some(store) = &self.store {
+                if let Err(e) = store.save_webhook_posts(&self.posted) {
+                    errors.push(e);
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that the sinks' settings make sense.
+    pub fn check(&self) -> Result<(), Box<dyn Error>> {
+        let mut names = HashSet::new();
+        for sink in &self.sinks {
+            if !names.insert(sink.name.as_str()) {
+                return Err(format!("there are two webhooks named {:?}", sink.name).into());
+            }
+            if sink.format == Format::Template && sink.template.is_none() {
+                return Err(format!("webhook {:?} uses the template format but has no template", sink.name).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{tempdir, MockServer};
+
+    fn alert() -> Alert {
+        Alert { name: "rust".to_string(), keywords: vec!["rust".to_string()] }
+    }
+
+    fn article(url: &str) -> Article {
+        serde_json::from_value(json!({
+            "source": { "id": null, "name": "Wire" },
+            "title": "Rust <1.62> \"released\"",
+            "url": url,
+            "description": "Faster & safer",
+            "author": null
+        })).unwrap()
+    }
+
+    fn sink(name: &str, url: &str, format: Format) -> Sink {
+        Sink {
+            name: name.to_string(),
+            url: url.to_string(),
+            format,
+            template: None,
+            alerts: vec![],
+            headers: HashMap::new(),
+            retries: 2,
+            retry_secs: 0
+        }
+    }
+
+    #[test]
+    fn formats_payloads() {
+        let article = article("https://wire.example/rust");
+        let payload = |sink: &Sink| serde_json::from_str::<Value>(&sink.payload(&alert(), &article).unwrap()).unwrap();
+
+        let slack = payload(&sink("chat", "", Format::Slack));
+        assert_eq!(slack["text"], "*[rust]* <https://wire.example/rust|Rust &lt;1.62&gt; \"released\">\nFaster &amp; safer");
+
+        let discord = payload(&sink("chat", "", Format::Discord));
+        assert_eq!(discord["content"], "[rust] Rust <1.62> \"released\"");
+        assert_eq!(discord["embeds"][0]["url"], "https://wire.example/rust");
+        assert_eq!(discord["embeds"][0]["footer"]["text"], "Wire");
+
+        let generic = payload(&sink("hook", "", Format::Generic));
+        assert_eq!(generic["alert"], "rust");
+        assert_eq!(generic["author"], Value::Null);
+
+        let mut templated = sink("hook", "", Format::Template);
+        templated.template = Some(r#"{"msg": "{{alert}}: {{title}}", "link": "{{url}}", "by": "{{author}}"}"#.to_string());
+        assert_eq!(payload(&templated), json!({
+            "msg": "rust: Rust <1.62> \"released\"",
+            "link": "https://wire.example/rust",
+            "by": ""
+        }));
+
+        templated.template = Some(r#"{"msg": {{title}}}"#.to_string());
+        assert!(templated.payload(&alert(), &article).is_err());
+    }
+
+    #[test]
+    fn fills_templates_in_one_pass() {
+        let article: Article = serde_json::from_value(json!({
+            "source": { "id": null, "name": "{{url}}" },
+            "title": "{{source}} in {{title}}",
+            "url": "https://wire.example/<{{alert}}>&",
+            "description": null
+        })).unwrap();
+        assert_eq!(
+            fill("{{title}} from {{source}} {{nope}} {{url", &alert(), &article),
+            "{{source}} in {{title}} from {{url}} {{nope}} {{url"
+        );
+
+        let slack = sink("chat", "", Format::Slack).payload(&alert(), &article).unwrap();
+        assert!(slack.contains("<https://wire.example/&lt;{{alert}}&gt;&amp;|"));
+    }
+
+    #[test]
+    fn keeps_discord_messages_short_enough() {
+        let mut article = serde_json::to_value(article("https://wire.example/rust")).unwrap();
+        article["title"] = json!("é".repeat(3000));
+        let article: Article = serde_json::from_value(article).unwrap();
+        let discord: Value = serde_json::from_str(&sink("chat", "", Format::Discord).payload(&alert(), &article).unwrap()).unwrap();
+        let content = discord["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), 2000);
+        assert!(content.starts_with("[rust] éé") && content.ends_with("é…"));
+        assert_eq!(discord["embeds"][0]["title"].as_str().unwrap().chars().count(), 256);
+        assert_eq!(truncate("short", 2000), "short");
+    }
+
+    #[test]
+    fn retries_and_never_posts_twice() {
+        let server = MockServer::start(vec![(503, String::new()), (200, String::new())]);
+        let dir = tempdir();
+
+        let mut hook = sink("team", &server.url, Format::Slack);
+        hook.headers.insert("X-Token".to_string(), "secret".to_string());
+        let mut other = sink("other", &server.url, Format::Generic);
+        other.alerts = vec!["elections".to_string()];
+        let mut webhooks = Webhooks::new(vec![hook.clone(), other]);
+        webhooks.persist_to(Store::at(dir.path().to_path_buf())).unwrap();
+
+        assert!(webhooks.post(&alert(), &article("https://wire.example/1")).is_empty());
+        assert!(webhooks.post(&alert(), &article("https://wire.example/1")).is_empty());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, "POST");
+        assert!(requests[1].headers.contains(&("x-token".to_string(), "secret".to_string())));
+        assert!(requests[1].headers.contains(&("content-type".to_string(), "application/json".to_string())));
+        assert!(requests[1].body.contains("wire.example/1"));
+
+        // What was posted is remembered across restarts.
+        let mut webhooks = Webhooks::new(vec![hook]);
+        webhooks.persist_to(Store::at(dir.path().to_path_buf())).unwrap();
+        assert!(webhooks.post(&alert(), &article("https://wire.example/1")).is_empty());
+        assert_eq!(server.requests().len(), 2);
+        assert!(webhooks.post(&alert(), &article("https://wire.example/2")).is_empty());
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_retries() {
+        let server = MockServer::start(vec![(500, String::new())]);
+        let mut webhooks = Webhooks::new(vec![sink("team", &server.url, Format::Discord)]);
+        let errors = webhooks.post(&alert(), &article("https://wire.example/1"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(server.requests().len(), 3);
+
+        // Not posted, so it's tried again next time.
+        webhooks.post(&alert(), &article("https://wire.example/1"));
+        assert_eq!(server.requests().len(), 6);
+
+        let server = MockServer::start(vec![(404, String::new())]);
+        let mut webhooks = Webhooks::new(vec![sink("team", &server.url, Format::Discord)]);
+        assert_eq!(webhooks.post(&alert(), &article("https://wire.example/1")).len(), 1);
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+//! The export window, writing the current feed into a reading digest in the
+//! downloads folder. Native only: the web build has no files to write to.
+
+use newsapi::export::{timestamp, Digest, Format, GroupBy, Item};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{SystemTime, UNIX_EPOCH};
+use eframe::egui::{ComboBox, Context, Window};
+
+enum ExportStatus {
+    Idle,
+    Working(Receiver<Result<PathBuf, String>>),
+    Done(PathBuf),
+    Failed(String)
+}
+
+pub struct ExportDialog {
+    pub open: bool,
+    format: Format,
+    group_by: GroupBy,
+    full_text: bool,
+    status: ExportStatus
+}
+
+impl Default for ExportDialog {
+    fn default() -> Self {
+        ExportDialog {
+            open: false,
+            format: Format::Markdown,
+            group_by: GroupBy::Source,
+            full_text: false,
+            status: ExportStatus::Idle
+        }
+    }
+}
+
+impl ExportDialog {
+    fn poll(&mut self) {
+        if let ExportStatus::Working(rx) = &self.status {
+            if let Ok(result) = rx.try_recv() {
+                self.status = match result {
+                    Ok(path) => {
+                        tracing::info!("Exported to {}", path.display());
+                        ExportStatus::Done(path)
+                    },
+                    Err(e) => {
+                        tracing::error!("Export failed: {}", e);
+                        ExportStatus::Failed(e)
+                    }
+                };
+            }
+        }
+    }
+
+    /// Draws the window. Returns true when the export button was clicked.
+    pub fn render(&mut self, ctx: &Context) -> bool {
+        self.poll();
+
+        let mut open = self.open;
+        let mut start = false;
+        Window::new("Export")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ComboBox::from_label("format")
+                    .selected_text(self.format.name())
+                    .show_ui(ui, |ui| {
+                        for format in Format::ALL {
+                            ui.selectable_value(&mut self.format, format, format.name());
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("group by");
+                    for group_by in GroupBy::ALL {
+                        ui.radio_value(&mut self.group_by, group_by, group_by.name());
+                    }
+                });
+                ui.checkbox(&mut self.full_text, "include the full text");
+
+                let working = matches!(self.status, ExportStatus::Working(_));
+                ui.horizontal(|ui| {
+                    start = ui.add_enabled(!working, eframe::egui::Button::new("export")).clicked();
+                    if working {
+                        ui.spinner();
+                    }
+                });
+                match &self.status {
+                    ExportStatus::Done(path) => {
+                        ui.label(format!("Saved to {}", path.display()));
+                    },
+                    ExportStatus::Failed(e) => {
+                        ui.colored_label(eframe::egui::Color32::RED, e);
+                    },
+                    _ => {},
+                }
+            });
+        self.open = open;
+        start
+    }
+
+    /// Writes `items` out in the background, extracting their full text
+    /// first if asked to.
+    pub fn start(&mut self, mut items: Vec<Item>) {
+        let (tx, rx) = channel();
+        let (format, group_by, full_text) = (self.format, self.group_by, self.full_text);
+        std::thread::spawn(move || {
+            if full_text {
+                fetch_texts(&mut items);
+            }
+            let _ = tx.send(write(items, format, group_by));
+        });
+        self.status = ExportStatus::Working(rx);
+    }
+}
+
+/// Pages downloaded at once when extracting the full text.
+const MAX_IN_FLIGHT: usize = 8;
+
+fn fetch_texts(items: &mut [Item]) {
+    for batch in items.chunks_mut(MAX_IN_FLIGHT) {
+        std::thread::scope(|scope| {
+            for item in batch {
+                scope.spawn(move || item.text = newsapi::extract::fetch(&item.url).ok());
+            }
+        });
+    }
+}
+
+fn write(items: Vec<Item>, format: Format, group_by: GroupBy) -> Result<PathBuf, String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let stamp = timestamp(now);
+    let date = &stamp[..10];
+    let mut digest = Digest::new(&format!("Headlines {}", date), items);
+    digest.group_by(group_by).created(now);
+
+    // Named down to the second, and never written over, so exports made
+    // the same day are all kept.
+    let dir = dirs::download_dir().or_else(dirs::home_dir).ok_or("no downloads folder on this system")?;
+    let time = stamp[11..19].replace(':', "");
+    let path = dir.join(format!("headlines-{}-{}.{}", date, time, format.extension()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(&digest.render(format)))
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    Ok(path)
+}
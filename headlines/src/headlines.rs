@@ -1,9 +1,32 @@
-use crate::fetch_news;
+use crate::{fetch_news, fetch_sources};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::export::ExportDialog;
+use crate::images::{ImageCache, ImageState};
+use crate::keys::{Action, KeyBindings};
+use crate::reader::Reader;
+use crate::summaries::SummaryCache;
+use crate::theme::ThemeSet;
+use crate::trending::Trending;
 #[cfg(target_arch = "wasm32")]
-use crate::fetch_web;
+use crate::{fetch_web, fetch_sources_web};
+use newsapi::{Category, Country, NewsAPI, NewsAPIError, SourceInfo};
+use newsapi::filter::FilterRule;
+use newsapi::language;
+use newsapi::rank::{Candidate, Click, Preferences, Ranker, SortMode, CLICKS_KEPT};
+use newsapi::sentiment::Tone;
+#[cfg(not(target_arch = "wasm32"))]
+use newsapi::export::Item;
 use serde::{ Serialize, Deserialize };
-use std::sync::mpsc::{ Receiver, Sender, channel, sync_channel, SyncSender };
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{ Receiver, Sender, channel };
 use eframe::egui::{
+    ComboBox,
+    DragValue,
+    Slider,
+    SidePanel,
+    ScrollArea,
+    TextEdit,
     Window,
     Color32,
     RichText,
@@ -15,36 +38,305 @@ use eframe::egui::{
 };
 
 pub const PADDING: f32 = 5.0;
-const WHITE: Color32 = Color32::from_rgb(255, 255, 255);
-const BLACK: Color32 = Color32::from_rgb(0, 0, 0);
+const THUMBNAIL_WIDTH: f32 = 96.0;
+const THUMBNAIL_HEIGHT: f32 = 72.0;
+const DEFAULT_FONT_SIZE: f32 = 14.0;
 const RED: Color32 = Color32::from_rgb(255, 0, 0);
-const CYAN: Color32 = Color32::from_rgb(0, 255, 255);
 
-pub enum Msg {
-    ApiKeySet(String)
+/// The newsapi parameters a feed is fetched with. Every distinct query gets
+/// its own tab worth of articles.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedQuery {
+    pub country: Country,
+    pub category: Option<Category>,
+    /// newsapi source id. When set, country and category are ignored.
+    pub source: Option<String>,
+    pub page_size: u32
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+impl Default for FeedQuery {
+    fn default() -> Self {
+        FeedQuery {
+            country: Country::Us,
+            category: None,
+            source: None,
+            page_size: 20
+        }
+    }
+}
+
+impl FeedQuery {
+    pub fn newsapi(&self, api_key: &str) -> NewsAPI {
+        let mut newsapi = NewsAPI::new(api_key);
+        newsapi
+            .country(self.country)
+            .category(self.category)
+            .sources(self.source.clone())
+            .page_size(self.page_size);
+        newsapi
+    }
+
+    /// The part of the query the source list depends on.
+    fn sources_key(&self) -> FeedQuery {
+        FeedQuery {
+            source: None,
+            page_size: 0,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct HeadlinesConfig {
+    /// Only read, to pick the theme of configs saved before themes existed.
+    #[serde(default, skip_serializing)]
     pub dark_mode: bool,
-    pub api_key: String
+    /// Name of the selected theme.
+    #[serde(default)]
+    pub theme: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub filters: Vec<FilterRule>,
+    /// Minutes between background refreshes, 0 disables them.
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval_mins: u32,
+    /// The selected feed, restored on the next start.
+    #[serde(default)]
+    pub query: FeedQuery,
+    #[serde(default = "default_font_size")]
+    pub font_size: f32,
+    /// Sentences in the summary on each card, 0 shows the description.
+    #[serde(default = "default_summary_sentences")]
+    pub summary_sentences: usize,
+    #[serde(default)]
+    pub keys: KeyBindings,
+    #[serde(default)]
+    pub saved: Vec<SavedArticle>,
+    /// Urls of the articles marked as read.
+    #[serde(default)]
+    pub read: HashSet<String>,
+    #[serde(default)]
+    pub sort: SortMode,
+    /// What the "for you" order goes by.
+    #[serde(default)]
+    pub ranking: Preferences,
+    /// Articles opened, oldest first.
+    #[serde(default)]
+    pub clicks: Vec<Click>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedArticle {
+    pub title: String,
+    pub url: String
+}
+
+fn default_refresh_interval() -> u32 {
+    15
+}
+
+fn default_font_size() -> f32 {
+    DEFAULT_FONT_SIZE
+}
+
+/// Summaries download every article page, so they are off unless asked for.
+fn default_summary_sentences() -> usize {
+    0
+}
+
+impl Default for HeadlinesConfig {
+    fn default() -> Self {
+        HeadlinesConfig {
+            dark_mode: false,
+            theme: "light".to_string(),
+            api_key: String::new(),
+            filters: Vec::new(),
+            refresh_interval_mins: default_refresh_interval(),
+            query: FeedQuery::default(),
+            font_size: default_font_size(),
+            summary_sentences: default_summary_sentences(),
+            keys: KeyBindings::default(),
+            saved: Vec::new(),
+            read: HashSet::new(),
+            sort: SortMode::default(),
+            ranking: Preferences::default(),
+            clicks: Vec::new()
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct NewsCardData {
     pub title: String,
     pub url: String,
-    pub description: String
+    /// newsapi's id of the publication, when it has one.
+    pub source_id: Option<String>,
+    /// Name of the publication.
+    pub source: String,
+    pub author: Option<String>,
+    /// Empty when the API has none.
+    pub description: String,
+    pub image_url: Option<String>,
+    /// RFC 3339 timestamp.
+    pub published_at: Option<String>,
+    /// ISO 639-1 code of the language the article is in, when detected.
+    pub language: Option<&'static str>,
+    /// Compound sentiment of the title and description, from -1 to 1.
+    pub sentiment: f64
+}
+
+impl<'a> From<&'a NewsCardData> for Candidate<'a> {
+    fn from(card: &'a NewsCardData) -> Self {
+        Candidate {
+            title: &card.title,
+            description: &card.description,
+            source_id: card.source_id.as_deref(),
+            source: &card.source,
+            published_at: card.published_at.as_deref()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub enum FetchStatus {
+    #[default]
+    Loading,
+    Loaded(usize),
+    Failed(NewsAPIError)
+}
+
+/// What the fetchers send back to the UI, tagged with the query they were
+/// fetching for: the progress of a fetch, the articles themselves or the
+/// sources available for the query.
+#[derive(Debug)]
+pub enum NewsMsg {
+    Status(FetchStatus),
+    Article(NewsCardData),
+    Sources(Vec<SourceInfo>)
 }
 
+/// Articles and fetch state of a single query.
 #[derive(Default)]
-pub struct Headlines {
+pub struct Feed {
     pub articles: Vec<NewsCardData>,
+    pub status: FetchStatus,
+    /// egui clock time (in seconds) of the last fetch started and of the
+    /// last article received.
+    pub last_fetch: f64,
+    pub last_updated: Option<f64>,
+    /// Where the next unseen article of a refresh goes, so that new stories
+    /// end up on top in the order the API returned them.
+    merge_index: Option<usize>
+}
+
+impl Feed {
+    fn receive(&mut self, msg: NewsMsg, now: f64) {
+        match msg {
+            NewsMsg::Status(status) => {
+                if let FetchStatus::Loaded(_) = status {
+                    self.last_updated = Some(now);
+                }
+                self.status = status;
+            },
+            NewsMsg::Article(news) => {
+                self.last_updated = Some(now);
+                if self.articles.iter().any(|a| a.url == news.url) {
+                    return;
+                }
+                match self.merge_index.as_mut() {
+                    Some(i) => {
+                        self.articles.insert(*i, news);
+                        *i += 1;
+                    },
+                    None => self.articles.push(news),
+                }
+            },
+            NewsMsg::Sources(_) => {}
+        }
+    }
+}
+
+/// Everything the order of the articles on screen depends on.
+#[derive(Debug, Clone, PartialEq)]
+struct OrderKey {
+    query: FeedQuery,
+    last_updated: Option<f64>,
+    articles: usize,
+    search: String,
+    sort: SortMode,
+    ranking: Preferences,
+    /// How many articles were opened, and when the last one was.
+    clicks: (usize, Option<i64>),
+    /// Recency changes with time, minute by minute is plenty.
+    minute: i64
+}
+
+#[derive(Default)]
+pub struct Headlines {
+    pub feeds: HashMap<FeedQuery, Feed>,
+    /// Sources per country and category. `None` while loading.
+    pub sources: HashMap<FeedQuery, Option<Vec<SourceInfo>>>,
     pub config: HeadlinesConfig,
     pub api_key_initialized: bool,
-    pub news_rx: Option<Receiver<NewsCardData>>,
-    pub news_tx: Option<Sender<NewsCardData>>,
-    pub app_tx: Option<SyncSender<Msg>>
+    pub news_rx: Option<Receiver<(FeedQuery, NewsMsg)>>,
+    pub news_tx: Option<Sender<(FeedQuery, NewsMsg)>>,
+    pub refresh_paused: bool,
+    pub show_settings: bool,
+    pub images: ImageCache,
+    pub themes: ThemeSet,
+    pub summaries: SummaryCache,
+    pub trending: Trending,
+    /// Article open in the reader view, if any.
+    pub reader: Option<Reader>,
+    /// Url of the card highlighted with the keyboard.
+    pub selected: Option<String>,
+    pub search: String,
+    pub show_help: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub export: ExportDialog,
+    /// Set to true to focus the search box on the next frame, false to
+    /// leave it.
+    search_focus: Option<bool>,
+    scroll_to_selected: bool,
+    /// The last order worked out by `article_order`, as ranking and
+    /// clustering every frame is too slow.
+    order: RefCell<Option<(OrderKey, Vec<usize>)>>,
+    /// Key the articles on screen were fetched with.
+    fetched_api_key: String,
+    applied_font_size: f32
+}
+
+fn matches_search(article: &NewsCardData, needle: &str) -> bool {
+    needle.is_empty()
+        || article.title.to_lowercase().contains(needle)
+        || article.description.to_lowercase().contains(needle)
+}
+
+/// Draws a loaded thumbnail scaled to fit the card, or a placeholder while
+/// it loads or if it couldn't be loaded.
+fn render_thumbnail(ui: &mut eframe::egui::Ui, state: &ImageState) {
+    let box_size = Vec2::new(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT);
+    match state {
+        ImageState::Ready(texture) => {
+            let size = texture.size_vec2();
+            let scale = (box_size.x / size.x).min(box_size.y / size.y);
+            ui.image(texture, size * scale);
+        },
+        ImageState::Loading | ImageState::Failed => {
+            let (rect, _) = ui.allocate_exact_size(box_size, eframe::egui::Sense::hover());
+            ui.painter().rect_filled(rect, 4., ui.visuals().faint_bg_color);
+            if let ImageState::Loading = state {
+                ui.painter().text(
+                    rect.center(),
+                    eframe::egui::Align2::CENTER_CENTER,
+                    "...",
+                    TextStyle::Body.resolve(ui.style()),
+                    ui.visuals().weak_text_color(),
+                );
+            }
+        }
+    }
 }
 
 /// This function has been taken as is from the egui examples
@@ -83,102 +375,594 @@ impl Headlines {
         setup_custom_fonts(&cc.egui_ctx);
 
         let mut config = HeadlinesConfig::default();
+        #[cfg(target_arch = "wasm32")]
+        let mut trending = Trending::default();
 
         if let Some(storage) = cc.storage {
             config = eframe::get_value(storage, "headlines").unwrap_or_default();
+            #[cfg(target_arch = "wasm32")]
+            {
+                trending = Trending::new(eframe::get_value(storage, "corpus").unwrap_or_default());
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let trending = Trending::from_archive(&config.filters);
+        if config.theme.is_empty() {
+            config.theme = if config.dark_mode { "dark" } else { "light" }.to_string();
         }
 
-        let articles: Vec<NewsCardData> = Vec::new();
+        let mut feeds = HashMap::new();
+        feeds.insert(config.query.clone(), Feed::default());
 
         let api_key = config.api_key.to_string();
+        let query = config.query.clone();
+        let filters = config.filters.clone();
         let (news_tx, news_rx) = channel();
         let news_tx_ = news_tx.clone();
-        let (app_tx, app_rx) = sync_channel(1);
 
+        // Without a key there's nothing to fetch yet: entering one reloads
+        // the feeds.
         #[cfg(not(target_arch="wasm32"))]
-        std::thread::spawn(move || {
-            if !api_key.is_empty() {
-                fetch_news(&api_key, &news_tx);
-            } else {
-                tracing::debug!("here");
-                loop {
-                    tracing::debug!("herehere");
-                    match app_rx.recv() {
-                        Ok(Msg::ApiKeySet(api_key)) => {
-                            tracing::info!("received api_key msg!");
-                            fetch_news(&api_key, &news_tx);
-                        },
-                        Err(e) => {
-                            tracing::error!("failed receiving message: {}", e);
-                        }
-                    }
-                }
-            }
-        });
+        if !api_key.is_empty() {
+            std::thread::spawn(move || {
+                fetch_news(&api_key, &query, &filters, &news_tx);
+            });
+        }
 
         #[cfg(target_arch="wasm32")]
         let api_key_web = config.api_key.clone();
         #[cfg(target_arch="wasm32")]
+        let query_web = config.query.clone();
+        #[cfg(target_arch="wasm32")]
+        let filters_web = config.filters.clone();
+        #[cfg(target_arch="wasm32")]
         let news_tx_web = news_tx_.clone();
         #[cfg(target_arch="wasm32")]
         gloo_timers::callback::Timeout::new(10, move || {
             wasm_bindgen_futures::spawn_local(async {
-                fetch_web(api_key_web, news_tx_web).await;
+                fetch_web(api_key_web, query_web, filters_web, news_tx_web).await;
             })
         }).forget();
 
-        #[cfg(target_arch="wasm32")]
-        let news_tx_web_ = news_tx_.clone();
-        #[cfg(target_arch="wasm32")]
-        gloo_timers::callback::Interval::new(500, move || {
-            match app_rx.try_recv() {
-                Ok(Msg::ApiKeySet(api_key)) => {
-                    wasm_bindgen_futures::spawn_local(fetch_web(api_key.clone(), news_tx_web_.clone()));
-                }
-                Err(e) => {
-                    tracing::error!("failed receiving msg: {}", e);
-                }
-            }
-        }).forget();
-
         Headlines {
             api_key_initialized: !config.api_key.is_empty(),
-            articles,
+            fetched_api_key: config.api_key.clone(),
+            applied_font_size: DEFAULT_FONT_SIZE,
+            show_settings: false,
+            feeds,
+            sources: HashMap::new(),
+            images: ImageCache::default(),
+            themes: ThemeSet::default(),
+            summaries: SummaryCache::default(),
+            trending,
+            reader: None,
+            selected: None,
+            search: String::new(),
+            show_help: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            export: ExportDialog::default(),
+            search_focus: None,
+            scroll_to_selected: false,
+            order: RefCell::new(None),
             config,
             news_rx: Some(news_rx),
             news_tx: Some(news_tx_),
-            app_tx: Some(app_tx)
+            refresh_paused: false
+        }
+    }
+
+    /// The feed of the current selection.
+    pub fn feed(&self) -> Option<&Feed> {
+        self.feeds.get(&self.config.query)
+    }
+
+    /// Fetches the selected feed and its source list the first time they are
+    /// looked at. Switching back to a feed shows what was already fetched.
+    pub fn ensure_feed(&mut self, now: f64) {
+        if !self.feeds.contains_key(&self.config.query) {
+            self.feeds.insert(self.config.query.clone(), Feed::default());
+            self.spawn_fetch(now);
+        }
+
+        let sources_key = self.config.query.sources_key();
+        if !self.sources.contains_key(&sources_key) {
+            self.sources.insert(sources_key.clone(), None);
+            self.spawn_sources_fetch(sources_key);
+        }
+    }
+
+    /// Fetches the selected feed again in the background. Articles already
+    /// on screen are kept, new ones are merged in on top as they arrive.
+    pub fn refresh(&mut self, now: f64) {
+        tracing::info!("Refreshing article list.");
+        if let Some(feed) = self.feeds.get_mut(&self.config.query) {
+            feed.merge_index = Some(0);
+        }
+        self.spawn_fetch(now);
+    }
+
+    /// Throws away every feed and fetches the selected one from scratch,
+    /// for when the API key changed.
+    pub fn reload(&mut self, now: f64) {
+        tracing::info!("Reloading all feeds.");
+        self.feeds.clear();
+        self.sources.clear();
+        self.ensure_feed(now);
+    }
+
+    fn spawn_fetch(&mut self, now: f64) {
+        self.fetched_api_key = self.config.api_key.clone();
+        let feed = self.feeds.entry(self.config.query.clone()).or_default();
+        feed.last_fetch = now;
+        feed.status = FetchStatus::Loading;
+
+        if let Some(tx) = &self.news_tx {
+            let tx_ = tx.clone();
+            let api_key = self.config.api_key.clone();
+            let query = self.config.query.clone();
+            let filters = self.config.filters.clone();
+
+            #[cfg(not(target_arch="wasm32"))]
+            std::thread::spawn(move || {
+                fetch_news(&api_key, &query, &filters, &tx_);
+            });
+
+            #[cfg(target_arch="wasm32")]
+            wasm_bindgen_futures::spawn_local(async {
+                fetch_web(api_key, query, filters, tx_).await;
+            });
+        }
+    }
+
+    fn spawn_sources_fetch(&self, query: FeedQuery) {
+        if let Some(tx) = &self.news_tx {
+            let tx_ = tx.clone();
+            let api_key = self.config.api_key.clone();
+
+            #[cfg(not(target_arch="wasm32"))]
+            std::thread::spawn(move || {
+                fetch_sources(&api_key, &query, &tx_);
+            });
+
+            #[cfg(target_arch="wasm32")]
+            wasm_bindgen_futures::spawn_local(async {
+                fetch_sources_web(api_key, query, tx_).await;
+            });
+        }
+    }
+
+    /// Triggers a refresh of the selected feed once the configured interval
+    /// has elapsed. Driven by the egui clock so it behaves the same natively
+    /// and on the web.
+    pub fn auto_refresh(&mut self, now: f64) {
+        let interval = self.config.refresh_interval_mins as f64 * 60.;
+        if self.refresh_paused || interval <= 0. {
+            return;
+        }
+        let due = self.feed().map(|f| now - f.last_fetch >= interval).unwrap_or(false);
+        if due {
+            self.refresh(now);
         }
     }
-  
-    pub fn render_news_cards(&self, ui: &mut eframe::egui::Ui) {
-        for a in &self.articles {
-            ui.add_space(PADDING);
 
-            let title = format!("> {}", a.title);
-            if self.config.dark_mode {
-                ui.colored_label(WHITE, title);
-            } else {
-                ui.colored_label(BLACK, title);
+    /// Scales every text style so that body text has the configured size.
+    pub fn apply_font_size(&mut self, ctx: &eframe::egui::Context) {
+        if self.applied_font_size == self.config.font_size {
+            return;
+        }
+        let defaults = eframe::egui::style::default_text_styles();
+        let scale = self.config.font_size / DEFAULT_FONT_SIZE;
+        let mut style = (*ctx.style()).clone();
+        for (text_style, font_id) in style.text_styles.iter_mut() {
+            if let Some(default) = defaults.get(text_style) {
+                font_id.size = default.size * scale;
             }
+        }
+        ctx.set_style(style);
+        self.applied_font_size = self.config.font_size;
+    }
 
-            ui.add_space(PADDING);
-            let description = RichText::new(&a.description).text_style(eframe::egui::TextStyle::Button);
-            ui.label(description);
-            
-            if self.config.dark_mode {
-                ui.style_mut().visuals.hyperlink_color = CYAN;
-            } else {
-                ui.style_mut().visuals.hyperlink_color = RED;
+    fn updated_label(&self, now: f64) -> String {
+        match self.feed().and_then(|f| f.last_updated) {
+            Some(t) => match ((now - t) / 60.) as u64 {
+                0 => "updated just now".to_string(),
+                1 => "updated 1 minute ago".to_string(),
+                n => format!("updated {} minutes ago", n),
+            },
+            None => "loading...".to_string(),
+        }
+    }
+
+    /// Spinner, error banner or empty state, depending on how the last fetch
+    /// of the selected feed went. Rendered above the cards.
+    pub fn render_status(&mut self, ui: &mut eframe::egui::Ui) {
+        let mut retry = false;
+        let feed = match self.feeds.get(&self.config.query) {
+            Some(feed) => feed,
+            None => return,
+        };
+
+        match &feed.status {
+            FetchStatus::Loading => {
+                ui.vertical_centered(|ui| {
+                    ui.spinner();
+                    ui.label("Fetching the latest headlines...");
+                });
+                ui.add_space(PADDING);
+            },
+            FetchStatus::Failed(e) => {
+                eframe::egui::Frame::group(ui.style()).fill(ui.visuals().faint_bg_color).show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.colored_label(RED, format!("Could not fetch news: {}", e));
+                    ui.horizontal(|ui| {
+                        if ui.button("retry").clicked() {
+                            retry = true;
+                        }
+                        if e.is_api_key_error() && ui.link("update your API key").clicked() {
+                            self.api_key_initialized = false;
+                        }
+                    });
+                });
+                ui.add_space(PADDING);
+            },
+            FetchStatus::Loaded(_) if feed.articles.is_empty() => {
+                ui.vertical_centered(|ui| {
+                    ui.label("No news right now. Try refreshing in a bit.");
+                });
+            },
+            FetchStatus::Loaded(_) => {}
+        }
+
+        if retry {
+            self.refresh(ui.input().time);
+        }
+    }
+
+    /// Cards of the selected feed. Every feed gets its own scroll area so
+    /// that switching back and forth keeps the scroll position.
+    pub fn render_feed(&mut self, ui: &mut eframe::egui::Ui) {
+        ScrollArea::new([false, true])
+            .id_source(&self.config.query)
+            .auto_shrink([false, false])
+            .always_show_scroll(false)
+            .show(ui, |ui| self.render_news_cards(ui));
+    }
+
+    pub fn render_news_cards(&mut self, ui: &mut eframe::egui::Ui) {
+        let order = self.article_order();
+        let articles = match self.feeds.get(&self.config.query) {
+            Some(feed) => &feed.articles,
+            None => return,
+        };
+        let mut clicked = None;
+        let mut opened_in_browser = None;
+        let theme = self.themes.get(&self.config.theme).clone();
+        let scale = self.config.font_size / DEFAULT_FONT_SIZE;
+        let colors = &theme.colors;
+
+        for a in order.iter().map(|&i| &articles[i]) {
+            let selected = self.selected.as_deref() == Some(a.url.as_str());
+            let saved = self.config.saved.iter().any(|s| s.url == a.url);
+            let read = self.config.read.contains(&a.url);
+
+            let mut frame = eframe::egui::Frame::none().inner_margin(theme.spacing).fill(colors.card);
+            if selected {
+                frame = frame.stroke(ui.visuals().selection.stroke);
             }
+            let mut card_clicked = None;
+            let card = frame.show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    if let Some(url) = &a.image_url {
+                        render_thumbnail(ui, self.images.thumbnail(url));
+                    }
 
-            ui.add_space(PADDING);
-            ui.allocate_ui_with_layout( Vec2::new(ui.available_width(), 0.0), Layout::right_to_left(), |ui| {
-                ui.hyperlink_to("read more...", &a.url);
+                    ui.vertical(|ui| {
+                        let title = RichText::new(format!("{} {}", if saved { "★" } else { ">" }, a.title))
+                            .size(theme.title_size * scale);
+                        if read {
+                            ui.label(title.weak());
+                        } else {
+                            ui.label(title.color(colors.title));
+                        }
+
+                        ui.add_space(theme.spacing);
+                        let summary = match self.config.summary_sentences {
+                            0 => None,
+                            _ => self.summaries.summary(&a.url, &a.description).filter(|s| !s.is_empty()),
+                        };
+                        let text = match summary {
+                            Some(summary) => summary.join(" "),
+                            None if a.description.is_empty() => "...".to_string(),
+                            None => a.description.to_string(),
+                        };
+                        ui.label(RichText::new(text).size(theme.description_size * scale).color(colors.description));
+                    });
+                });
+
+                ui.add_space(theme.spacing);
+                ui.allocate_ui_with_layout( Vec2::new(ui.available_width(), 0.0), Layout::right_to_left(), |ui| {
+                    if ui.hyperlink_to("open in browser", &a.url).clicked() {
+                        opened_in_browser = Some(a.url.to_string());
+                    }
+                    if ui.link("read more...").clicked() {
+                        card_clicked = Some(Action::Open);
+                    }
+                    if ui.link(if read { "mark unread" } else { "mark read" }).clicked() {
+                        card_clicked = Some(Action::MarkRead);
+                    }
+                    if ui.link(if saved { "unsave" } else { "save" }).clicked() {
+                        card_clicked = Some(Action::Save);
+                    }
+                    ui.with_layout(Layout::left_to_right(), |ui| {
+                        let tone = Tone::of(a.sentiment);
+                        let color = match tone {
+                            Tone::Positive => colors.positive,
+                            Tone::Negative => colors.negative,
+                            Tone::Neutral => colors.description,
+                        };
+                        ui.label(RichText::new("●").small().color(color))
+                            .on_hover_text(format!("{} {:+.2}", tone.name(), a.sentiment));
+                        if let Some(code) = a.language {
+                            ui.label(RichText::new(code.to_uppercase()).small().strong().color(colors.accent))
+                                .on_hover_text(language::name(code));
+                        }
+                    });
+                });
             });
-            ui.add_space(PADDING);
+
+            if card_clicked.is_some() {
+                self.selected = Some(a.url.to_string());
+                clicked = card_clicked;
+            }
+            if selected && self.scroll_to_selected {
+                card.response.scroll_to_me(Some(eframe::egui::Align::Center));
+                self.scroll_to_selected = false;
+            }
             ui.separator();
         }
+
+        if let Some(url) = opened_in_browser {
+            self.record_click(&url);
+        }
+        if let Some(action) = clicked {
+            self.apply_to_selected(action);
+        }
+    }
+
+    /// Indices of the selected feed's articles that match the search box, in
+    /// the picked order. Only worked out again when something it depends on
+    /// changed.
+    fn article_order(&self) -> Vec<usize> {
+        let feed = match self.feeds.get(&self.config.query) {
+            Some(feed) => feed,
+            None => return vec![],
+        };
+        let now = chrono::Utc::now().timestamp();
+        let key = OrderKey {
+            query: self.config.query.clone(),
+            last_updated: feed.last_updated,
+            articles: feed.articles.len(),
+            search: self.search.clone(),
+            sort: self.config.sort,
+            ranking: self.config.ranking.clone(),
+            clicks: (self.config.clicks.len(), self.config.clicks.last().map(|c| c.at)),
+            minute: now / 60
+        };
+        if let Some((cached, order)) = self.order.borrow().as_ref() {
+            if *cached == key {
+                return order.clone();
+            }
+        }
+        let order = self.rank(&feed.articles, now);
+        *self.order.borrow_mut() = Some((key, order.clone()));
+        order
+    }
+
+    fn rank(&self, articles: &[NewsCardData], now: i64) -> Vec<usize> {
+        let needle = self.search.to_lowercase();
+        let visible: Vec<usize> = (0..articles.len()).filter(|&i| matches_search(&articles[i], &needle)).collect();
+        if self.config.sort == SortMode::Api {
+            return visible;
+        }
+
+        let candidates: Vec<Candidate> = visible.iter().map(|&i| Candidate::from(&articles[i])).collect();
+        let ranker = Ranker::new(&self.config.ranking, &self.config.clicks, now);
+        ranker.order(&candidates, self.config.sort).into_iter().map(|i| visible[i]).collect()
+    }
+
+    /// Remembers that the article at `url` was opened, for the "for you"
+    /// order.
+    fn record_click(&mut self, url: &str) {
+        let article = match self.feeds.get(&self.config.query).and_then(|f| f.articles.iter().find(|a| a.url == url)) {
+            Some(article) => article,
+            None => return,
+        };
+        let now = chrono::Utc::now().timestamp();
+        self.config.clicks.push(Click {
+            url: article.url.to_string(),
+            title: article.title.to_string(),
+            source: article.source.to_string(),
+            at: now
+        });
+        let excess = self.config.clicks.len().saturating_sub(CLICKS_KEPT);
+        self.config.clicks.drain(..excess);
+    }
+
+    /// Articles of the selected feed that match the search box.
+    fn visible_articles(&self) -> Vec<&NewsCardData> {
+        match self.feeds.get(&self.config.query) {
+            Some(feed) => self.article_order().into_iter().map(|i| &feed.articles[i]).collect(),
+            None => vec![],
+        }
+    }
+
+    fn selected_article(&self) -> Option<&NewsCardData> {
+        let url = self.selected.as_ref()?;
+        self.feeds.get(&self.config.query)?.articles.iter().find(|a| &a.url == url)
+    }
+
+    fn move_selection(&mut self, step: isize) {
+        let urls: Vec<String> = self.visible_articles().iter().map(|a| a.url.to_string()).collect();
+        if urls.is_empty() {
+            return;
+        }
+        let next = match self.selected.as_ref().and_then(|s| urls.iter().position(|u| u == s)) {
+            Some(i) => (i as isize + step).clamp(0, urls.len() as isize - 1) as usize,
+            None => 0,
+        };
+        self.selected = Some(urls[next].to_string());
+        self.scroll_to_selected = true;
+    }
+
+    /// Opens, saves or marks as read the selected article.
+    fn apply_to_selected(&mut self, action: Action) {
+        let (url, title) = match self.selected_article() {
+            Some(a) => (a.url.to_string(), a.title.to_string()),
+            None => return,
+        };
+        match action {
+            Action::Open => {
+                self.record_click(&url);
+                self.config.read.insert(url.to_string());
+                self.reader = Some(Reader::open(&url, &title));
+            },
+            Action::Save => {
+                let before = self.config.saved.len();
+                self.config.saved.retain(|s| s.url != url);
+                if self.config.saved.len() == before {
+                    self.config.saved.push(SavedArticle { title, url });
+                }
+            },
+            Action::MarkRead => {
+                if self.config.read.contains(&url) {
+                    self.config.read.remove(&url);
+                } else {
+                    self.config.read.insert(url);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Applies this frame's keyboard shortcuts.
+    pub fn handle_keys(&mut self, ctx: &eframe::egui::Context) {
+        for action in self.config.keys.actions(ctx) {
+            match action {
+                Action::Back => {
+                    if self.show_help {
+                        self.show_help = false;
+                    } else if ctx.wants_keyboard_input() {
+                        self.search_focus = Some(false);
+                    } else if self.reader.is_some() {
+                        self.reader = None;
+                    } else {
+                        self.search.clear();
+                    }
+                },
+                Action::Help => self.show_help = !self.show_help,
+                Action::ToggleTheme => self.config.theme = self.themes.next(&self.config.theme).name.to_string(),
+                Action::Refresh => self.refresh(ctx.input().time),
+                Action::Search => {
+                    self.reader = None;
+                    self.search_focus = Some(true);
+                },
+                Action::Down | Action::Up | Action::Open if self.reader.is_some() => {},
+                Action::Down => self.move_selection(1),
+                Action::Up => self.move_selection(-1),
+                Action::Open | Action::Save | Action::MarkRead => self.apply_to_selected(action),
+            }
+        }
+    }
+
+    /// Saved articles, opened in the reader when clicked.
+    fn render_saved(&mut self, ui: &mut eframe::egui::Ui) {
+        let mut open = None;
+        let mut remove = None;
+        for (i, saved) in self.config.saved.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("x").on_hover_text("unsave").clicked() {
+                    remove = Some(i);
+                }
+                if ui.link(&saved.title).on_hover_text(&saved.url).clicked() {
+                    open = Some(Reader::open(&saved.url, &saved.title));
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.config.saved.remove(i);
+        }
+        if open.is_some() {
+            self.reader = open;
+        }
+    }
+
+    /// Country, category and source picker.
+    pub fn render_sidebar(&mut self, ctx: &eframe::egui::Context) {
+        SidePanel::left("feeds").resizable(true).default_width(160.).show(ctx, |ui| {
+            let query = &mut self.config.query;
+
+            ui.add_space(PADDING);
+            ui.label(RichText::new("Country").text_style(TextStyle::Heading));
+            ComboBox::from_id_source("sidebar_country")
+                .selected_text(query.country.name())
+                .show_ui(ui, |ui| {
+                    for country in Country::ALL {
+                        if ui.selectable_value(&mut query.country, country, country.name()).clicked() {
+                            query.source = None;
+                        }
+                    }
+                });
+
+            ui.add_space(PADDING);
+            ui.label(RichText::new("Category").text_style(TextStyle::Heading));
+            if ui.selectable_label(query.category.is_none() && query.source.is_none(), "All").clicked() {
+                query.category = None;
+                query.source = None;
+            }
+            for category in Category::ALL {
+                let selected = query.category == Some(category) && query.source.is_none();
+                if ui.selectable_label(selected, category.name()).clicked() {
+                    query.category = Some(category);
+                    query.source = None;
+                }
+            }
+
+            if !self.config.saved.is_empty() {
+                ui.add_space(PADDING);
+                eframe::egui::CollapsingHeader::new(RichText::new("Saved").text_style(TextStyle::Heading))
+                    .default_open(true)
+                    .show(ui, |ui| self.render_saved(ui));
+            }
+
+            ui.add_space(PADDING);
+            let trending = eframe::egui::CollapsingHeader::new(RichText::new("Trending").text_style(TextStyle::Heading))
+                .default_open(true)
+                .show(ui, |ui| self.trending.render(ui));
+            if let Some(term) = trending.body_returned.flatten() {
+                self.search = term;
+                self.reader = None;
+            }
+
+            let query = &mut self.config.query;
+            ui.add_space(PADDING);
+            ui.label(RichText::new("Sources").text_style(TextStyle::Heading));
+            match self.sources.get(&query.sources_key()) {
+                Some(Some(sources)) => {
+                    ScrollArea::vertical().id_source("sources").show(ui, |ui| {
+                        for source in sources {
+                            let selected = query.source.as_deref() == Some(source.id.as_str());
+                            if ui.selectable_label(selected, &source.name).on_hover_text(&source.description).clicked() {
+                                query.source = Some(source.id.to_string());
+                            }
+                        }
+                    });
+                },
+                Some(None) => {
+                    ui.spinner();
+                },
+                None => {}
+            }
+        });
     }
 
     pub fn render_top_panel(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
@@ -187,60 +971,115 @@ impl Headlines {
             eframe::egui::menu::bar(ui, |ui| {
                 ui.with_layout(Layout::left_to_right(), |ui| {
                     ui.label(RichText::new("((.))").text_style(TextStyle::Heading));
+                    let search = ui.add(TextEdit::singleline(&mut self.search).hint_text("search").desired_width(160.));
+                    match self.search_focus.take() {
+                        Some(true) => search.request_focus(),
+                        Some(false) => search.surrender_focus(),
+                        None => {},
+                    }
+                    ComboBox::from_id_source("sort")
+                        .selected_text(self.config.sort.name())
+                        .show_ui(ui, |ui| {
+                            for sort in SortMode::ALL {
+                                ui.selectable_value(&mut self.config.sort, sort, sort.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text("order of the articles");
                 });
                 ui.with_layout(Layout::right_to_left(), |ui| {
-                    let close_btn = ui.add(Button::new(RichText::new("X").text_style(TextStyle::Body)));
+                    let keys = &self.config.keys;
+                    let close_btn = ui.add(Button::new(RichText::new("quit").text_style(TextStyle::Body)));
                     if close_btn.clicked() {
                         frame.quit()
                     }
-                    let refresh_btn = ui.add(Button::new(RichText::new("r").text_style(TextStyle::Body)));
-
+                    let help_btn = ui.add(Button::new(RichText::new("?").text_style(TextStyle::Body)))
+                        .on_hover_text(format!("keyboard shortcuts ({})", keys.help.join(", ")));
+                    if help_btn.clicked() {
+                        self.show_help = !self.show_help;
+                    }
+                    let now = ui.input().time;
+                    let refresh_btn = ui.add(Button::new(RichText::new("refresh").text_style(TextStyle::Body)))
+                        .on_hover_text(format!("refresh now ({})", keys.refresh.join(", ")));
                     if refresh_btn.clicked() {
-                        tracing::info!("Refreshing article list.");
-                        self.articles = vec![];
-                        if let Some(tx) = &self.news_tx {
-                            let tx_ = tx.clone();
-                            let api_key = self.config.api_key.clone();
-
-                            #[cfg(not(target_arch="wasm32"))]
-                            std::thread::spawn(move || {
-                                // Putting a sleep here to test that the UI gets repainted even as
-                                // there is a network delay in fetching the data.
-                                std::thread::sleep(std::time::Duration::from_millis(2000));
-                                fetch_news(&api_key, &tx_);
-                            });
-
-                            #[cfg(target_arch="wasm32")]
-                            gloo_timers::callback::Timeout::new(2000, move || {
-                                wasm_bindgen_futures::spawn_local(async {
-                                    fetch_web(api_key, tx_).await;
-                                })
-                            }).forget();
-                        }
+                        self.refresh(now);
+                    }
+
+                    let pause_text = if self.refresh_paused { "resume" } else { "pause" };
+                    let pause_btn = ui.add(Button::new(RichText::new(pause_text).text_style(TextStyle::Body)));
+                    if pause_btn.clicked() {
+                        self.refresh_paused = !self.refresh_paused;
                     }
 
-                    let theme_btn = ui.add(Button::new(RichText::new("@").text_style(TextStyle::Body)));
-                    if theme_btn.clicked() {
-                        tracing::info!("Changing theme.");
-                        self.config.dark_mode = !self.config.dark_mode;
+                    let settings_btn = ui.add(Button::new(RichText::new("settings").text_style(TextStyle::Body)));
+                    if settings_btn.clicked() {
+                        self.show_settings = !self.show_settings;
                     }
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        let export_btn = ui.add(Button::new(RichText::new("export").text_style(TextStyle::Body)))
+                            .on_hover_text("save the articles as a Markdown, HTML or EPUB digest");
+                        if export_btn.clicked() {
+                            self.export.open = !self.export.open;
+                        }
+                    }
+
+                    self.render_theme_picker(ui, "top_theme")
+                        .on_hover_text(format!("switch with {}", self.config.keys.toggle_theme.join(", ")));
+
+                    ui.label(RichText::new(self.updated_label(now)).text_style(TextStyle::Small));
                 });
             });
             ui.add_space(10.);
         });
     }
 
+    /// The export window, fed with the articles on screen.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_export(&mut self, ctx: &eframe::egui::Context) {
+        if !self.export.render(ctx) {
+            return;
+        }
+        let category = self.config.query.category.map(|c| c.name().to_string());
+        let items = self.visible_articles()
+            .into_iter()
+            .map(|a| Item {
+                title: a.title.to_string(),
+                url: a.url.to_string(),
+                source: a.source.to_string(),
+                category: category.clone(),
+                author: a.author.clone(),
+                published_at: a.published_at.clone(),
+                description: Some(a.description.to_string()).filter(|d| !d.is_empty()),
+                sentiment: Some(a.sentiment),
+                ..Item::default()
+            })
+            .collect();
+        self.export.start(items);
+    }
+
+    fn render_theme_picker(&mut self, ui: &mut eframe::egui::Ui, id: &str) -> eframe::egui::Response {
+        let selected = &mut self.config.theme;
+        ComboBox::from_id_source(id)
+            .selected_text(selected.as_str())
+            .show_ui(ui, |ui| {
+                for theme in &self.themes.themes {
+                    ui.selectable_value(selected, theme.name.to_string(), &theme.name);
+                }
+            })
+            .response
+    }
+
     pub fn render_config(&mut self, ctx: &eframe::egui::Context) {
         Window::new("Configuration").show(ctx, |ui| {
             ui.label("Enter your API_KEY for newsapi.org");
             let text_input = ui.text_edit_singleline(&mut self.config.api_key);
             if text_input.lost_focus() && ui.input().key_pressed(eframe::egui::Key::Enter) {
                 self.api_key_initialized = true;
-
-                if let Some(tx) = &self.app_tx {
-                    let _ = tx.send(Msg::ApiKeySet(self.config.api_key.to_string()));
-                };
-
+                // The feed on screen may not be the one fetched at startup,
+                // and a rejected key left it failed.
+                self.reload(ui.input().time);
                 tracing::info!("API key set");
             }
             ui.label("If you don't have an API key, create one at");
@@ -248,11 +1087,82 @@ impl Headlines {
         });
     }
 
-    pub fn preload_articles(&mut self) {
+    pub fn render_settings(&mut self, ctx: &eframe::egui::Context) {
+        let mut open = self.show_settings;
+        let mut api_key_changed = false;
+
+        Window::new("Settings").open(&mut open).show(ctx, |ui| {
+            eframe::egui::Grid::new("settings_grid").num_columns(2).spacing([20., 8.]).show(ui, |ui| {
+                ui.label("API key");
+                let text_input = ui.text_edit_singleline(&mut self.config.api_key);
+                api_key_changed = text_input.lost_focus() && self.config.api_key != self.fetched_api_key;
+                ui.end_row();
+
+                let query = &mut self.config.query;
+                ui.label("Country");
+                ComboBox::from_id_source("country")
+                    .selected_text(query.country.name())
+                    .show_ui(ui, |ui| {
+                        for country in Country::ALL {
+                            ui.selectable_value(&mut query.country, country, country.name());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Category");
+                ComboBox::from_id_source("category")
+                    .selected_text(query.category.map(|c| c.name()).unwrap_or("All"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut query.category, None, "All");
+                        for category in Category::ALL {
+                            ui.selectable_value(&mut query.category, Some(category), category.name());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Articles per fetch");
+                ui.add(Slider::new(&mut query.page_size, 5..=100));
+                ui.end_row();
+
+                ui.label("Refresh every");
+                ui.add(DragValue::new(&mut self.config.refresh_interval_mins).clamp_range(0..=24 * 60).suffix(" min"));
+                ui.end_row();
+
+                ui.label("Summary sentences");
+                ui.add(Slider::new(&mut self.config.summary_sentences, 0..=5));
+                ui.end_row();
+
+                ui.label("Font size");
+                ui.add(Slider::new(&mut self.config.font_size, 10.0..=24.0));
+                ui.end_row();
+
+                ui.label("Theme");
+                self.render_theme_picker(ui, "settings_theme");
+                ui.end_row();
+            });
+            ui.small("0 minutes turns background refresh off. 0 summary sentences shows the description instead.");
+            ui.small("Themes dropped into the headlines/themes config directory show up here.");
+        });
+        self.show_settings = open;
+
+        if api_key_changed {
+            self.reload(ctx.input().time);
+        }
+    }
+
+    pub fn preload_articles(&mut self, now: f64) {
         if let Some(rx) = &self.news_rx {
             match rx.try_recv() {
-                Ok(news) => {
-                    self.articles.push(news);
+                Ok((query, NewsMsg::Sources(sources))) => {
+                    self.sources.insert(query, Some(sources));
+                },
+                Ok((query, msg)) => {
+                    if let NewsMsg::Article(news) = &msg {
+                        self.trending.add(&news.url, &news.title, &news.description);
+                    }
+                    if let Some(feed) = self.feeds.get_mut(&query) {
+                        feed.receive(msg, now);
+                    }
                 },
                 // Err(_) => {}
                 Err(e) => {
@@ -263,3 +1173,35 @@ impl Headlines {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(source_id: Option<&str>, source: &str) -> NewsCardData {
+        NewsCardData {
+            title: "Bridge reopens".to_string(),
+            url: "https://news.example/bridge".to_string(),
+            source_id: source_id.map(|id| id.to_string()),
+            source: source.to_string(),
+            author: None,
+            description: String::new(),
+            image_url: None,
+            published_at: Some("2022-06-10T12:00:00Z".to_string()),
+            language: None,
+            sentiment: 0.0
+        }
+    }
+
+    #[test]
+    fn cards_are_ranked_by_source_id() {
+        let preferences = Preferences {
+            sources: HashMap::from([("bbc-news".to_string(), 1.0), ("BBC News".to_string(), -1.0)]),
+            ..Preferences::default()
+        };
+        let ranker = Ranker::new(&preferences, &[], 1654862400);
+        let cards = [card(Some("bbc-news"), "BBC News"), card(None, "BBC News")];
+        let candidates: Vec<Candidate> = cards.iter().map(Candidate::from).collect();
+        let scores = ranker.scores(&candidates);
+        assert_eq!((scores[0].source, scores[1].source), (1.0, -1.0));
+    }
+}
@@ -0,0 +1,340 @@
+//! Image pipeline for the news cards and the reader. Images are downloaded
+//! and decoded off the UI thread, downscaled, and handed back to be uploaded
+//! as egui textures. Thumbnails are kept in memory and, natively, on disk.
+
+use eframe::egui::{ColorImage, Context, TextureHandle};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Thumbnails are decoded to at most this size.
+pub const THUMBNAIL_SIZE: [u32; 2] = [160, 120];
+/// Images in the reader are decoded to at most this size.
+const FULL_SIZE: [u32; 2] = [1200, 1200];
+/// Textures kept in memory before the least recently drawn ones get dropped.
+const MAX_TEXTURES: usize = 200;
+/// Downloads running at the same time.
+const MAX_IN_FLIGHT: usize = 4;
+/// Images larger than this are not worth downloading.
+const MAX_DOWNLOAD_BYTES: u64 = 10 * 1024 * 1024;
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_DISK_BYTES: u64 = 50 * 1024 * 1024;
+
+/// What an image is decoded for. The same url can be cached at both sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Size {
+    Thumbnail,
+    Full
+}
+
+impl Size {
+    fn bounds(&self) -> [u32; 2] {
+        match self {
+            Self::Thumbnail => THUMBNAIL_SIZE,
+            Self::Full => FULL_SIZE,
+        }
+    }
+}
+
+type Key = (String, Size);
+
+pub enum ImageState<T = TextureHandle> {
+    Loading,
+    Ready(T),
+    Failed
+}
+
+/// Which images are loaded, waiting or failed, with the loaded ones dropped
+/// least recently used first once there are more than `capacity`.
+struct Cache<T> {
+    images: HashMap<Key, ImageState<T>>,
+    /// Keys of the ready images, least recently used first.
+    order: VecDeque<Key>,
+    /// Keys asked for and not loading yet.
+    queue: VecDeque<Key>,
+    capacity: usize
+}
+
+impl<T> Cache<T> {
+    fn new(capacity: usize) -> Cache<T> {
+        Cache { images: HashMap::new(), order: VecDeque::new(), queue: VecDeque::new(), capacity }
+    }
+
+    /// State of the image for `key`, queueing it the first time it is asked
+    /// for, and after it was dropped.
+    fn get(&mut self, key: Key) -> &ImageState<T> {
+        match self.images.get(&key) {
+            Some(ImageState::Ready(_)) => {
+                if let Some(i) = self.order.iter().position(|k| *k == key) {
+                    let key = self.order.remove(i).unwrap();
+                    self.order.push_back(key);
+                }
+            },
+            Some(_) => {},
+            None => {
+                self.images.insert(key.clone(), ImageState::Loading);
+                self.queue.push_back(key.clone());
+            },
+        }
+        &self.images[&key]
+    }
+
+    /// Records the outcome of a load.
+    fn insert(&mut self, key: Key, image: Option<T>) {
+        let state = match image {
+            Some(image) => {
+                self.order.push_back(key.clone());
+                ImageState::Ready(image)
+            },
+            None => ImageState::Failed,
+        };
+        self.images.insert(key, state);
+
+        while self.order.len() > self.capacity {
+            if let Some(key) = self.order.pop_front() {
+                self.images.remove(&key);
+            }
+        }
+    }
+
+    fn next_queued(&mut self) -> Option<Key> {
+        self.queue.pop_front()
+    }
+}
+
+pub struct ImageCache {
+    cache: Cache<TextureHandle>,
+    in_flight: usize,
+    tx: Sender<(Key, Option<ColorImage>)>,
+    rx: Receiver<(Key, Option<ColorImage>)>
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        ImageCache {
+            cache: Cache::new(MAX_TEXTURES),
+            in_flight: 0,
+            tx,
+            rx
+        }
+    }
+}
+
+impl ImageCache {
+    /// State of the thumbnail for `url`, queueing it for loading the first
+    /// time it is asked for.
+    pub fn thumbnail(&mut self, url: &str) -> &ImageState {
+        self.cache.get((url.to_string(), Size::Thumbnail))
+    }
+
+    /// State of the image at `url` at reading size.
+    pub fn full(&mut self, url: &str) -> &ImageState {
+        self.cache.get((url.to_string(), Size::Full))
+    }
+
+    /// Uploads the images decoded since the last frame and starts the next
+    /// downloads. Call once per frame.
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok(((url, size), image)) = self.rx.try_recv() {
+            self.in_flight -= 1;
+            let texture = image.map(|image| ctx.load_texture(url.as_str(), image));
+            self.cache.insert((url, size), texture);
+        }
+
+        while self.in_flight < MAX_IN_FLIGHT {
+            let key = match self.cache.next_queued() {
+                Some(key) => key,
+                None => break,
+            };
+            self.in_flight += 1;
+            spawn_load(key, self.tx.clone());
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_load((url, size): Key, tx: Sender<(Key, Option<ColorImage>)>) {
+    std::thread::spawn(move || {
+        let image = match size {
+            Size::Thumbnail => disk::load(&url),
+            Size::Full => disk::download(&url).and_then(|bytes| decode(&bytes, size)).map(|image| to_color_image(&image)),
+        };
+        if image.is_none() {
+            tracing::warn!("Could not load image {}", url);
+        }
+        let _ = tx.send(((url, size), image));
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_load((url, size): Key, tx: Sender<(Key, Option<ColorImage>)>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let image = match download_web(&url).await {
+            Some(bytes) => decode(&bytes, size).map(|image| to_color_image(&image)),
+            None => None,
+        };
+        let _ = tx.send(((url, size), image));
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn download_web(url: &str) -> Option<Vec<u8>> {
+    let resp = reqwasm::http::Request::get(url).send().await.ok()?;
+    if !resp.ok() {
+        return None;
+    }
+    let bytes = resp.binary().await.ok()?;
+    if bytes.len() as u64 > MAX_DOWNLOAD_BYTES {
+        return None;
+    }
+    Some(bytes)
+}
+
+/// Decodes an image, downscaled to fit `size` if it's bigger.
+fn decode(bytes: &[u8], size: Size) -> Option<image::RgbaImage> {
+    let image = image::load_from_memory(bytes).ok()?;
+    let [w, h] = size.bounds();
+    if image.width() <= w && image.height() <= h {
+        return Some(image.to_rgba8());
+    }
+    Some(image.thumbnail(w, h).to_rgba8())
+}
+
+fn to_color_image(image: &image::RgbaImage) -> ColorImage {
+    let size = [image.width() as usize, image.height() as usize];
+    ColorImage::from_rgba_unmultiplied(size, image.as_raw())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod disk {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    fn cache_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|d| d.join("headlines").join("thumbnails"))
+    }
+
+    fn cache_path(dir: &Path, url: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        dir.join(format!("{:016x}.png", hasher.finish()))
+    }
+
+    /// Reads the thumbnail from the disk cache, downloading and caching it
+    /// if it isn't there yet.
+    pub fn load(url: &str) -> Option<ColorImage> {
+        let path = cache_dir().map(|dir| cache_path(&dir, url));
+
+        if let Some(thumb) = path.as_ref().and_then(|p| std::fs::read(p).ok()).and_then(|b| decode(&b, Size::Thumbnail)) {
+            return Some(to_color_image(&thumb));
+        }
+
+        let thumb = decode(&download(url)?, Size::Thumbnail)?;
+        if let Some(path) = path {
+            store(&path, &thumb);
+        }
+        Some(to_color_image(&thumb))
+    }
+
+    pub fn download(url: &str) -> Option<Vec<u8>> {
+        let resp = ureq::get(url).call().ok()?;
+        let mut bytes = Vec::new();
+        resp.into_reader().take(MAX_DOWNLOAD_BYTES + 1).read_to_end(&mut bytes).ok()?;
+        if bytes.len() as u64 > MAX_DOWNLOAD_BYTES {
+            return None;
+        }
+        Some(bytes)
+    }
+
+    fn store(path: &Path, thumb: &image::RgbaImage) {
+        let dir = match path.parent() {
+            Some(dir) => dir,
+            None => return,
+        };
+        if std::fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        if let Err(e) = thumb.save_with_format(path, image::ImageFormat::Png) {
+            tracing::warn!("Could not cache thumbnail: {}", e);
+            return;
+        }
+        prune(dir);
+    }
+
+    /// Deletes the least recently written thumbnails until the cache fits
+    /// in `MAX_DISK_BYTES`.
+    fn prune(dir: &Path) {
+        let mut files: Vec<_> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let meta = e.metadata().ok()?;
+                    Some((meta.modified().ok()?, meta.len(), e.path()))
+                })
+                .collect(),
+            Err(_) => return,
+        };
+
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort();
+        for (_, len, path) in files {
+            if total <= MAX_DISK_BYTES {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: usize) -> Key {
+        (format!("https://img.example/{}.png", n), Size::Thumbnail)
+    }
+
+    #[test]
+    fn queues_each_image_once() {
+        let mut cache: Cache<u32> = Cache::new(10);
+        assert!(matches!(cache.get(key(1)), ImageState::Loading));
+        assert!(matches!(cache.get(key(1)), ImageState::Loading));
+        // The reader's copy is a separate image.
+        cache.get((key(1).0, Size::Full));
+        assert_eq!(cache.next_queued(), Some(key(1)));
+        assert_eq!(cache.next_queued(), Some((key(1).0, Size::Full)));
+        assert_eq!(cache.next_queued(), None);
+
+        cache.insert(key(1), Some(7));
+        assert!(matches!(cache.get(key(1)), ImageState::Ready(7)));
+        cache.insert(key(2), None);
+        assert!(matches!(cache.get(key(2)), ImageState::Failed));
+        assert_eq!(cache.next_queued(), None);
+    }
+
+    #[test]
+    fn drops_the_least_recently_used() {
+        let mut cache: Cache<usize> = Cache::new(2);
+        for n in 1..=2 {
+            cache.get(key(n));
+            cache.insert(key(n), Some(n));
+        }
+        // Drawing the first one keeps it, the second one goes instead.
+        cache.get(key(1));
+        cache.get(key(3));
+        cache.insert(key(3), Some(3));
+        while cache.next_queued().is_some() {}
+
+        assert!(matches!(cache.get(key(1)), ImageState::Ready(1)));
+        assert!(matches!(cache.get(key(3)), ImageState::Ready(3)));
+        assert_eq!(cache.next_queued(), None);
+        // Asked for again, the dropped one is loaded again.
+        assert!(matches!(cache.get(key(2)), ImageState::Loading));
+        assert_eq!(cache.next_queued(), Some(key(2)));
+    }
+}
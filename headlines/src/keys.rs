@@ -0,0 +1,164 @@
+//! Keyboard shortcuts. Bindings are stored as the typed character ("j",
+//! "/", "?") or, for keys that don't type anything, the egui key name
+//! ("Enter", "ArrowDown", "Escape").
+
+use serde::{Deserialize, Serialize};
+use eframe::egui::{Context, Event, Grid, Key, RichText, Window};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Down,
+    Up,
+    Open,
+    Save,
+    MarkRead,
+    Search,
+    Refresh,
+    ToggleTheme,
+    Help,
+    Back
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub down: Vec<String>,
+    pub up: Vec<String>,
+    pub open: Vec<String>,
+    pub save: Vec<String>,
+    pub mark_read: Vec<String>,
+    pub search: Vec<String>,
+    pub refresh: Vec<String>,
+    pub toggle_theme: Vec<String>,
+    pub help: Vec<String>,
+    pub back: Vec<String>
+}
+
+fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|k| k.to_string()).collect()
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            down: keys(&["j", "ArrowDown"]),
+            up: keys(&["k", "ArrowUp"]),
+            open: keys(&["o", "Enter"]),
+            save: keys(&["s"]),
+            mark_read: keys(&["m"]),
+            search: keys(&["/"]),
+            refresh: keys(&["r"]),
+            toggle_theme: keys(&["t"]),
+            help: keys(&["?"]),
+            back: keys(&["Escape"])
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Every action with its description and keys, in the order the help
+    /// overlay lists them.
+    pub fn list(&self) -> [(Action, &'static str, &[String]); 10] {
+        [
+            (Action::Down, "next article", &self.down),
+            (Action::Up, "previous article", &self.up),
+            (Action::Open, "open in the reader", &self.open),
+            (Action::Save, "save for later", &self.save),
+            (Action::MarkRead, "mark as read / unread", &self.mark_read),
+            (Action::Search, "search", &self.search),
+            (Action::Refresh, "refresh", &self.refresh),
+            (Action::ToggleTheme, "toggle the theme", &self.toggle_theme),
+            (Action::Help, "show this help", &self.help),
+            (Action::Back, "close the reader, help or search", &self.back),
+        ]
+    }
+
+    /// Actions triggered by this frame's key presses. While a text field has
+    /// focus only `back` is looked at, so typing doesn't trigger shortcuts.
+    pub fn actions(&self, ctx: &Context) -> Vec<Action> {
+        let typing = ctx.wants_keyboard_input();
+        let pressed: Vec<String> = ctx
+            .input()
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) if !typing => Some(text.to_string()),
+                Event::Key { key, pressed: true, modifiers } if modifiers.is_none() => named_key(*key),
+                _ => None,
+            })
+            .collect();
+
+        let mut actions = Vec::new();
+        for key in &pressed {
+            for (action, _, bound) in self.list() {
+                if bound.contains(key) && (!typing || action == Action::Back) {
+                    actions.push(action);
+                }
+            }
+        }
+        actions
+    }
+}
+
+/// Name of a key that doesn't produce text. Letters, digits and space come
+/// in as text events instead.
+fn named_key(key: Key) -> Option<String> {
+    match key {
+        Key::ArrowDown | Key::ArrowLeft | Key::ArrowRight | Key::ArrowUp
+        | Key::Escape | Key::Tab | Key::Backspace | Key::Enter
+        | Key::Insert | Key::Delete | Key::Home | Key::End
+        | Key::PageUp | Key::PageDown => Some(format!("{:?}", key)),
+        _ => None,
+    }
+}
+
+/// The `?` overlay listing the bindings.
+pub fn render_help(ctx: &Context, bindings: &KeyBindings, open: &mut bool) {
+    Window::new("Keyboard shortcuts")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            Grid::new("shortcuts_grid").num_columns(2).spacing([20., 4.]).show(ui, |ui| {
+                for (_, description, keys) in bindings.list() {
+                    ui.label(RichText::new(keys.join("  ")).monospace());
+                    ui.label(description);
+                    ui.end_row();
+                }
+            });
+            ui.small("Bindings can be changed in the `keys` section of the app state.");
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_left_out_keep_their_defaults() {
+        let bindings: KeyBindings = toml::from_str(r#"down = ["n", "ArrowDown"]"#).unwrap();
+        assert_eq!(bindings.down, keys(&["n", "ArrowDown"]));
+        assert_eq!(bindings.up, KeyBindings::default().up);
+        assert_eq!(bindings.back, keys(&["Escape"]));
+    }
+
+    #[test]
+    fn unknown_actions_are_ignored() {
+        let bindings: KeyBindings = toml::from_str("jump = [\"g\"]\nhelp = [\"h\"]").unwrap();
+        assert_eq!(bindings.help, keys(&["h"]));
+        assert!(bindings.list().iter().all(|(_, _, bound)| !bound.contains(&"g".to_string())));
+    }
+
+    #[test]
+    fn invalid_bindings_are_rejected() {
+        assert!(toml::from_str::<KeyBindings>(r#"down = "j""#).is_err());
+        assert!(toml::from_str::<KeyBindings>("down = [1]").is_err());
+    }
+
+    #[test]
+    fn only_keys_without_text_are_named() {
+        assert_eq!(named_key(Key::ArrowDown), Some("ArrowDown".to_string()));
+        assert_eq!(named_key(Key::Escape), Some("Escape".to_string()));
+        assert_eq!(named_key(Key::J), None);
+    }
+}
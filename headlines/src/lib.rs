@@ -1,8 +1,17 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
 mod headlines;
+mod images;
+mod keys;
+mod reader;
+mod summaries;
+mod theme;
+mod trending;
 
-pub use headlines::{Headlines, Msg, NewsCardData, PADDING};
+pub use headlines::{Feed, FeedQuery, FetchStatus, Headlines, NewsCardData, NewsMsg, PADDING};
 use eframe::App;
-use newsapi::NewsAPI;
+use newsapi::Article;
+use newsapi::filter::{FilterEngine, FilterReport, FilterRule};
 use std::sync::mpsc::Sender;
 use eframe::egui::{
     RichText,
@@ -17,23 +26,39 @@ impl App for Headlines {
     fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
         ctx.request_repaint();
 
-        if self.config.dark_mode {
-            ctx.set_visuals(eframe::egui::Visuals::dark());
-        } else {
-            ctx.set_visuals(eframe::egui::Visuals::light());
-        }
+        self.themes.poll(ctx.input().time);
+        ctx.set_visuals(self.themes.get(&self.config.theme).visuals());
 
         if !self.api_key_initialized {
             self.render_config(ctx);
         } else {
-            self.preload_articles();
+            let now = ctx.input().time;
+            // Wait for sliders to be released before hitting the API.
+            if !ctx.is_using_pointer() {
+                self.ensure_feed(now);
+            }
+            self.auto_refresh(now);
+            self.preload_articles(now);
+            self.apply_font_size(ctx);
+            self.images.poll(ctx);
+            self.summaries.poll(self.config.summary_sentences);
+            self.handle_keys(ctx);
             self.render_top_panel(ctx, frame);
+            self.render_settings(ctx);
+            keys::render_help(ctx, &self.config.keys, &mut self.show_help);
+            #[cfg(not(target_arch = "wasm32"))]
+            self.render_export(ctx);
+            self.render_sidebar(ctx);
             eframe::egui::CentralPanel::default().show(ctx, |ui| {
                 render_header(ui);
-                eframe::egui::containers::ScrollArea::new([false, true])
-                    .auto_shrink([false, false])
-                    .always_show_scroll(false)
-                    .show(ui, |ui| self.render_news_cards(ui));
+                if let Some(reader) = &mut self.reader {
+                    if reader.render(ui, &mut self.images) {
+                        self.reader = None;
+                    }
+                } else {
+                    self.render_status(ui);
+                    self.render_feed(ui);
+                }
                 render_footer(ctx);
                 });
         }
@@ -41,49 +66,132 @@ impl App for Headlines {
 
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, "headlines", &self.config);
+        // The desktop app shares the archive of clinews instead.
+        #[cfg(target_arch = "wasm32")]
+        eframe::set_value(storage, "corpus", self.trending.corpus());
     }
 }
 
+/// Drops the `articles` `filters` hide, and makes cards of the others, on
+/// the desktop and on the web alike.
+fn cards(articles: &mut Vec<Article>, filters: &FilterEngine) -> (Vec<NewsCardData>, FilterReport) {
+    let report = filters.apply(articles);
+    let cards = articles
+        .iter()
+        .map(|a| NewsCardData {
+            title: a.title().to_string(),
+            url: a.url().to_string(),
+            source_id: a.source().id().cloned(),
+            source: a.source().name().to_string(),
+            author: a.author().cloned(),
+            description: a.description().cloned().unwrap_or_default(),
+            image_url: a.url_to_image().filter(|u| !u.is_empty()).map(|u| u.to_string()),
+            published_at: a.published_at().cloned(),
+            language: a.language(),
+            sentiment: a.sentiment()
+        })
+        .collect();
+    (cards, report)
+}
+
 #[cfg(target_arch = "wasm32")]
-pub async fn fetch_web(api_key: String, news_tx: Sender<NewsCardData>) {
-    let response = NewsAPI::new(&api_key).fetch_web().await;
-    if let Ok(response) = response {
-        tracing::info!("Fetched!");
-        let response_articles = response.articles();
-        for a in response_articles.iter() {
-            let news = NewsCardData {
-                title: a.title().to_string(),
-                url: a.url().to_string(),
-                description: a.description().map(|s| s.to_string()).unwrap_or("...".to_string())
-            };
-            if let Err(e) = news_tx.send(news) {
-                tracing::error!("Error sending data: {}", e);
+pub async fn fetch_web(api_key: String, query: FeedQuery, filters: Vec<FilterRule>, news_tx: Sender<(FeedQuery, NewsMsg)>) {
+    let send = |msg| news_tx.send((query.clone(), msg)).map_err(|e| e.to_string());
+    let _ = send(NewsMsg::Status(FetchStatus::Loading));
+    let filters = match FilterEngine::new(&filters) {
+        Ok(filters) => filters,
+        Err(e) => {
+            tracing::error!("Invalid filter rules: {:?}", e);
+            let _ = send(NewsMsg::Status(FetchStatus::Failed(e)));
+            return;
+        }
+    };
+    let response = query.newsapi(&api_key).fetch_web().await;
+    match response {
+        Ok(mut response) => {
+            tracing::info!("Fetched!");
+            let (cards, report) = cards(&mut response.articles, &filters);
+            log_filter_report(&report);
+            let loaded = cards.len();
+            for news in cards {
+                if let Err(e) = send(NewsMsg::Article(news)) {
+                    tracing::error!("Error sending data: {}", e);
+                }
             }
-            // articles.push(news);
+            let _ = send(NewsMsg::Status(FetchStatus::Loaded(loaded)));
+        },
+        Err(e) => {
+            tracing::error!("Could not fetch articles: {:?}", e);
+            let _ = send(NewsMsg::Status(FetchStatus::Failed(e)));
         }
-    } else {
-        tracing::error!("Could not fetch articles: {:?}", response);
     }
 }
 
-fn fetch_news(api_key: &str, news_tx: &Sender<NewsCardData>) {
-    let response = NewsAPI::new(&api_key).fetch();
-    if let Ok(response) = response {
-        tracing::info!("Fetched!");
-        let response_articles = response.articles();
-        for a in response_articles.iter() {
-            let news = NewsCardData {
-                title: a.title().to_string(),
-                url: a.url().to_string(),
-                description: a.description().map(|s| s.to_string()).unwrap_or("...".to_string())
-            };
-            if let Err(e) = news_tx.send(news) {
-                tracing::error!("Error sending data: {}", e);
+fn fetch_news(api_key: &str, query: &FeedQuery, filters: &[FilterRule], news_tx: &Sender<(FeedQuery, NewsMsg)>) {
+    let send = |msg| news_tx.send((query.clone(), msg)).map_err(|e| e.to_string());
+    let _ = send(NewsMsg::Status(FetchStatus::Loading));
+    let filters = match FilterEngine::new(filters) {
+        Ok(filters) => filters,
+        Err(e) => {
+            tracing::error!("Invalid filter rules: {:?}", e);
+            let _ = send(NewsMsg::Status(FetchStatus::Failed(e)));
+            return;
+        }
+    };
+    let response = query.newsapi(api_key).fetch();
+    match response {
+        Ok(mut response) => {
+            tracing::info!("Fetched!");
+            let (cards, report) = cards(&mut response.articles, &filters);
+            log_filter_report(&report);
+            #[cfg(not(target_arch = "wasm32"))]
+            trending::record(&response.articles);
+            let loaded = cards.len();
+            for news in cards {
+                if let Err(e) = send(NewsMsg::Article(news)) {
+                    tracing::error!("Error sending data: {}", e);
+                }
             }
-            // articles.push(news);
+            let _ = send(NewsMsg::Status(FetchStatus::Loaded(loaded)));
+        },
+        Err(e) => {
+            tracing::error!("Could not fetch articles: {:?}", e);
+            let _ = send(NewsMsg::Status(FetchStatus::Failed(e)));
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch_sources_web(api_key: String, query: FeedQuery, news_tx: Sender<(FeedQuery, NewsMsg)>) {
+    match query.newsapi(&api_key).fetch_sources_web().await {
+        Ok(response) => {
+            let _ = news_tx.send((query, NewsMsg::Sources(response.sources)));
+        },
+        Err(e) => {
+            tracing::error!("Could not fetch sources: {:?}", e);
+            let _ = news_tx.send((query, NewsMsg::Sources(vec![])));
+        }
+    }
+}
+
+fn fetch_sources(api_key: &str, query: &FeedQuery, news_tx: &Sender<(FeedQuery, NewsMsg)>) {
+    match query.newsapi(api_key).fetch_sources() {
+        Ok(response) => {
+            let _ = news_tx.send((query.clone(), NewsMsg::Sources(response.sources)));
+        },
+        Err(e) => {
+            tracing::error!("Could not fetch sources: {:?}", e);
+            let _ = news_tx.send((query.clone(), NewsMsg::Sources(vec![])));
         }
-    } else {
-        tracing::error!("Could not fetch articles: {:?}", response);
+    }
+}
+
+fn log_filter_report(report: &FilterReport) {
+    for (rule, hidden) in &report.hidden {
+        tracing::info!("{} articles hidden by {}", hidden, rule);
+    }
+    if report.unmatched > 0 {
+        tracing::info!("{} articles hidden by no include rule matching", report.unmatched);
     }
 }
 
@@ -0,0 +1,172 @@
+//! In-app reader view. The article page is downloaded and run through
+//! `newsapi::extract`, and the result is drawn as egui rich text.
+
+use crate::images::{ImageCache, ImageState};
+use crate::headlines::PADDING;
+use newsapi::NewsAPIError;
+use newsapi::extract::{Block, Document, Inline};
+use std::sync::mpsc::{channel, Receiver};
+use eframe::egui::{RichText, ScrollArea, TextStyle, Ui};
+
+pub enum ReaderStatus {
+    Loading,
+    Ready(Document),
+    Failed(NewsAPIError)
+}
+
+pub struct Reader {
+    pub url: String,
+    pub title: String,
+    pub status: ReaderStatus,
+    rx: Receiver<Result<Document, NewsAPIError>>
+}
+
+impl Reader {
+    /// Starts downloading and extracting the article at `url`.
+    pub fn open(url: &str, title: &str) -> Reader {
+        let (tx, rx) = channel();
+        let url_ = url.to_string();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || {
+            let _ = tx.send(newsapi::extract::fetch(&url_));
+        });
+
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = tx.send(newsapi::extract::fetch_web(&url_).await);
+        });
+
+        Reader {
+            url: url.to_string(),
+            title: title.to_string(),
+            status: ReaderStatus::Loading,
+            rx
+        }
+    }
+
+    pub fn poll(&mut self) {
+        if let Ok(result) = self.rx.try_recv() {
+            self.status = match result {
+                Ok(doc) => ReaderStatus::Ready(doc),
+                Err(e) => {
+                    tracing::error!("Could not extract {}: {:?}", self.url, e);
+                    ReaderStatus::Failed(e)
+                }
+            };
+        }
+    }
+
+    /// Draws the article. Returns true when the back button was clicked.
+    pub fn render(&mut self, ui: &mut Ui, images: &mut ImageCache) -> bool {
+        self.poll();
+
+        let mut back = false;
+        ui.horizontal(|ui| {
+            back = ui.button("⬅ back").clicked();
+            ui.hyperlink_to("open in browser", &self.url);
+        });
+        ui.separator();
+
+        match &self.status {
+            ReaderStatus::Loading => {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(PADDING * 4.);
+                    ui.spinner();
+                    ui.label("Loading article...");
+                });
+            },
+            ReaderStatus::Failed(e) => {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(PADDING * 4.);
+                    ui.label(format!("Could not load the article: {}", e));
+                    ui.hyperlink_to("Open it in the browser instead", &self.url);
+                });
+            },
+            ReaderStatus::Ready(doc) => {
+                // Keyed by url so that every article keeps its own scroll position.
+                ScrollArea::new([false, true])
+                    .id_source(&self.url)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let title = doc.title.as_deref().unwrap_or(&self.title);
+                        ui.heading(title);
+                        ui.add_space(PADDING * 2.);
+                        for block in &doc.blocks {
+                            render_block(ui, block, images);
+                            ui.add_space(PADDING);
+                        }
+                    });
+            },
+        }
+
+        back
+    }
+}
+
+fn render_block(ui: &mut Ui, block: &Block, images: &mut ImageCache) {
+    match block {
+        Block::Heading(level, inlines) => {
+            let style = if *level <= 2 { TextStyle::Heading } else { TextStyle::Button };
+            ui.add_space(PADDING);
+            render_inlines(ui, inlines, Some(style), false);
+        },
+        Block::Paragraph(inlines) => render_inlines(ui, inlines, None, false),
+        Block::Quote(inlines) => {
+            ui.horizontal(|ui| {
+                ui.add_space(PADDING * 4.);
+                render_inlines(ui, inlines, None, true);
+            });
+        },
+        Block::ListItem(inlines) => {
+            ui.horizontal_top(|ui| {
+                ui.label("•");
+                render_inlines(ui, inlines, None, false);
+            });
+        },
+        Block::Code(code) => {
+            ui.label(RichText::new(code).monospace());
+        },
+        Block::Image { src, alt } => {
+            match images.full(src) {
+                ImageState::Ready(texture) => {
+                    let size = texture.size_vec2();
+                    let scale = (ui.available_width() / size.x).min(1.);
+                    ui.image(texture, size * scale).on_hover_text(alt);
+                },
+                ImageState::Loading => {
+                    ui.spinner();
+                },
+                ImageState::Failed => {
+                    if !alt.is_empty() {
+                        ui.label(RichText::new(format!("[{}]", alt)).weak());
+                    }
+                },
+            }
+        },
+    }
+}
+
+fn render_inlines(ui: &mut Ui, inlines: &[Inline], style: Option<TextStyle>, italics: bool) {
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.;
+        for inline in inlines {
+            let styled = |text: RichText| {
+                let text = match &style {
+                    Some(style) => text.text_style(style.clone()).strong(),
+                    None => text,
+                };
+                if italics { text.italics() } else { text }
+            };
+            match inline {
+                Inline::Text(t) => { ui.label(styled(RichText::new(t))); },
+                Inline::Emphasis(t) => { ui.label(styled(RichText::new(t).italics())); },
+                Inline::Strong(t) => { ui.label(styled(RichText::new(t).strong())); },
+                Inline::Code(t) => { ui.label(styled(RichText::new(t).code())); },
+                Inline::Link { text, href } => {
+                    ui.hyperlink_to(styled(RichText::new(text)), href);
+                },
+            }
+        }
+    });
+}
@@ -0,0 +1,104 @@
+//! Summaries for the news cards. Article pages are downloaded and extracted
+//! off the UI thread; the summary itself is cheap and gets recomputed from
+//! the kept text when the sentence count changes.
+
+use newsapi::summary::summarize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Pages downloaded at the same time.
+const MAX_IN_FLIGHT: usize = 4;
+
+pub struct SummaryCache {
+    /// Article text by url, `None` while it loads.
+    texts: HashMap<String, Option<String>>,
+    summaries: HashMap<String, Vec<String>>,
+    sentences: usize,
+    /// Urls waiting to be downloaded, with the description to fall back on.
+    queue: VecDeque<(String, String)>,
+    in_flight: usize,
+    tx: Sender<(String, String)>,
+    rx: Receiver<(String, String)>
+}
+
+impl Default for SummaryCache {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        SummaryCache {
+            texts: HashMap::new(),
+            summaries: HashMap::new(),
+            sentences: 0,
+            queue: VecDeque::new(),
+            in_flight: 0,
+            tx,
+            rx
+        }
+    }
+}
+
+impl SummaryCache {
+    /// Summary of the article at `url`, queueing the page for download the
+    /// first time it is asked for. `None` while it loads.
+    pub fn summary(&mut self, url: &str, description: &str) -> Option<&[String]> {
+        if !self.texts.contains_key(url) {
+            self.texts.insert(url.to_string(), None);
+            self.queue.push_back((url.to_string(), description.to_string()));
+        }
+        self.summaries.get(url).map(|s| s.as_slice())
+    }
+
+    /// Summarises the texts downloaded since the last frame and starts the
+    /// next downloads. Call once per frame with the configured sentence
+    /// count.
+    pub fn poll(&mut self, sentences: usize) {
+        if sentences != self.sentences {
+            self.sentences = sentences;
+            self.summaries = self
+                .texts
+                .iter()
+                .filter_map(|(url, text)| Some((url.to_string(), summarize(text.as_ref()?, sentences))))
+                .collect();
+        }
+
+        while let Ok((url, text)) = self.rx.try_recv() {
+            self.in_flight -= 1;
+            self.summaries.insert(url.to_string(), summarize(&text, self.sentences));
+            self.texts.insert(url, Some(text));
+        }
+
+        // Turned off since they were asked for, the pages aren't needed.
+        while self.in_flight < MAX_IN_FLIGHT && self.sentences > 0 {
+            let (url, description) = match self.queue.pop_front() {
+                Some(next) => next,
+                None => break,
+            };
+            self.in_flight += 1;
+            spawn_load(url, description, self.tx.clone());
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_load(url: String, description: String, tx: Sender<(String, String)>) {
+    std::thread::spawn(move || {
+        let text = match newsapi::extract::fetch(&url) {
+            Ok(doc) => doc.text(),
+            Err(e) => {
+                tracing::warn!("Could not extract {}, summarising the description: {:?}", url, e);
+                description
+            }
+        };
+        let _ = tx.send((url, text));
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_load(url: String, description: String, tx: Sender<(String, String)>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let text = match newsapi::extract::fetch_web(&url).await {
+            Ok(doc) => doc.text(),
+            Err(_) => description,
+        };
+        let _ = tx.send((url, text));
+    });
+}
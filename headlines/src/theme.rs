@@ -0,0 +1,236 @@
+//! Named colour themes. The presets are compiled in from `themes/*.toml`;
+//! natively, more can be dropped into `<config dir>/headlines/themes/`,
+//! and are picked up again whenever a file there changes.
+
+use serde::Deserialize;
+use eframe::egui::{Color32, Stroke, Visuals};
+
+const PRESETS: &[&str] = &[
+    include_str!("../themes/light.toml"),
+    include_str!("../themes/dark.toml"),
+    include_str!("../themes/solarized.toml"),
+    include_str!("../themes/gruvbox.toml"),
+    include_str!("../themes/high-contrast.toml"),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Colors {
+    #[serde(with = "hex")]
+    pub background: Color32,
+    #[serde(with = "hex")]
+    pub card: Color32,
+    #[serde(with = "hex")]
+    pub title: Color32,
+    #[serde(with = "hex")]
+    pub description: Color32,
+    #[serde(with = "hex")]
+    pub hyperlink: Color32,
+    #[serde(with = "hex")]
+    pub accent: Color32,
+    /// Of the dot on the cards of positive and negative articles. Optional,
+    /// for themes written before there were any.
+    #[serde(with = "hex", default = "default_positive")]
+    pub positive: Color32,
+    #[serde(with = "hex", default = "default_negative")]
+    pub negative: Color32
+}
+
+fn default_positive() -> Color32 {
+    Color32::from_rgb(0, 170, 70)
+}
+
+fn default_negative() -> Color32 {
+    Color32::from_rgb(255, 0, 0)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    /// Whether to start from egui's dark or light widgets.
+    pub dark: bool,
+    /// Space around and between the parts of a card.
+    pub spacing: f32,
+    /// Sizes at the default font size; the font size setting scales them.
+    pub title_size: f32,
+    pub description_size: f32,
+    pub colors: Colors
+}
+
+impl Theme {
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = if self.dark { Visuals::dark() } else { Visuals::light() };
+        visuals.widgets.noninteractive.bg_fill = self.colors.background;
+        visuals.hyperlink_color = self.colors.hyperlink;
+        visuals.selection.bg_fill = self.colors.accent;
+        visuals.selection.stroke = Stroke::new(1., self.colors.accent);
+        visuals
+    }
+}
+
+mod hex {
+    use eframe::egui::Color32;
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    /// Parses `#rrggbb`.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color32, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let hex = s.strip_prefix('#').unwrap_or(&s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| D::Error::custom(format!("invalid colour {:?}, expected #rrggbb", s)))
+        };
+        if hex.len() != 6 {
+            return Err(D::Error::custom(format!("invalid colour {:?}, expected #rrggbb", s)));
+        }
+        Ok(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+/// The presets plus the user's own themes.
+pub struct ThemeSet {
+    pub themes: Vec<Theme>,
+    /// egui clock time of the last look at the theme directory.
+    #[cfg(not(target_arch = "wasm32"))]
+    last_check: f64,
+    #[cfg(not(target_arch = "wasm32"))]
+    stamps: Vec<(std::path::PathBuf, std::time::SystemTime)>
+}
+
+impl Default for ThemeSet {
+    fn default() -> Self {
+        let mut set = ThemeSet {
+            themes: presets(),
+            #[cfg(not(target_arch = "wasm32"))]
+            last_check: f64::NEG_INFINITY,
+            #[cfg(not(target_arch = "wasm32"))]
+            stamps: vec![]
+        };
+        set.poll(0.);
+        set
+    }
+}
+
+fn presets() -> Vec<Theme> {
+    PRESETS.iter().map(|t| toml::from_str(t).expect("invalid preset theme")).collect()
+}
+
+impl ThemeSet {
+    /// The theme called `name`, or the first preset if there's none.
+    pub fn get(&self, name: &str) -> &Theme {
+        self.themes.iter().find(|t| t.name == name).unwrap_or(&self.themes[0])
+    }
+
+    /// The theme after `name` in the list, wrapping around.
+    pub fn next(&self, name: &str) -> &Theme {
+        let i = self.themes.iter().position(|t| t.name == name).map(|i| i + 1).unwrap_or(0);
+        &self.themes[i % self.themes.len()]
+    }
+
+    /// Reloads the user's themes if a file in the theme directory was added,
+    /// removed or modified. Looks at most once a second.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll(&mut self, now: f64) {
+        if now - self.last_check < 1. {
+            return;
+        }
+        self.last_check = now;
+
+        let dir = match dirs::config_dir() {
+            Some(dir) => dir.join("headlines").join("themes"),
+            None => return,
+        };
+        let mut stamps: Vec<_> = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map(|e| e == "toml").unwrap_or(false))
+                .filter_map(|p| Some((p.clone(), p.metadata().ok()?.modified().ok()?)))
+                .collect(),
+            Err(_) => vec![],
+        };
+        stamps.sort();
+        if stamps == self.stamps {
+            return;
+        }
+
+        let mut themes = presets();
+        for (path, _) in &stamps {
+            let theme = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| toml::from_str::<Theme>(&s).map_err(|e| e.to_string()));
+            match theme {
+                Ok(theme) => {
+                    tracing::info!("Loaded theme {} from {}", theme.name, path.display());
+                    // A user theme with a preset's name replaces the preset.
+                    themes.retain(|t| t.name != theme.name);
+                    themes.push(theme);
+                },
+                Err(e) => tracing::warn!("Invalid theme {}: {}", path.display(), e),
+            }
+        }
+        self.themes = themes;
+        self.stamps = stamps;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn poll(&mut self, _now: f64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM: &str = r##"
+name = "custom"
+dark = true
+spacing = 4.0
+title_size = 16.0
+description_size = 12.0
+
+[colors]
+background = "#102030"
+card = "#000000"
+title = "ffffff"
+description = "#c8c8c8"
+hyperlink = "#ff8000"
+accent = "#00ff00"
+"##;
+
+    #[test]
+    fn presets_and_custom_themes_parse() {
+        assert_eq!(presets().len(), PRESETS.len());
+
+        let theme: Theme = toml::from_str(CUSTOM).unwrap();
+        assert_eq!(theme.name, "custom");
+        assert_eq!(theme.colors.background, Color32::from_rgb(0x10, 0x20, 0x30));
+        assert_eq!(theme.colors.title, Color32::WHITE);
+        assert_eq!(theme.visuals().hyperlink_color, Color32::from_rgb(255, 128, 0));
+        assert_eq!((theme.colors.positive, theme.colors.negative), (default_positive(), default_negative()));
+
+        let theme: Theme = toml::from_str(&format!("{}positive = \"#00ff80\"\n", CUSTOM)).unwrap();
+        assert_eq!(theme.colors.positive, Color32::from_rgb(0, 255, 128));
+    }
+
+    #[test]
+    fn invalid_colours_are_rejected() {
+        for colour in ["#12345", "#1234567", "#gg0000", "#ééé", "red"] {
+            let theme = CUSTOM.replace("#102030", colour);
+            assert!(toml::from_str::<Theme>(&theme).is_err(), "{} was accepted", colour);
+        }
+        let theme = CUSTOM.replace("spacing = 4.0", "spacing = \"wide\"");
+        assert!(toml::from_str::<Theme>(&theme).is_err());
+        let theme = CUSTOM.replace("accent = \"#00ff00\"", "");
+        assert!(toml::from_str::<Theme>(&theme).is_err());
+    }
+
+    #[test]
+    fn unknown_names_fall_back_to_the_first_theme() {
+        let set = ThemeSet { themes: presets(), last_check: 0., stamps: vec![] };
+        assert_eq!(set.get("dark").name, "dark");
+        assert_eq!(set.get("nope").name, "light");
+        assert_eq!(set.next("light").name, "dark");
+        assert_eq!(set.next("high-contrast").name, "light");
+        assert_eq!(set.next("nope").name, "light");
+    }
+}
@@ -0,0 +1,130 @@
+//! The "Trending" panel: terms standing out in today's articles, against
+//! the ones before. On the desktop they come from the archive `clinews`
+//! keeps, so both list the same terms. In the browser, which has no files,
+//! they come from the articles received in the app.
+
+use newsapi::archive::{self, ARCHIVE_DAYS};
+use newsapi::keywords::{sparkline, Corpus, Trend};
+use eframe::egui::RichText;
+
+/// Days shown in the sparklines, today included.
+const DAYS: i64 = 7;
+/// Terms shown.
+const TOP: usize = 10;
+
+#[derive(Default)]
+pub struct Trending {
+    corpus: Corpus,
+    /// The day the trends were worked out for, and the trends with their
+    /// sparklines. Cleared when articles come in.
+    trends: Option<(i64, Vec<(Trend, String)>)>
+}
+
+/// Seconds the local time is ahead of UTC.
+fn offset() -> i64 {
+    chrono::Local::now().offset().local_minus_utc() as i64
+}
+
+/// Days since the Unix epoch, in local time, as `clinews trends` counts them.
+fn today() -> i64 {
+    archive::day(chrono::Utc::now().timestamp(), offset())
+}
+
+/// Where `clinews` keeps its archive: in `<data dir>/clinews/`, or the
+/// directory pointed to by `$CLINEWS_DATA_DIR`.
+#[cfg(not(target_arch = "wasm32"))]
+fn archive_path() -> Option<std::path::PathBuf> {
+    let dir = match std::env::var("CLINEWS_DATA_DIR") {
+        Ok(dir) => std::path::PathBuf::from(dir),
+        Err(_) => dirs::data_dir()?.join("clinews"),
+    };
+    Some(dir.join("archive.json"))
+}
+
+/// Adds `articles` to the archive. Fetches run on threads of their own, so
+/// they take turns not to lose each other's articles.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn record(articles: &[newsapi::Article]) {
+    static RECORDING: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let path = match archive_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let _turn = RECORDING.lock();
+    if let Err(e) = archive::record(&path, articles, chrono::Utc::now().timestamp()) {
+        tracing::error!("Could not archive the articles: {:?}", e);
+    }
+}
+
+impl Trending {
+    pub fn new(corpus: Corpus) -> Trending {
+        Trending { corpus, trends: None }
+    }
+
+    /// The trends of the archived articles that pass `filters`. None while
+    /// the filters are invalid, as no articles are shown either.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_archive(filters: &[newsapi::filter::FilterRule]) -> Trending {
+        let filters = match newsapi::filter::FilterEngine::new(filters) {
+            Ok(filters) => filters,
+            Err(_) => return Trending::default(),
+        };
+        let archived = match archive_path().map(|path| archive::load(&path)) {
+            Some(Ok(archived)) => archived,
+            Some(Err(e)) => {
+                tracing::error!("Could not read the archive: {:?}", e);
+                vec![]
+            },
+            None => vec![],
+        };
+        Trending::new(archive::corpus(&archived, &filters, offset()))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn corpus(&self) -> &Corpus {
+        &self.corpus
+    }
+
+    pub fn add(&mut self, url: &str, title: &str, description: &str) {
+        let today = today();
+        if self.corpus.add(today, url, &format!("{}\n{}", title, description)) {
+            self.corpus.forget_before(today - ARCHIVE_DAYS + 1);
+            self.trends = None;
+        }
+    }
+
+    /// Lists the trending terms, and returns the one clicked if any.
+    pub fn render(&mut self, ui: &mut eframe::egui::Ui) -> Option<String> {
+        let today = today();
+        if !matches!(self.trends, Some((day, _)) if day == today) {
+            let trends = self.corpus
+                .trending(today, TOP)
+                .into_iter()
+                .map(|t| {
+                    let history = sparkline(&self.corpus.history(&t.term, today - DAYS + 1..=today));
+                    (t, history)
+                })
+                .collect();
+            self.trends = Some((today, trends));
+        }
+
+        let trends = match &self.trends {
+            Some((_, trends)) if !trends.is_empty() => trends,
+            _ => {
+                ui.weak("nothing stands out yet");
+                return None;
+            }
+        };
+        let mut clicked = None;
+        for (trend, history) in trends {
+            ui.horizontal(|ui| {
+                if ui.link(&trend.term).on_hover_text("search for it").clicked() {
+                    clicked = Some(trend.term.to_string());
+                }
+                ui.label(RichText::new(history).monospace())
+                    .on_hover_text(format!("{} articles today, {} days shown", trend.articles, DAYS));
+            });
+        }
+        clicked
+    }
+}
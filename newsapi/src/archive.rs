@@ -0,0 +1,73 @@
+//! The articles seen over the last month, shared by `clinews`, `clinews
+//! watch` and the headlines app in one `archive.json`. Trends are worked out
+//! from it, by the day articles were first seen in local time.
+
+use crate::filter::FilterEngine;
+use crate::keywords::Corpus;
+use crate::{Article, NewsAPIError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How long articles are kept in the archive.
+pub const ARCHIVE_DAYS: i64 = 30;
+
+/// An article as first seen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archived {
+    /// Unix time.
+    pub seen_at: i64,
+    pub article: Article
+}
+
+/// Every article archived at `path`, oldest first.
+pub fn load(path: &Path) -> Result<Vec<Archived>, NewsAPIError> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Adds the articles not archived at `path` yet, as seen at `now` (Unix
+/// time), and forgets those older than a month.
+pub fn record(path: &Path, articles: &[Article], now: i64) -> Result<(), NewsAPIError> {
+    let mut archive = load(path)?;
+    archive.retain(|a| now - a.seen_at < ARCHIVE_DAYS * 86_400);
+    for article in articles {
+        if !archive.iter().any(|a| a.article.url == article.url) {
+            archive.push(Archived { seen_at: now, article: article.clone() });
+        }
+    }
+    replace(path, &serde_json::to_string(&archive)?)?;
+    Ok(())
+}
+
+/// Replaces the file at `path` in one go: several programs may use it at
+/// the same time, and none should read a half written file.
+pub fn replace(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Day number of the Unix time `at`, in the time zone `offset` seconds
+/// ahead of UTC.
+pub fn day(at: i64, offset: i64) -> i64 {
+    (at + offset).div_euclid(86_400)
+}
+
+/// The titles and descriptions of the archived articles that pass the
+/// filters, by the day they were first seen.
+pub fn corpus(archive: &[Archived], filters: &FilterEngine, offset: i64) -> Corpus {
+    let mut corpus = Corpus::default();
+    for archived in archive.iter().filter(|a| filters.is_visible(&a.article)) {
+        let article = &archived.article;
+        let text = format!("{}\n{}", article.title, article.description.as_deref().unwrap_or_default());
+        corpus.add(day(archived.seen_at, offset), &article.url, &text);
+    }
+    corpus
+}
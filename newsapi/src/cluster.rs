@@ -0,0 +1,97 @@
+//! Groups headlines about the same story. Two titles are linked when they
+//! share enough content words, and linked titles end up in one cluster,
+//! even through a chain of links.
+
+use crate::headline;
+use crate::summary::STOPWORDS;
+use std::collections::HashSet;
+
+/// Content words two titles need in common, at the least.
+const MIN_SHARED: usize = 2;
+/// Share of the shorter title's words the two need in common.
+const MIN_OVERLAP: f64 = 0.5;
+
+/// Lowercased words of a title, without stopwords, numbers and the
+/// " - Source" suffix newsapi adds. A plural "s" is dropped so that
+/// "rate" and "rates" match.
+pub fn title_words(title: &str) -> HashSet<String> {
+    headline(title)
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.to_lowercase())
+        .filter(|w| w.chars().count() > 2 && !STOPWORDS.contains(&w.as_str()) && !w.chars().all(|c| c.is_numeric()))
+        .map(|w| match w.strip_suffix('s') {
+            Some(stem) if stem.len() > 3 && !stem.ends_with('s') => stem.to_string(),
+            _ => w,
+        })
+        .collect()
+}
+
+fn related(a: &HashSet<String>, b: &HashSet<String>) -> bool {
+    let shared = a.intersection(b).count();
+    let shorter = a.len().min(b.len());
+    shared >= MIN_SHARED && shared as f64 / shorter as f64 >= MIN_OVERLAP
+}
+
+/// Indices of `titles` grouped by story. Clusters come in the order of their
+/// first title, and keep their titles in the original order.
+pub fn cluster(titles: &[&str]) -> Vec<Vec<usize>> {
+    let words: Vec<HashSet<String>> = titles.iter().map(|t| title_words(t)).collect();
+    let mut parent: Vec<usize> = (0..titles.len()).collect();
+
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for i in 0..titles.len() {
+        for j in i + 1..titles.len() {
+            if related(&words[i], &words[j]) {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    let mut roots: Vec<usize> = vec![];
+    for i in 0..titles.len() {
+        let r = root(&mut parent, i);
+        match roots.iter().position(|&x| x == r) {
+            Some(c) => clusters[c].push(i),
+            None => {
+                roots.push(r);
+                clusters.push(vec![i]);
+            }
+        }
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_words_skip_noise() {
+        let words = title_words("Fed raises interest rates by 0.5 points - The Wire");
+        let mut words: Vec<_> = words.into_iter().collect();
+        words.sort();
+        assert_eq!(words, vec!["fed", "interest", "point", "raise", "rate"]);
+    }
+
+    #[test]
+    fn clusters_titles_about_the_same_story() {
+        let titles = [
+            "Fed raises interest rates by half a point - The Wire",
+            "Championship final goes to extra time",
+            "Interest rates: what the Fed raise means for mortgages - Gazette",
+            "Storm warning for the east coast",
+            "Mortgages get pricier as the Fed raises rates",
+        ];
+        assert_eq!(cluster(&titles), vec![vec![0, 2, 4], vec![1], vec![3]]);
+        assert!(cluster(&[]).is_empty());
+    }
+}
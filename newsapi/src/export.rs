@@ -0,0 +1,604 @@
+//! Reading digests: a set of articles grouped by source or category, written
+//! out as Markdown, as a standalone HTML page, as an EPUB 3 book or as JSON.
+
+use chrono::{DateTime, SecondsFormat};
+use crate::extract::{inline_markdown, Block, Document, Inline};
+use crate::sentiment::Tone;
+use crate::Article;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Markdown,
+    Html,
+    Epub,
+    Json
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Self::Markdown, Self::Html, Self::Epub, Self::Json];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Markdown => "Markdown",
+            Self::Html => "HTML",
+            Self::Epub => "EPUB",
+            Self::Json => "JSON",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Epub => "epub",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "epub" => Ok(Self::Epub),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown format {:?}, pick one of markdown, html, epub, json", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Source,
+    Category
+}
+
+impl GroupBy {
+    pub const ALL: [GroupBy; 2] = [Self::Source, Self::Category];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Source => "Source",
+            Self::Category => "Category",
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "source" => Ok(Self::Source),
+            "category" => Ok(Self::Category),
+            _ => Err(format!("unknown grouping {:?}, pick source or category", s)),
+        }
+    }
+}
+
+/// One article of a digest.
+#[derive(Debug, Clone, Default)]
+pub struct Item {
+    pub title: String,
+    pub url: String,
+    pub source: String,
+    pub category: Option<String>,
+    pub author: Option<String>,
+    /// RFC 3339 timestamp.
+    pub published_at: Option<String>,
+    pub description: Option<String>,
+    /// Compound sentiment of the title and description, from -1 to 1.
+    pub sentiment: Option<f64>,
+    /// The extracted article, when the full text is wanted.
+    pub text: Option<Document>
+}
+
+impl Item {
+    pub fn from_article(article: &Article, category: Option<&str>) -> Item {
+        Item {
+            title: article.title().to_string(),
+            url: article.url().to_string(),
+            source: article.source().name().to_string(),
+            category: category.map(|c| c.to_string()),
+            author: article.author().cloned(),
+            published_at: article.published_at().cloned(),
+            description: article.description().cloned(),
+            sentiment: Some(article.sentiment()),
+            text: None
+        }
+    }
+
+    /// Source, publication date and author, separated by dots.
+    fn meta(&self) -> String {
+        let mut parts = vec![];
+        if !self.source.is_empty() {
+            parts.push(self.source.to_string());
+        }
+        if let Some(date) = self.published_at.as_ref().and_then(|p| p.get(..10)) {
+            parts.push(date.to_string());
+        }
+        if let Some(author) = self.author.as_ref().filter(|a| !a.trim().is_empty()) {
+            parts.push(format!("by {}", author.trim()));
+        }
+        parts.join(" · ")
+    }
+}
+
+pub struct Digest {
+    title: String,
+    group_by: GroupBy,
+    /// Unix time the digest was made, for the EPUB metadata.
+    created: u64,
+    items: Vec<Item>
+}
+
+impl Digest {
+    pub fn new(title: &str, items: Vec<Item>) -> Digest {
+        #[cfg(not(target_arch = "wasm32"))]
+        let created = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        #[cfg(target_arch = "wasm32")]
+        let created = 0;
+
+        Digest {
+            title: title.to_string(),
+            group_by: GroupBy::Source,
+            created,
+            items
+        }
+    }
+
+    pub fn group_by(&mut self, group_by: GroupBy) -> &mut Digest {
+        self.group_by = group_by;
+        self
+    }
+
+    pub fn created(&mut self, created: u64) -> &mut Digest {
+        self.created = created;
+        self
+    }
+
+    /// The items by group name, groups sorted by name and items kept in
+    /// their original order. Items without a category end up under "Other".
+    pub fn groups(&self) -> Vec<(String, Vec<&Item>)> {
+        let mut groups: Vec<(String, Vec<&Item>)> = vec![];
+        for item in &self.items {
+            let name = match self.group_by {
+                GroupBy::Source => Some(item.source.as_str()).filter(|s| !s.is_empty()),
+                GroupBy::Category => item.category.as_deref(),
+            }.unwrap_or("Other");
+            match groups.iter_mut().find(|(n, _)| n == name) {
+                Some((_, items)) => items.push(item),
+                None => groups.push((name.to_string(), vec![item])),
+            }
+        }
+        groups.sort_by_key(|(name, _)| name.to_lowercase());
+        groups
+    }
+
+    pub fn render(&self, format: Format) -> Vec<u8> {
+        match format {
+            Format::Markdown => self.to_markdown().into_bytes(),
+            Format::Html => self.to_html().into_bytes(),
+            Format::Epub => self.to_epub(),
+            Format::Json => self.to_json().into_bytes(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = vec![format!("# {}", self.title)];
+        for (group, items) in self.groups() {
+            out.push(format!("## {}", group));
+            for item in items {
+                out.push(format!("### [{}]({})", item.title, item.url));
+                let meta = item.meta();
+                if !meta.is_empty() {
+                    out.push(format!("*{}*", meta));
+                }
+                if let Some(description) = item.description.as_ref().filter(|d| !d.trim().is_empty()) {
+                    out.push(description.trim().to_string());
+                }
+                if let Some(text) = &item.text {
+                    out.extend(text.blocks.iter().map(block_markdown));
+                }
+            }
+        }
+        out.join("\n\n") + "\n"
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut toc = String::new();
+        let mut body = String::new();
+        for (i, (group, items)) in self.groups().into_iter().enumerate() {
+            toc.push_str(&format!("<li><a href=\"#group-{}\">{}</a></li>\n", i + 1, escape(&group)));
+            body.push_str(&format!("<section id=\"group-{}\">\n<h2>{}</h2>\n", i + 1, escape(&group)));
+            for item in items {
+                body.push_str(&item_html(item));
+            }
+            body.push_str("</section>\n");
+        }
+
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{title}</title>\n<style>\n{style}</style>\n</head>\n<body>\n\
+             <h1>{title}</h1>\n<nav>\n<ul>\n{toc}</ul>\n</nav>\n{body}</body>\n</html>\n",
+            title = escape(&self.title),
+            style = STYLE,
+            toc = toc,
+            body = body
+        )
+    }
+
+    /// The items as a JSON object, with the full text as Markdown. Groups
+    /// are left to the reader, every item has its source and category.
+    pub fn to_json(&self) -> String {
+        let articles: Vec<serde_json::Value> = self
+            .items
+            .iter()
+            .map(|item| {
+                let text = item.text.as_ref().map(|text| {
+                    text.blocks.iter().map(block_markdown).collect::<Vec<String>>().join("\n\n")
+                });
+                serde_json::json!({
+                    "title": item.title,
+                    "url": item.url,
+                    "source": item.source,
+                    "category": item.category,
+                    "author": item.author,
+                    "published_at": item.published_at,
+                    "description": item.description,
+                    "sentiment": item.sentiment.map(|s| Tone::of(s).name()),
+                    "sentiment_score": item.sentiment,
+                    "text": text
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&serde_json::json!({ "title": self.title, "articles": articles })).unwrap_or_default() + "\n"
+    }
+
+    /// An EPUB 3 book with one chapter per group.
+    pub fn to_epub(&self) -> Vec<u8> {
+        let groups = self.groups();
+        let mut zip = ZipWriter::default();
+        // The mimetype has to come first, uncompressed, for readers to
+        // recognise the file.
+        zip.add("mimetype", b"application/epub+zip");
+        zip.add("META-INF/container.xml", CONTAINER.as_bytes());
+
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        let mut nav = String::new();
+        for (i, (group, items)) in groups.iter().enumerate() {
+            let file = format!("chapter-{}.xhtml", i + 1);
+            let body: String = items.iter().map(|item| item_html(item)).collect();
+            zip.add(&format!("OEBPS/{}", file), xhtml(group, &format!("<h2>{}</h2>\n{}", escape(group), body)).as_bytes());
+            // Images stay on the web, which readers only fetch for chapters
+            // that declare it.
+            let remote = items.iter().any(|item| has_images(item));
+            manifest.push_str(&format!(
+                "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{}/>\n",
+                i + 1,
+                file,
+                if remote { " properties=\"remote-resources\"" } else { "" }
+            ));
+            spine.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", i + 1));
+            nav.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", file, escape(group)));
+        }
+
+        let nav = format!("<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n", escape(&self.title), nav);
+        zip.add("OEBPS/nav.xhtml", xhtml(&self.title, &nav).as_bytes());
+
+        let urls: Vec<&str> = self.items.iter().map(|i| i.url.as_str()).collect();
+        let package = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\">\n\
+             <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             <dc:identifier id=\"id\">urn:newsapi-digest:{id:08x}</dc:identifier>\n\
+             <dc:title>{title}</dc:title>\n<dc:language>en</dc:language>\n\
+             <meta property=\"dcterms:modified\">{modified}</meta>\n</metadata>\n\
+             <manifest>\n<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+             {manifest}</manifest>\n<spine>\n{spine}</spine>\n</package>\n",
+            id = crc32fast::hash(format!("{}\n{}", self.title, urls.join("\n")).as_bytes()),
+            title = escape(&self.title),
+            modified = timestamp(self.created),
+            manifest = manifest,
+            spine = spine
+        );
+        zip.add("OEBPS/content.opf", package.as_bytes());
+
+        zip.finish()
+    }
+}
+
+const STYLE: &str = "body { max-width: 42em; margin: 2em auto; padding: 0 1em; font-family: Georgia, serif; line-height: 1.5; color: #222; }
+h1, h2, h3 { font-family: Helvetica, Arial, sans-serif; line-height: 1.2; }
+h2 { border-bottom: 1px solid #ccc; padding-bottom: .2em; margin-top: 2em; }
+a { color: #1a5fb4; }
+.meta { color: #666; font-style: italic; }
+blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1em; color: #555; }
+img { max-width: 100%; }
+";
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+</rootfiles>
+</container>
+";
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"en\">\n\
+         <head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+/// Markdown for a block of an article inside the digest, where headings
+/// start at level four.
+fn block_markdown(block: &Block) -> String {
+    match block {
+        Block::Heading(level, inlines) => format!("{} {}", "#".repeat((*level as usize + 2).clamp(4, 6)), inline_markdown(inlines)),
+        Block::Paragraph(inlines) => inline_markdown(inlines),
+        Block::Quote(inlines) => format!("> {}", inline_markdown(inlines)),
+        Block::ListItem(inlines) => format!("* {}", inline_markdown(inlines)),
+        Block::Code(code) => format!("```\n{}\n```", code),
+        Block::Image { src, alt } => format!("![{}]({})", alt, src),
+    }
+}
+
+fn has_images(item: &Item) -> bool {
+    item.text.as_ref().map(|text| text.blocks.iter().any(|b| matches!(b, Block::Image { .. }))).unwrap_or(false)
+}
+
+/// An article as HTML that is also valid XHTML, for the EPUB chapters.
+fn item_html(item: &Item) -> String {
+    let mut out = format!("<article>\n<h3><a href=\"{}\">{}</a></h3>\n", escape(&item.url), escape(&item.title));
+    let meta = item.meta();
+    if !meta.is_empty() {
+        out.push_str(&format!("<p class=\"meta\">{}</p>\n", escape(&meta)));
+    }
+    if let Some(description) = item.description.as_ref().filter(|d| !d.trim().is_empty()) {
+        out.push_str(&format!("<p>{}</p>\n", escape(description.trim())));
+    }
+    if let Some(text) = &item.text {
+        let mut in_list = false;
+        for block in &text.blocks {
+            let is_item = matches!(block, Block::ListItem(_));
+            if is_item != in_list {
+                out.push_str(if is_item { "<ul>\n" } else { "</ul>\n" });
+                in_list = is_item;
+            }
+            out.push_str(&match block {
+                Block::Heading(level, inlines) => {
+                    let level = (*level + 2).clamp(4, 6);
+                    format!("<h{}>{}</h{}>\n", level, inline_html(inlines), level)
+                },
+                Block::Paragraph(inlines) => format!("<p>{}</p>\n", inline_html(inlines)),
+                Block::Quote(inlines) => format!("<blockquote><p>{}</p></blockquote>\n", inline_html(inlines)),
+                Block::ListItem(inlines) => format!("<li>{}</li>\n", inline_html(inlines)),
+                Block::Code(code) => format!("<pre><code>{}</code></pre>\n", escape(code)),
+                Block::Image { src, alt } => format!("<p><img src=\"{}\" alt=\"{}\"/></p>\n", escape(src), escape(alt)),
+            });
+        }
+        if in_list {
+            out.push_str("</ul>\n");
+        }
+    }
+    out.push_str("</article>\n");
+    out
+}
+
+fn inline_html(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => escape(text),
+            Inline::Emphasis(text) => format!("<em>{}</em>", escape(text)),
+            Inline::Strong(text) => format!("<strong>{}</strong>", escape(text)),
+            Inline::Code(text) => format!("<code>{}</code>", escape(text)),
+            Inline::Link { text, href } => format!("<a href=\"{}\">{}</a>", escape(href), escape(text)),
+        })
+        .collect()
+}
+
+/// Escapes text for HTML and XML.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `secs` since the Unix epoch as `YYYY-MM-DDThh:mm:ssZ`.
+pub fn timestamp(secs: u64) -> String {
+    let date = i64::try_from(secs).ok().and_then(|secs| DateTime::from_timestamp(secs, 0)).unwrap_or_default();
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Just enough of the zip format for an EPUB: files are stored as they are,
+/// without compression.
+#[derive(Default)]
+struct ZipWriter {
+    out: Vec<u8>,
+    central: Vec<u8>,
+    count: u16
+}
+
+impl ZipWriter {
+    fn add(&mut self, name: &str, data: &[u8]) {
+        let offset = self.out.len() as u32;
+        let crc = crc32fast::hash(data);
+        let size = data.len() as u32;
+        // Version 2.0, no flags, stored, 1980-01-01 00:00.
+        let common = |buf: &mut Vec<u8>| {
+            for n in [20u16, 0, 0, 0, 0x21] {
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            for n in [crc, size, size] {
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            buf.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            buf.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        self.out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        common(&mut self.out);
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(data);
+
+        self.central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes());
+        common(&mut self.central);
+        // No comment, disk 0, no attributes.
+        for n in [0u16, 0, 0] {
+            self.central.extend_from_slice(&n.to_le_bytes());
+        }
+        self.central.extend_from_slice(&0u32.to_le_bytes());
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.count += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.out.len() as u32;
+        let size = self.central.len() as u32;
+        self.out.append(&mut self.central);
+        self.out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        for n in [0u16, 0, self.count, self.count] {
+            self.out.extend_from_slice(&n.to_le_bytes());
+        }
+        self.out.extend_from_slice(&size.to_le_bytes());
+        self.out.extend_from_slice(&offset.to_le_bytes());
+        self.out.extend_from_slice(&0u16.to_le_bytes());
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, source: &str, category: Option<&str>) -> Item {
+        Item {
+            title: title.to_string(),
+            url: format!("https://{}.example/{}", source.to_lowercase(), title.len()),
+            source: source.to_string(),
+            category: category.map(|c| c.to_string()),
+            published_at: Some("2022-06-10T09:00:00Z".to_string()),
+            description: Some(format!("About {}.", title)),
+            ..Item::default()
+        }
+    }
+
+    fn digest() -> Digest {
+        let mut digest = Digest::new("Morning <digest>", vec![
+            item("Rates rise", "Wire", Some("Business")),
+            item("Cup final", "Gazette", Some("Sports")),
+            item("Chip shortage", "Wire", None),
+        ]);
+        digest.created(1_654_858_800);
+        digest
+    }
+
+    #[test]
+    fn groups_by_source_or_category() {
+        let mut digest = digest();
+        let names = |d: &Digest| d.groups().iter().map(|(n, items)| (n.clone(), items.len())).collect::<Vec<_>>();
+        assert_eq!(names(&digest), vec![("Gazette".to_string(), 1), ("Wire".to_string(), 2)]);
+        digest.group_by(GroupBy::Category);
+        assert_eq!(
+            names(&digest),
+            vec![("Business".to_string(), 1), ("Other".to_string(), 1), ("Sports".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn renders_markdown_and_html() {
+        let mut digest = digest();
+        digest.items[0].text = Some(Document {
+            title: None,
+            blocks: vec![
+                Block::Heading(2, vec![Inline::Text("Why".into())]),
+                Block::Paragraph(vec![Inline::Text("Inflation & ".into()), Inline::Emphasis("wages".into())]),
+            ]
+        });
+
+        let markdown = digest.to_markdown();
+        assert!(markdown.starts_with("# Morning <digest>\n\n## Gazette\n\n### [Cup final](https://gazette.example/9)"));
+        assert!(markdown.contains("*Wire · 2022-06-10*\n\nAbout Rates rise.\n\n#### Why\n\nInflation & *wages*"));
+
+        let html = digest.to_html();
+        assert!(html.contains("<title>Morning &lt;digest&gt;</title>"));
+        assert!(html.contains("<h2>Wire</h2>"));
+        assert!(html.contains("<h4>Why</h4>\n<p>Inflation &amp; <em>wages</em></p>"));
+    }
+
+    #[test]
+    fn writes_json_with_sentiment() {
+        let mut digest = digest();
+        digest.items[0].sentiment = Some(-0.6);
+        let json: serde_json::Value = serde_json::from_str(&digest.to_json()).unwrap();
+        assert_eq!(json["title"], "Morning <digest>");
+        assert_eq!(json["articles"][0]["url"], "https://wire.example/10");
+        assert_eq!(json["articles"][0]["sentiment"], "negative");
+        assert_eq!(json["articles"][0]["sentiment_score"], -0.6);
+        assert!(json["articles"][1]["sentiment_score"].is_null());
+        assert_eq!(json["articles"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn writes_an_epub_container() {
+        let epub = digest().to_epub();
+        assert_eq!(&epub[..4], b"PK\x03\x04");
+        // The first entry is the uncompressed mimetype.
+        assert_eq!(&epub[30..38], b"mimetype");
+        assert_eq!(&epub[38..58], b"application/epub+zip");
+
+        let text = String::from_utf8_lossy(&epub);
+        for name in ["META-INF/container.xml", "OEBPS/content.opf", "OEBPS/nav.xhtml", "OEBPS/chapter-2.xhtml"] {
+            assert!(text.contains(name), "{} missing", name);
+        }
+        assert!(text.contains("<meta property=\"dcterms:modified\">2022-06-10T11:00:00Z</meta>"));
+        assert!(!text.contains("remote-resources"));
+        // End of central directory with the number of entries.
+        let end = &epub[epub.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 6);
+    }
+
+    #[test]
+    fn declares_remote_images_in_the_epub() {
+        let mut digest = digest();
+        digest.items[1].text = Some(Document {
+            title: None,
+            blocks: vec![Block::Image { src: "https://gazette.example/cup.jpg".into(), alt: "The cup".into() }]
+        });
+        let epub = String::from_utf8_lossy(&digest.to_epub()).to_string();
+        assert!(epub.contains(
+            "<item id=\"chapter-1\" href=\"chapter-1.xhtml\" media-type=\"application/xhtml+xml\" properties=\"remote-resources\"/>"
+        ));
+        assert!(epub.contains("<item id=\"chapter-2\" href=\"chapter-2.xhtml\" media-type=\"application/xhtml+xml\"/>"));
+    }
+}
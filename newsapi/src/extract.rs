@@ -0,0 +1,552 @@
+//! Readability-style extraction of the main content of an article page.
+//!
+//! Paragraphs are scored by their length and number of commas, and the score
+//! is handed up to their parent and grandparent. The element with the best
+//! score, discounted by how much of its text is links, is taken to be the
+//! article body. Anything that looks like navigation, comments, sharing
+//! widgets or ads is skipped.
+
+use crate::NewsAPIError;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+use std::time::Duration;
+use url::Url;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    Text(String),
+    Emphasis(String),
+    Strong(String),
+    Code(String),
+    Link { text: String, href: String }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Heading(u8, Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    Quote(Vec<Inline>),
+    ListItem(Vec<Inline>),
+    Code(String),
+    Image { src: String, alt: String }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub title: Option<String>,
+    pub blocks: Vec<Block>
+}
+
+impl Document {
+    /// Plain text of the body, one block per line. Images are left out.
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::Heading(_, inlines)
+                | Block::Paragraph(inlines)
+                | Block::Quote(inlines)
+                | Block::ListItem(inlines) => Some(inline_text(inlines)),
+                Block::Code(code) => Some(code.to_string()),
+                Block::Image { .. } => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The article as Markdown, title first.
+    pub fn to_markdown(&self) -> String {
+        let mut out = Vec::new();
+        if let Some(title) = &self.title {
+            out.push(format!("# {}", title));
+        }
+        for block in &self.blocks {
+            out.push(match block {
+                Block::Heading(level, inlines) => {
+                    // The title is the only level one heading.
+                    format!("{} {}", "#".repeat((*level).max(2) as usize), inline_markdown(inlines))
+                },
+                Block::Paragraph(inlines) => inline_markdown(inlines),
+                Block::Quote(inlines) => format!("> {}", inline_markdown(inlines)),
+                Block::ListItem(inlines) => format!("* {}", inline_markdown(inlines)),
+                Block::Code(code) => format!("```\n{}\n```", code),
+                Block::Image { src, alt } => format!("![{}]({})", alt, src),
+            });
+        }
+        out.join("\n\n")
+    }
+}
+
+pub fn inline_markdown(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|i| match i {
+            Inline::Text(t) => t.to_string(),
+            Inline::Emphasis(t) => format!("*{}*", t),
+            Inline::Strong(t) => format!("**{}**", t),
+            Inline::Code(t) => format!("`{}`", t),
+            Inline::Link { text, href } => format!("[{}]({})", text, href),
+        })
+        .collect()
+}
+
+pub fn inline_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|i| match i {
+            Inline::Text(t) | Inline::Emphasis(t) | Inline::Strong(t) | Inline::Code(t) => t.as_str(),
+            Inline::Link { text, .. } => text.as_str(),
+        })
+        .collect()
+}
+
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "nav", "header", "footer", "aside", "form",
+    "iframe", "button", "svg", "select", "input", "textarea", "template"
+];
+
+const BLOCK_TAGS: &[&str] = &[
+    "p", "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "ul", "ol", "li",
+    "pre", "figure", "img", "table", "div", "section", "article", "main"
+];
+
+struct Patterns {
+    negative: Regex,
+    positive: Regex
+}
+
+impl Patterns {
+    fn new() -> Patterns {
+        Patterns {
+            negative: Regex::new(
+                r"(?i)comment|sidebar|footer|footnote|masthead|menu|nav|share|social|advert|\bads?\b|promo|sponsor|related|recommend|subscribe|newsletter|cookie|popup|modal|breadcrumb|byline|tags?\b|widget|outbrain|taboola"
+            ).unwrap(),
+            positive: Regex::new(r"(?i)article|body|content|entry|main|post|story|text").unwrap()
+        }
+    }
+
+    fn class_and_id(el: &ElementRef) -> String {
+        format!(
+            "{} {}",
+            el.value().attr("class").unwrap_or_default(),
+            el.value().attr("id").unwrap_or_default()
+        )
+    }
+
+    /// Looks like boilerplate: a skipped tag, or a class or id that reads
+    /// like navigation or ads without also reading like content.
+    fn is_unlikely(&self, el: &ElementRef) -> bool {
+        let tag = el.value().name();
+        if SKIPPED_TAGS.contains(&tag) {
+            return true;
+        }
+        if tag == "body" || tag == "html" || tag == "article" || tag == "main" {
+            return false;
+        }
+        let names = Self::class_and_id(el);
+        self.negative.is_match(&names) && !self.positive.is_match(&names)
+    }
+
+    fn class_weight(&self, el: &ElementRef) -> f64 {
+        let names = Self::class_and_id(el);
+        let mut weight = 0.;
+        if self.negative.is_match(&names) {
+            weight -= 25.;
+        }
+        if self.positive.is_match(&names) {
+            weight += 25.;
+        }
+        weight
+    }
+}
+
+fn tag_weight(tag: &str) -> f64 {
+    match tag {
+        "article" => 10.,
+        "div" | "main" | "section" => 5.,
+        "pre" | "td" | "blockquote" => 3.,
+        "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.,
+        _ => 0.,
+    }
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn text_len(el: &ElementRef) -> usize {
+    el.text().map(|t| t.trim().len()).sum()
+}
+
+fn link_density(el: &ElementRef) -> f64 {
+    let total = text_len(el);
+    if total == 0 {
+        return 0.;
+    }
+    let links: usize = el
+        .select(&Selector::parse("a").unwrap())
+        .map(|a| text_len(&a))
+        .sum();
+    links as f64 / total as f64
+}
+
+fn extract_title(doc: &Html) -> Option<String> {
+    let og = Selector::parse(r#"meta[property="og:title"]"#).unwrap();
+    let title = Selector::parse("title").unwrap();
+    let h1 = Selector::parse("h1").unwrap();
+
+    doc.select(&og)
+        .filter_map(|m| m.value().attr("content"))
+        .map(normalize_whitespace)
+        .chain(doc.select(&title).map(|t| normalize_whitespace(&t.text().collect::<String>())))
+        .chain(doc.select(&h1).map(|t| normalize_whitespace(&t.text().collect::<String>())))
+        .find(|t| !t.is_empty())
+}
+
+/// Finds the element that most likely holds the article body.
+fn find_content<'a>(doc: &'a Html, patterns: &Patterns) -> Option<ElementRef<'a>> {
+    let paragraphs = Selector::parse("p, pre, td").unwrap();
+    let mut scores: HashMap<_, (ElementRef, f64)> = HashMap::new();
+
+    for p in doc.select(&paragraphs) {
+        let unlikely = std::iter::once(p)
+            .chain(p.ancestors().filter_map(ElementRef::wrap))
+            .any(|el| patterns.is_unlikely(&el));
+        if unlikely {
+            continue;
+        }
+
+        let text = normalize_whitespace(&p.text().collect::<String>());
+        if text.len() < 25 {
+            continue;
+        }
+        let score = 1. + text.matches(',').count() as f64 + (text.len() as f64 / 100.).min(3.);
+
+        let parents = p.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (depth, parent) in parents.enumerate() {
+            let entry = scores.entry(parent.id()).or_insert_with(|| {
+                (parent, tag_weight(parent.value().name()) + patterns.class_weight(&parent))
+            });
+            entry.1 += if depth == 0 { score } else { score / 2. };
+        }
+    }
+
+    scores
+        .into_values()
+        .map(|(el, score)| (el, score * (1. - link_density(&el))))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(el, _)| el)
+}
+
+struct Converter<'a> {
+    patterns: &'a Patterns,
+    base_url: Option<Url>,
+    blocks: Vec<Block>
+}
+
+impl<'a> Converter<'a> {
+    fn resolve(&self, href: &str) -> String {
+        match &self.base_url {
+            Some(base) => base.join(href).map(|u| u.to_string()).unwrap_or_else(|_| href.to_string()),
+            None => href.to_string(),
+        }
+    }
+
+    fn push(&mut self, block: Block) {
+        let empty = match &block {
+            Block::Heading(_, inlines) | Block::Paragraph(inlines) | Block::Quote(inlines) | Block::ListItem(inlines) => {
+                inline_text(inlines).trim().is_empty()
+            },
+            Block::Code(code) => code.trim().is_empty(),
+            Block::Image { src, .. } => src.is_empty(),
+        };
+        if !empty {
+            self.blocks.push(block);
+        }
+    }
+
+    fn image(&mut self, el: &ElementRef) {
+        let src = el.value().attr("src").or_else(|| el.value().attr("data-src"));
+        if let Some(src) = src {
+            if !src.starts_with("data:") {
+                let image = Block::Image {
+                    src: self.resolve(src),
+                    alt: el.value().attr("alt").unwrap_or_default().to_string()
+                };
+                self.push(image);
+            }
+        }
+    }
+
+    /// Walks a container, turning block level children into blocks. Loose
+    /// text between them is gathered into paragraphs.
+    fn blocks(&mut self, el: ElementRef) {
+        let mut loose = Vec::new();
+
+        for child in el.children() {
+            if let Some(text) = child.value().as_text() {
+                push_text(&mut loose, Inline::Text(text.to_string()));
+                continue;
+            }
+            let child = match ElementRef::wrap(child) {
+                Some(child) => child,
+                None => continue,
+            };
+            if self.patterns.is_unlikely(&child) {
+                continue;
+            }
+
+            let tag = child.value().name();
+            if !BLOCK_TAGS.contains(&tag) {
+                self.inlines(child, &mut loose);
+                continue;
+            }
+
+            self.push(Block::Paragraph(finish_inlines(std::mem::take(&mut loose))));
+            match tag {
+                "p" => {
+                    let mut inlines = Vec::new();
+                    self.inlines(child, &mut inlines);
+                    self.push(Block::Paragraph(finish_inlines(inlines)));
+                },
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    let mut inlines = Vec::new();
+                    self.inlines(child, &mut inlines);
+                    let level = tag[1..].parse().unwrap_or(2);
+                    self.push(Block::Heading(level, finish_inlines(inlines)));
+                },
+                "blockquote" => {
+                    let mut inlines = Vec::new();
+                    self.inlines(child, &mut inlines);
+                    self.push(Block::Quote(finish_inlines(inlines)));
+                },
+                "li" => {
+                    let mut inlines = Vec::new();
+                    self.inlines(child, &mut inlines);
+                    self.push(Block::ListItem(finish_inlines(inlines)));
+                },
+                "pre" => {
+                    self.push(Block::Code(child.text().collect::<String>().trim_end().to_string()));
+                },
+                "img" => self.image(&child),
+                "table" => {
+                    if link_density(&child) < 0.5 {
+                        self.blocks(child);
+                    }
+                },
+                _ => self.blocks(child),
+            }
+        }
+
+        self.push(Block::Paragraph(finish_inlines(loose)));
+    }
+
+    /// Collects the text of an element, keeping links and emphasis. Images
+    /// found along the way become blocks of their own.
+    fn inlines(&mut self, el: ElementRef, out: &mut Vec<Inline>) {
+        for child in el.children() {
+            if let Some(text) = child.value().as_text() {
+                push_text(out, Inline::Text(text.to_string()));
+                continue;
+            }
+            let child = match ElementRef::wrap(child) {
+                Some(child) => child,
+                None => continue,
+            };
+            if self.patterns.is_unlikely(&child) {
+                continue;
+            }
+
+            let text = || child.text().collect::<String>();
+            match child.value().name() {
+                "a" => match child.value().attr("href") {
+                    Some(href) if !href.starts_with("javascript:") => {
+                        let text = normalize_whitespace(&text());
+                        if !text.is_empty() {
+                            out.push(Inline::Link { text, href: self.resolve(href) });
+                        }
+                    },
+                    _ => push_text(out, Inline::Text(text())),
+                },
+                "em" | "i" => push_text(out, Inline::Emphasis(text())),
+                "strong" | "b" => push_text(out, Inline::Strong(text())),
+                "code" => push_text(out, Inline::Code(text())),
+                "br" => push_text(out, Inline::Text(" ".to_string())),
+                "img" => self.image(&child),
+                _ => self.inlines(child, out),
+            }
+        }
+    }
+}
+
+fn push_text(out: &mut Vec<Inline>, inline: Inline) {
+    match (out.last_mut(), inline) {
+        (Some(Inline::Text(prev)), Inline::Text(text)) => prev.push_str(&text),
+        (_, inline) => out.push(inline),
+    }
+}
+
+/// Collapses whitespace inside and between inlines and trims the ends.
+fn finish_inlines(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut out: Vec<Inline> = Vec::new();
+
+    for inline in inlines {
+        let squash = |t: &str| {
+            let mut s = normalize_whitespace(t);
+            if t.starts_with(char::is_whitespace) && !s.is_empty() {
+                s.insert(0, ' ');
+            }
+            if t.ends_with(char::is_whitespace) && !s.is_empty() {
+                s.push(' ');
+            }
+            if s.is_empty() && !t.is_empty() {
+                s.push(' ');
+            }
+            s
+        };
+        let inline = match inline {
+            Inline::Text(t) => Inline::Text(squash(&t)),
+            Inline::Emphasis(t) => Inline::Emphasis(normalize_whitespace(&t)),
+            Inline::Strong(t) => Inline::Strong(normalize_whitespace(&t)),
+            Inline::Code(t) => Inline::Code(normalize_whitespace(&t)),
+            link => link,
+        };
+        match inline {
+            Inline::Text(t) if t.is_empty() => {},
+            Inline::Emphasis(t) | Inline::Strong(t) | Inline::Code(t) if t.is_empty() => {},
+            inline => out.push(inline),
+        }
+    }
+
+    if let Some(Inline::Text(t)) = out.first_mut() {
+        *t = t.trim_start().to_string();
+    }
+    if let Some(Inline::Text(t)) = out.last_mut() {
+        *t = t.trim_end().to_string();
+    }
+    out.retain(|i| !matches!(i, Inline::Text(t) if t.is_empty()));
+    out
+}
+
+/// Extracts the main content of an HTML page. Relative links and images are
+/// resolved against `base_url`.
+pub fn extract(html: &str, base_url: Option<&str>) -> Result<Document, NewsAPIError> {
+    let doc = Html::parse_document(html);
+    let patterns = Patterns::new();
+
+    let content = find_content(&doc, &patterns).ok_or(NewsAPIError::NoContent)?;
+    let mut converter = Converter {
+        patterns: &patterns,
+        base_url: base_url.and_then(|u| Url::parse(u).ok()),
+        blocks: Vec::new()
+    };
+    converter.blocks(content);
+
+    if converter.blocks.is_empty() {
+        return Err(NewsAPIError::NoContent);
+    }
+
+    Ok(Document {
+        title: extract_title(&doc),
+        blocks: converter.blocks
+    })
+}
+
+/// Downloads an article page and extracts its content.
+pub fn fetch(url: &str) -> Result<Document, NewsAPIError> {
+    let agent = ureq::AgentBuilder::new().timeout(FETCH_TIMEOUT).build();
+    let html = agent.get(url).call()?.into_string()?;
+    extract(&html, Some(url))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch_web(url: &str) -> Result<Document, NewsAPIError> {
+    let resp = reqwasm::http::Request::get(url)
+        .send()
+        .await
+        .map_err(|_| NewsAPIError::BadRequest("failed sending request."))?;
+    let html = resp
+        .text()
+        .await
+        .map_err(|_| NewsAPIError::BadRequest("failed reading the page."))?;
+    extract(&html, Some(url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEWS_SITE: &str = include_str!("../tests/fixtures/news_site.html");
+    const BLOG_POST: &str = include_str!("../tests/fixtures/blog_post.html");
+    const NO_CONTENT: &str = include_str!("../tests/fixtures/no_content.html");
+
+    #[test]
+    fn extracts_article_body_without_boilerplate() {
+        let doc = extract(NEWS_SITE, Some("https://news.example.com/world/2022/06/storm")).unwrap();
+        let text = doc.text();
+
+        assert_eq!(doc.title.as_deref(), Some("Storm batters the coast as thousands lose power"));
+        assert!(text.starts_with("A powerful storm swept along the coast on Tuesday"));
+        assert!(text.contains("Emergency crews worked through the night"));
+        assert!(!text.contains("Subscribe to our newsletter"));
+        assert!(!text.contains("Most read"));
+        assert!(!text.contains("Sign in"));
+        assert!(!text.contains("Leave a comment"));
+
+        assert!(doc.blocks.contains(&Block::Heading(2, vec![Inline::Text("What happens next".to_string())])));
+        assert!(doc.blocks.contains(&Block::Image {
+            src: "https://news.example.com/img/storm.jpg".to_string(),
+            alt: "Waves crash over the sea wall".to_string()
+        }));
+        assert!(doc.blocks.contains(&Block::Quote(vec![
+            Inline::Text("\"We have never seen anything like it,\" said one resident.".to_string())
+        ])));
+    }
+
+    #[test]
+    fn keeps_links_and_emphasis() {
+        let doc = extract(BLOG_POST, Some("https://blog.example.org/posts/borrowck/")).unwrap();
+
+        assert_eq!(doc.title.as_deref(), Some("Understanding the borrow checker"));
+        let first = match &doc.blocks[0] {
+            Block::Paragraph(inlines) => inlines.clone(),
+            other => panic!("expected a paragraph, got {:?}", other),
+        };
+        assert_eq!(first, vec![
+            Inline::Text("The ".to_string()),
+            Inline::Strong("borrow checker".to_string()),
+            Inline::Text(" is the part of the compiler that enforces the ".to_string()),
+            Inline::Link {
+                text: "ownership rules".to_string(),
+                href: "https://blog.example.org/posts/ownership/".to_string()
+            },
+            Inline::Text(", and it is what makes Rust feel ".to_string()),
+            Inline::Emphasis("different".to_string()),
+            Inline::Text(" at first.".to_string()),
+        ]);
+        assert!(doc.blocks.iter().any(|b| matches!(b, Block::Code(c) if c.contains("let r = &s;"))));
+        assert!(doc.blocks.iter().any(|b| matches!(b, Block::ListItem(i) if inline_text(i) == "Each value has a single owner.")));
+        assert!(!doc.text().contains("Related posts"));
+    }
+
+    #[test]
+    fn converts_to_markdown() {
+        let doc = extract(BLOG_POST, Some("https://blog.example.org/posts/borrowck/")).unwrap();
+        let markdown = doc.to_markdown();
+
+        assert!(markdown.starts_with("# Understanding the borrow checker\n\nThe **borrow checker** is the part"));
+        assert!(markdown.contains("[ownership rules](https://blog.example.org/posts/ownership/)"));
+        assert!(markdown.contains("feel *different* at first."));
+        assert!(markdown.contains("```\nlet s = String::from(\"hello\");\nlet r = &s;"));
+        assert!(markdown.contains("\n\n* Each value has a single owner.\n\n"));
+    }
+
+    #[test]
+    fn pages_without_prose_have_no_content() {
+        assert!(matches!(extract(NO_CONTENT, None), Err(NewsAPIError::NoContent)));
+    }
+}
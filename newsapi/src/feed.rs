@@ -0,0 +1,284 @@
+//! Syndication feeds: a set of articles as an RSS 2.0 or an Atom 1.0
+//! document, for feed readers.
+
+use chrono::{DateTime, Utc};
+use crate::export::{escape, timestamp, Item};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Rss,
+    Atom
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Self::Rss, Self::Atom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rss => "RSS",
+            Self::Atom => "Atom",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Rss => "rss",
+            Self::Atom => "atom",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Rss => "application/rss+xml",
+            Self::Atom => "application/atom+xml",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rss" => Ok(Self::Rss),
+            "atom" => Ok(Self::Atom),
+            _ => Err(format!("unknown feed format {:?}, pick one of rss, atom", s)),
+        }
+    }
+}
+
+pub struct Feed {
+    title: String,
+    description: String,
+    /// The page the feed is about.
+    link: String,
+    /// Where the feed itself can be fetched from, if it's published.
+    self_link: Option<String>,
+    /// Unix time of the last change.
+    updated: u64,
+    items: Vec<Item>
+}
+
+impl Feed {
+    pub fn new(title: &str, items: Vec<Item>) -> Feed {
+        Feed {
+            title: title.to_string(),
+            description: title.to_string(),
+            link: "https://newsapi.org/".to_string(),
+            self_link: None,
+            updated: Utc::now().timestamp() as u64,
+            items
+        }
+    }
+
+    pub fn description(&mut self, description: &str) -> &mut Feed {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn link(&mut self, link: &str) -> &mut Feed {
+        self.link = link.to_string();
+        self
+    }
+
+    pub fn self_link(&mut self, self_link: Option<&str>) -> &mut Feed {
+        self.self_link = self_link.map(|l| l.to_string());
+        self
+    }
+
+    pub fn updated(&mut self, updated: u64) -> &mut Feed {
+        self.updated = updated;
+        self
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Rss => self.to_rss(),
+            Format::Atom => self.to_atom(),
+        }
+    }
+
+    pub fn to_rss(&self) -> String {
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             <channel>\n"
+        );
+        out.push_str(&format!("<title>{}</title>\n", escape(&self.title)));
+        out.push_str(&format!("<link>{}</link>\n", escape(&self.link)));
+        out.push_str(&format!("<description>{}</description>\n", escape(&self.description)));
+        out.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", rfc822(self.updated)));
+        if let Some(self_link) = &self.self_link {
+            out.push_str(&format!(
+                "<atom:link href=\"{}\" rel=\"self\" type=\"{}\"/>\n",
+                escape(self_link),
+                Format::Rss.content_type()
+            ));
+        }
+
+        for item in &self.items {
+            out.push_str("<item>\n");
+            out.push_str(&format!("<title>{}</title>\n", escape(&item.title)));
+            out.push_str(&format!("<link>{}</link>\n", escape(&item.url)));
+            out.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", escape(&item.url)));
+            if let Some(published) = item.published_at.as_deref().and_then(parse_rfc3339) {
+                out.push_str(&format!("<pubDate>{}</pubDate>\n", rfc822(published)));
+            }
+            // RSS wants an email address in <author>, Dublin Core takes a name.
+            if let Some(author) = item.author.as_ref().filter(|a| !a.trim().is_empty()) {
+                out.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(author.trim())));
+            }
+            if let Some(category) = &item.category {
+                out.push_str(&format!("<category>{}</category>\n", escape(category)));
+            }
+            if let Some(description) = item.description.as_ref().filter(|d| !d.trim().is_empty()) {
+                out.push_str(&format!("<description>{}</description>\n", escape(description.trim())));
+            }
+            out.push_str("</item>\n");
+        }
+        out.push_str("</channel>\n</rss>\n");
+        out
+    }
+
+    pub fn to_atom(&self) -> String {
+        let updated = timestamp(self.updated);
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        out.push_str(&format!("<id>{}</id>\n", escape(self.self_link.as_ref().unwrap_or(&self.link))));
+        out.push_str(&format!("<title>{}</title>\n", escape(&self.title)));
+        out.push_str(&format!("<subtitle>{}</subtitle>\n", escape(&self.description)));
+        out.push_str(&format!("<updated>{}</updated>\n", updated));
+        out.push_str(&format!("<link rel=\"alternate\" href=\"{}\"/>\n", escape(&self.link)));
+        if let Some(self_link) = &self.self_link {
+            out.push_str(&format!(
+                "<link rel=\"self\" type=\"{}\" href=\"{}\"/>\n",
+                Format::Atom.content_type(),
+                escape(self_link)
+            ));
+        }
+
+        for item in &self.items {
+            let published = item.published_at.as_deref().and_then(parse_rfc3339);
+            out.push_str("<entry>\n");
+            out.push_str(&format!("<id>{}</id>\n", escape(&item.url)));
+            out.push_str(&format!("<title>{}</title>\n", escape(&item.title)));
+            out.push_str(&format!("<link rel=\"alternate\" href=\"{}\"/>\n", escape(&item.url)));
+            out.push_str(&format!("<updated>{}</updated>\n", published.map(timestamp).unwrap_or_else(|| updated.clone())));
+            if let Some(published) = published {
+                out.push_str(&format!("<published>{}</published>\n", timestamp(published)));
+            }
+            // Atom needs an author for every entry, the source will do.
+            let author = [item.author.as_deref(), Some(item.source.as_str())]
+                .into_iter()
+                .flatten()
+                .map(|a| a.trim())
+                .find(|a| !a.is_empty())
+                .unwrap_or("unknown");
+            out.push_str(&format!("<author><name>{}</name></author>\n", escape(author)));
+            if let Some(category) = &item.category {
+                out.push_str(&format!("<category term=\"{}\"/>\n", escape(category)));
+            }
+            if let Some(description) = item.description.as_ref().filter(|d| !d.trim().is_empty()) {
+                out.push_str(&format!("<summary type=\"text\">{}</summary>\n", escape(description.trim())));
+            }
+            out.push_str("</entry>\n");
+        }
+        out.push_str("</feed>\n");
+        out
+    }
+}
+
+/// Unix time of a date like `2022-06-10T09:00:00Z` or
+/// `2022-06-10T11:00:00.123+02:00`, as newsapi gives them.
+pub fn parse_rfc3339(date: &str) -> Option<u64> {
+    let date = DateTime::parse_from_rfc3339(date).ok()?;
+    u64::try_from(date.timestamp()).ok()
+}
+
+/// `secs` since the Unix epoch as `Fri, 10 Jun 2022 09:00:00 +0000`, the
+/// date format of RSS.
+pub fn rfc822(secs: u64) -> String {
+    let date = i64::try_from(secs).ok().and_then(|secs| DateTime::from_timestamp(secs, 0));
+    date.map(|date| date.to_rfc2822()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> Feed {
+        let items = vec![
+            Item {
+                title: "Rates rise & markets fall".to_string(),
+                url: "https://wire.example/rates?id=1&lang=en".to_string(),
+                source: "Wire".to_string(),
+                category: Some("Business".to_string()),
+                author: Some("Ada Lovelace".to_string()),
+                published_at: Some("2022-06-10T11:00:00+02:00".to_string()),
+                description: Some("The <b>Fed</b> moved again.".to_string()),
+                ..Item::default()
+            },
+            Item {
+                title: "Cup final".to_string(),
+                url: "https://gazette.example/cup".to_string(),
+                source: "Gazette".to_string(),
+                ..Item::default()
+            },
+        ];
+        let mut feed = Feed::new("Headlines: Business", items);
+        feed.self_link(Some("http://localhost:8080/feed.atom?category=business")).updated(1_654_858_800);
+        feed
+    }
+
+    #[test]
+    fn converts_dates() {
+        assert_eq!(parse_rfc3339("2022-06-10T09:00:00Z"), Some(1_654_851_600));
+        assert_eq!(parse_rfc3339("2022-06-10T11:00:00.5+02:00"), Some(1_654_851_600));
+        assert_eq!(parse_rfc3339("2022-06-10T04:30:00-04:30"), Some(1_654_851_600));
+        assert_eq!(parse_rfc3339("1972-02-29T00:00:00Z"), Some(68_169_600));
+        assert_eq!(parse_rfc3339("yesterday"), None);
+        assert_eq!(rfc822(1_654_851_600), "Fri, 10 Jun 2022 09:00:00 +0000");
+        assert_eq!(rfc822(0), "Thu, 1 Jan 1970 00:00:00 +0000");
+    }
+
+    #[test]
+    fn renders_rss() {
+        let rss = feed().to_rss();
+        assert!(rss.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\""));
+        assert!(rss.contains("<title>Headlines: Business</title>\n<link>https://newsapi.org/</link>"));
+        assert!(rss.contains("<lastBuildDate>Fri, 10 Jun 2022 11:00:00 +0000</lastBuildDate>"));
+        assert!(rss.contains("<atom:link href=\"http://localhost:8080/feed.atom?category=business\" rel=\"self\""));
+        assert!(rss.contains(
+            "<item>\n<title>Rates rise &amp; markets fall</title>\n\
+             <link>https://wire.example/rates?id=1&amp;lang=en</link>\n\
+             <guid isPermaLink=\"true\">https://wire.example/rates?id=1&amp;lang=en</guid>\n\
+             <pubDate>Fri, 10 Jun 2022 09:00:00 +0000</pubDate>\n\
+             <dc:creator>Ada Lovelace</dc:creator>\n\
+             <category>Business</category>\n\
+             <description>The &lt;b&gt;Fed&lt;/b&gt; moved again.</description>\n</item>"
+        ));
+        assert!(rss.contains("<item>\n<title>Cup final</title>\n<link>https://gazette.example/cup</link>\n<guid isPermaLink=\"true\">https://gazette.example/cup</guid>\n</item>"));
+        assert!(rss.ends_with("</channel>\n</rss>\n"));
+    }
+
+    #[test]
+    fn renders_atom() {
+        let atom = feed().to_atom();
+        assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n<id>http://localhost:8080/feed.atom?category=business</id>"));
+        assert!(atom.contains("<updated>2022-06-10T11:00:00Z</updated>"));
+        assert!(atom.contains(
+            "<entry>\n<id>https://wire.example/rates?id=1&amp;lang=en</id>\n\
+             <title>Rates rise &amp; markets fall</title>\n\
+             <link rel=\"alternate\" href=\"https://wire.example/rates?id=1&amp;lang=en\"/>\n\
+             <updated>2022-06-10T09:00:00Z</updated>\n<published>2022-06-10T09:00:00Z</published>\n\
+             <author><name>Ada Lovelace</name></author>\n<category term=\"Business\"/>\n\
+             <summary type=\"text\">The &lt;b&gt;Fed&lt;/b&gt; moved again.</summary>\n</entry>"
+        ));
+        // Without a date the entry takes the feed's, and the source stands in
+        // for the author.
+        assert!(atom.contains("<updated>2022-06-10T11:00:00Z</updated>\n<author><name>Gazette</name></author>"));
+        assert!(atom.ends_with("</entry>\n</feed>\n"));
+    }
+}
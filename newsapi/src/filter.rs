@@ -0,0 +1,331 @@
+use crate::sentiment::Tone;
+use crate::{Article, NewsAPIError};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Include,
+    Exclude
+}
+
+/// What a rule looks at. Keywords and regexes are matched case-insensitively
+/// against the title and description, single word keywords as whole words
+/// ("ai" doesn't match "said") and phrases as they are. Sources against the newsapi source id
+/// and domains against the host of the article url (subdomains included).
+/// Sentiment matches articles whose title and description have that tone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Matcher {
+    Keyword(String),
+    Regex(String),
+    Source(String),
+    Domain(String),
+    Sentiment(Tone)
+}
+
+/// A single filter rule as written in the config file:
+///
+/// ```toml
+/// [[filters]]
+/// name = "no gossip"
+/// action = "exclude"
+/// keyword = "kardashian"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterRule {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub action: Action,
+    #[serde(flatten)]
+    pub matcher: Matcher
+}
+
+impl FilterRule {
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.to_string();
+        }
+
+        let action = match self.action {
+            Action::Include => "include",
+            Action::Exclude => "exclude",
+        };
+        let (kind, value) = match &self.matcher {
+            Matcher::Keyword(v) => ("keyword", v.as_str()),
+            Matcher::Regex(v) => ("regex", v.as_str()),
+            Matcher::Source(v) => ("source", v.as_str()),
+            Matcher::Domain(v) => ("domain", v.as_str()),
+            Matcher::Sentiment(tone) => ("sentiment", tone.name()),
+        };
+        format!("{} {} \"{}\"", action, kind, value)
+    }
+}
+
+enum CompiledMatcher {
+    Keyword(String),
+    Regex(Regex),
+    Source(String),
+    Domain(String),
+    Sentiment(Tone)
+}
+
+impl CompiledMatcher {
+    fn matches(&self, article: &Article) -> bool {
+        match self {
+            Self::Keyword(keyword) => {
+                let text = article_text(article).to_lowercase();
+                match keyword.contains(' ') {
+                    true => text.contains(keyword),
+                    false => text.split(|c: char| !c.is_alphanumeric()).any(|w| w == keyword),
+                }
+            },
+            Self::Regex(re) => re.is_match(&article_text(article)),
+            Self::Source(id) => article
+                .source()
+                .id()
+                .map(|s| s.eq_ignore_ascii_case(id))
+                .unwrap_or(false),
+            Self::Domain(domain) => {
+                match Url::parse(article.url()).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) {
+                    Some(host) => host == *domain || host.ends_with(&format!(".{}", domain)),
+                    None => false
+                }
+            },
+            Self::Sentiment(tone) => article.tone() == *tone,
+        }
+    }
+}
+
+fn article_text(article: &Article) -> String {
+    match article.description() {
+        Some(description) => format!("{}\n{}", article.title(), description),
+        None => article.title().to_string(),
+    }
+}
+
+struct CompiledRule {
+    label: String,
+    action: Action,
+    matcher: CompiledMatcher
+}
+
+/// Number of articles hidden by each rule, in the order the rules were
+/// configured. Articles dropped because no include rule matched them are
+/// counted separately in `unmatched`.
+#[derive(Debug, Default, Clone)]
+pub struct FilterReport {
+    pub hidden: Vec<(String, usize)>,
+    pub unmatched: usize
+}
+
+impl FilterReport {
+    pub fn total(&self) -> usize {
+        self.hidden.iter().map(|(_, n)| n).sum::<usize>() + self.unmatched
+    }
+
+    /// Adds the counts of another run, e.g. over the next page of results.
+    pub fn merge(&mut self, other: &FilterReport) {
+        for (rule, hidden) in &other.hidden {
+            match self.hidden.iter_mut().find(|(r, _)| r == rule) {
+                Some((_, n)) => *n += hidden,
+                None => self.hidden.push((rule.clone(), *hidden)),
+            }
+        }
+        self.unmatched += other.unmatched;
+    }
+}
+
+pub struct FilterEngine {
+    rules: Vec<CompiledRule>,
+    has_includes: bool
+}
+
+impl FilterEngine {
+    pub fn new(rules: &[FilterRule]) -> Result<FilterEngine, NewsAPIError> {
+        let mut compiled = Vec::with_capacity(rules.len());
+
+        for rule in rules {
+            let matcher = match &rule.matcher {
+                Matcher::Keyword(k) => CompiledMatcher::Keyword(k.to_lowercase()),
+                Matcher::Regex(r) => CompiledMatcher::Regex(
+                    RegexBuilder::new(r).case_insensitive(true).build()?
+                ),
+                Matcher::Source(s) => CompiledMatcher::Source(s.to_string()),
+                Matcher::Domain(d) => CompiledMatcher::Domain(d.trim_start_matches('.').to_lowercase()),
+                Matcher::Sentiment(tone) => CompiledMatcher::Sentiment(*tone),
+            };
+            compiled.push(CompiledRule {
+                label: rule.label(),
+                action: rule.action,
+                matcher
+            });
+        }
+
+        Ok(FilterEngine {
+            has_includes: compiled.iter().any(|r| r.action == Action::Include),
+            rules: compiled
+        })
+    }
+
+    /// Returns `None` if the article should be shown, otherwise the index of
+    /// the exclude rule that hid it (`Some(None)` if no include rule matched).
+    fn verdict(&self, article: &Article) -> Option<Option<usize>> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.action == Action::Exclude && rule.matcher.matches(article) {
+                return Some(Some(i));
+            }
+        }
+
+        if self.has_includes {
+            let included = self.rules
+                .iter()
+                .any(|r| r.action == Action::Include && r.matcher.matches(article));
+            if !included {
+                return Some(None);
+            }
+        }
+
+        None
+    }
+
+    pub fn is_visible(&self, article: &Article) -> bool {
+        self.verdict(article).is_none()
+    }
+
+    /// Drops every hidden article from `articles` and reports which rules
+    /// were responsible.
+    pub fn apply(&self, articles: &mut Vec<Article>) -> FilterReport {
+        let mut counts = vec![0; self.rules.len()];
+        let mut unmatched = 0;
+
+        articles.retain(|a| match self.verdict(a) {
+            None => true,
+            Some(Some(i)) => {
+                counts[i] += 1;
+                false
+            },
+            Some(None) => {
+                unmatched += 1;
+                false
+            }
+        });
+
+        FilterReport {
+            hidden: self.rules
+                .iter()
+                .zip(counts)
+                .filter(|(r, _)| r.action == Action::Exclude)
+                .map(|(r, n)| (r.label.to_string(), n))
+                .collect(),
+            unmatched
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(source: &str, title: &str, url: &str) -> Article {
+        serde_json::from_value(serde_json::json!({
+            "source": { "id": source, "name": source },
+            "title": title,
+            "url": url,
+            "description": null
+        })).unwrap()
+    }
+
+    fn rules(specs: &[(Action, Matcher)]) -> Vec<FilterRule> {
+        specs
+            .iter()
+            .map(|(action, matcher)| FilterRule { name: None, action: *action, matcher: matcher.clone() })
+            .collect()
+    }
+
+    #[test]
+    fn exclude_rules_hide_and_count() {
+        let engine = FilterEngine::new(&rules(&[
+            (Action::Exclude, Matcher::Keyword("Kardashian".into())),
+            (Action::Exclude, Matcher::Regex(r"\bnfl\b".into())),
+            (Action::Exclude, Matcher::Domain("gossip.com".into())),
+        ])).unwrap();
+
+        let mut articles = vec![
+            article("bbc-news", "Kardashian spotted", "https://bbc.co.uk/1"),
+            article("espn", "NFL week 3 recap", "https://espn.com/2"),
+            article("cnn", "Markets rally", "https://edition.gossip.com/3"),
+            article("cnn", "Elections update", "https://cnn.com/4"),
+        ];
+        let report = engine.apply(&mut articles);
+
+        assert_eq!(articles.len(), 1);
+        assert_eq!(articles[0].title(), "Elections update");
+        assert_eq!(report.hidden.iter().map(|(_, n)| *n).collect::<Vec<_>>(), vec![1, 1, 1]);
+        assert_eq!(report.total(), 3);
+    }
+
+    #[test]
+    fn keywords_match_whole_words() {
+        let engine = FilterEngine::new(&rules(&[
+            (Action::Exclude, Matcher::Keyword("NFL".into())),
+            (Action::Exclude, Matcher::Keyword("ai".into())),
+            (Action::Exclude, Matcher::Keyword("interest rates".into())),
+        ])).unwrap();
+
+        let mut articles = vec![
+            article("espn", "NFL: week 3 recap", "https://espn.com/1"),
+            article("cnn", "Conflict in the region, officials said", "https://cnn.com/2"),
+            article("cnn", "Rain expected all weekend", "https://cnn.com/3"),
+            article("cnn", "New AI model released", "https://cnn.com/4"),
+            article("bbc-news", "Fed holds interest rates", "https://bbc.co.uk/5"),
+        ];
+        engine.apply(&mut articles);
+
+        let titles: Vec<&str> = articles.iter().map(|a| a.title()).collect();
+        assert_eq!(titles, vec!["Conflict in the region, officials said", "Rain expected all weekend"]);
+    }
+
+    #[test]
+    fn include_rules_keep_only_matches() {
+        let engine = FilterEngine::new(&rules(&[
+            (Action::Include, Matcher::Source("bbc-news".into())),
+            (Action::Exclude, Matcher::Keyword("football".into())),
+        ])).unwrap();
+
+        let mut articles = vec![
+            article("bbc-news", "Budget announced", "https://bbc.co.uk/1"),
+            article("bbc-news", "Football scores", "https://bbc.co.uk/2"),
+            article("cnn", "Budget announced", "https://cnn.com/3"),
+        ];
+        let report = engine.apply(&mut articles);
+
+        assert_eq!(articles.len(), 1);
+        assert_eq!(report.hidden, vec![("exclude keyword \"football\"".to_string(), 1)]);
+        assert_eq!(report.unmatched, 1);
+    }
+
+    #[test]
+    fn sentiment_rules_match_the_tone() {
+        let engine = FilterEngine::new(&rules(&[
+            (Action::Exclude, Matcher::Sentiment(Tone::Negative)),
+        ])).unwrap();
+
+        let mut articles = vec![
+            article("bbc-news", "Storm kills three", "https://bbc.co.uk/1"),
+            article("bbc-news", "Rescue team praised", "https://bbc.co.uk/2"),
+            article("bbc-news", "Council meets on Tuesday", "https://bbc.co.uk/3"),
+        ];
+        let report = engine.apply(&mut articles);
+
+        assert_eq!(articles.len(), 2);
+        assert_eq!(report.hidden, vec![("exclude sentiment \"negative\"".to_string(), 1)]);
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(FilterEngine::new(&rules(&[(Action::Exclude, Matcher::Regex("(".into()))])).is_err());
+    }
+}
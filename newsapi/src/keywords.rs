@@ -0,0 +1,223 @@
+//! What the headlines are about: the terms of a day's articles, weighed by
+//! TF-IDF against the days before. A term is a word or two adjacent words,
+//! so that "interest rates" trends as one.
+
+use crate::summary::STOPWORDS;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::RangeInclusive;
+
+/// Words that say little about the story, on top of the usual stopwords.
+const FILLER: &[&str] = &[
+    "says", "new", "news", "just", "now", "why", "amid", "get", "gets", "may",
+    "like", "year", "years", "day", "days", "week", "time", "back", "first",
+    "two", "three", "make", "makes", "here", "live", "update", "updates",
+    "video", "watch", "report", "reports", "could", "should", "after", "still"
+];
+
+/// Articles a term needs to be in on the day to trend.
+const MIN_ARTICLES: usize = 2;
+/// Share of a word's articles a longer term needs to stand in for the word.
+const SUBSUMED: f64 = 0.8;
+/// Characters two-word terms don't span.
+const BREAKS: &[char] = &['.', ',', ':', ';', '!', '?', '"', '(', ')', '|', '–', '—', '“', '”'];
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The terms of a text, lowercased. Words are kept if they're longer than
+/// two letters or written in capitals ("EU", "AI"), and stopwords and numbers
+/// are dropped. Punctuation and dropped words break up two-word terms.
+pub fn terms(text: &str) -> HashSet<String> {
+    let mut terms = HashSet::new();
+    for segment in text.split(BREAKS) {
+        let mut previous: Option<String> = None;
+        for word in segment.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '’') {
+            let word = word.trim_matches(|c| c == '\'' || c == '’');
+            let lower = word.to_lowercase();
+            let keep = (word.chars().count() > 2 || (word.chars().count() == 2 && word.chars().all(char::is_uppercase)))
+                && !STOPWORDS.contains(&lower.as_str())
+                && !FILLER.contains(&lower.as_str())
+                && !lower.chars().all(|c| c.is_numeric());
+            if !keep {
+                previous = None;
+                continue;
+            }
+            if let Some(previous) = previous {
+                terms.insert(format!("{} {}", previous, lower));
+            }
+            terms.insert(lower.to_string());
+            previous = Some(lower);
+        }
+    }
+    terms
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Day {
+    documents: usize,
+    /// Documents each term is in.
+    terms: HashMap<String, usize>,
+    urls: HashSet<String>
+}
+
+/// A term of the day, with the number of articles it's in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trend {
+    pub term: String,
+    pub articles: usize,
+    pub score: f64
+}
+
+/// How many articles had each term, day by day. Days are numbered by the
+/// caller, one apart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Corpus {
+    days: BTreeMap<i64, Day>
+}
+
+impl Corpus {
+    /// Adds the article at `url` to `day`, unless it's there already.
+    /// Returns whether it was added.
+    pub fn add(&mut self, day: i64, url: &str, text: &str) -> bool {
+        if self.days.values().any(|d| d.urls.contains(url)) {
+            return false;
+        }
+        let entry = self.days.entry(day).or_default();
+        entry.documents += 1;
+        entry.urls.insert(url.to_string());
+        for term in terms(text) {
+            *entry.terms.entry(term).or_insert(0) += 1;
+        }
+        true
+    }
+
+    /// Forgets the days before `day`.
+    pub fn forget_before(&mut self, day: i64) {
+        self.days = self.days.split_off(&day);
+    }
+
+    pub fn documents(&self, day: i64) -> usize {
+        self.days.get(&day).map(|d| d.documents).unwrap_or_default()
+    }
+
+    /// The `n` terms of `day` that stand out the most from the other days.
+    /// A word is left out when a two-word term stands for it, and a term is
+    /// left out when it shares a word with one that ranks higher.
+    pub fn trending(&self, day: i64, n: usize) -> Vec<Trend> {
+        let today = match self.days.get(&day) {
+            Some(today) => today,
+            None => return vec![],
+        };
+        let mut background = 0;
+        let mut background_terms: HashMap<&str, usize> = HashMap::new();
+        for (_, other) in self.days.iter().filter(|(d, _)| **d != day) {
+            background += other.documents;
+            for (term, count) in &other.terms {
+                *background_terms.entry(term).or_insert(0) += count;
+            }
+        }
+
+        let subsumed: HashSet<&str> = today
+            .terms
+            .iter()
+            .filter(|(term, _)| term.contains(' '))
+            .flat_map(|(term, &count)| {
+                term.split(' ').filter(move |word| count as f64 >= today.terms.get(*word).copied().unwrap_or(0) as f64 * SUBSUMED)
+            })
+            .collect();
+
+        let mut candidates: Vec<Trend> = today
+            .terms
+            .iter()
+            .filter(|(term, &count)| count >= MIN_ARTICLES && !subsumed.contains(term.as_str()))
+            .map(|(term, &count)| {
+                let seen = background_terms.get(term.as_str()).copied().unwrap_or(0);
+                let idf = ((1 + background) as f64 / (1 + seen) as f64).ln() + 1.0;
+                Trend { term: term.to_string(), articles: count, score: count as f64 * idf }
+            })
+            .collect();
+        // Ties go to the longer term, then alphabetically, so the result
+        // doesn't depend on the order of the map.
+        candidates.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.term.len().cmp(&a.term.len()))
+                .then(a.term.cmp(&b.term))
+        });
+
+        let mut trends: Vec<Trend> = vec![];
+        for candidate in candidates {
+            if trends.len() == n {
+                break;
+            }
+            let overlaps = trends.iter().any(|t| t.term.split(' ').any(|w| candidate.term.split(' ').any(|c| c == w)));
+            if !overlaps {
+                trends.push(candidate);
+            }
+        }
+        trends
+    }
+
+    /// Articles with `term`, for each day of `days`.
+    pub fn history(&self, term: &str, days: RangeInclusive<i64>) -> Vec<usize> {
+        days.map(|day| self.days.get(&day).and_then(|d| d.terms.get(term)).copied().unwrap_or_default()).collect()
+    }
+}
+
+/// `counts` as bars, the highest one full and zeros blank.
+pub fn sparkline(counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or_default();
+    counts
+        .iter()
+        .map(|&count| match (count, max) {
+            (0, _) => ' ',
+            (count, max) => BARS[count * (BARS.len() - 1) / max],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_words_and_pairs() {
+        let mut terms: Vec<String> = terms("EU leaders' summit: Interest rates rise, says the ECB's chief in 2024").into_iter().collect();
+        terms.sort();
+        assert_eq!(terms, vec![
+            "chief", "ecb's", "ecb's chief", "eu", "eu leaders", "interest", "interest rates", "leaders", "leaders summit",
+            "rates", "rates rise", "rise", "summit"
+        ]);
+    }
+
+    #[test]
+    fn trends_stand_out_from_the_days_before() {
+        let mut corpus = Corpus::default();
+        let mut n = 0;
+        let mut add = |corpus: &mut Corpus, day, text: &str| {
+            n += 1;
+            corpus.add(day, &format!("https://a.example/{}", n), text)
+        };
+        for _ in 0..3 {
+            add(&mut corpus, 1, "Weather: storm over the coast");
+        }
+        add(&mut corpus, 2, "Weather: sunny spells");
+        add(&mut corpus, 3, "Weather: rain later");
+        add(&mut corpus, 3, "Fed raises interest rates");
+        add(&mut corpus, 3, "Interest rates hit mortgages");
+        add(&mut corpus, 3, "Interest rates: what now");
+        add(&mut corpus, 3, "Weather: cloudy");
+        assert!(!corpus.add(3, "https://a.example/1", "Weather again"));
+
+        let trends = corpus.trending(3, 5);
+        let terms: Vec<(&str, usize)> = trends.iter().map(|t| (t.term.as_str(), t.articles)).collect();
+        assert_eq!(terms, vec![("interest rates", 3), ("weather", 2)]);
+        assert!(corpus.trending(4, 5).is_empty());
+
+        assert_eq!(corpus.history("weather", 0..=3), vec![0, 3, 1, 2]);
+        assert_eq!(sparkline(&corpus.history("weather", 0..=3)), " █▃▅");
+        corpus.forget_before(3);
+        assert_eq!(corpus.history("weather", 0..=3), vec![0, 0, 0, 2]);
+        assert_eq!(corpus.documents(3), 5);
+    }
+}
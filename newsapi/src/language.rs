@@ -0,0 +1,396 @@
+//! Language detection for headlines, done locally, and translation through
+//! a pluggable `Translator`.
+//!
+//! Scripts used by a single language give it away directly. Latin-script
+//! languages are told apart by their most common words and by letters only
+//! some of them use.
+
+use crate::{Article, NewsAPIError};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The most common words of each Latin-script language, by ISO 639-1 code.
+/// A word several languages share, like "de" or "som", counts for each of
+/// them: the words and letters only one uses decide, and a tie detects
+/// nothing.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "of", "to", "in", "is", "for", "on", "with", "as", "at", "by", "from", "that", "this", "it", "are", "was", "be", "has", "have", "after", "over", "new", "says", "will", "its", "an", "not", "but", "how", "why", "what", "who"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "mit", "den", "dem", "ein", "eine", "einen", "für", "auf", "von", "zu", "im", "sich", "auch", "nach", "bei", "wie", "wird", "werden", "über", "aus", "vor", "sind", "hat", "noch"]),
+    ("fr", &["le", "la", "les", "des", "et", "est", "une", "un", "du", "pour", "dans", "sur", "au", "aux", "par", "avec", "qui", "que", "pas", "plus", "ce", "cette", "son", "sa", "ses", "été", "sont", "après", "mais"]),
+    ("es", &["el", "los", "las", "del", "y", "es", "una", "un", "por", "para", "con", "que", "en", "se", "lo", "su", "al", "más", "como", "pero", "tras", "según", "sus", "ha", "sobre", "entre", "este", "esta"]),
+    ("it", &["il", "lo", "gli", "della", "delle", "dei", "di", "che", "è", "per", "con", "una", "un", "non", "sono", "nel", "nella", "alla", "al", "dal", "più", "anche", "come", "ma", "su", "tra", "dopo"]),
+    ("pt", &["o", "os", "as", "do", "da", "dos", "das", "e", "é", "um", "uma", "para", "com", "não", "em", "no", "na", "nos", "que", "por", "mais", "ao", "pelo", "pela", "após", "sobre", "diz"]),
+    ("nl", &["de", "het", "een", "en", "van", "is", "dat", "niet", "op", "voor", "met", "zijn", "aan", "bij", "ook", "naar", "wordt", "uit", "dit", "die", "nog", "meer", "over", "maar"]),
+    ("sv", &["och", "att", "är", "inte", "för", "till", "ett", "från", "efter", "nya", "vid", "också", "sig", "hur", "säger", "som", "det"]),
+    ("no", &["og", "er", "ikke", "til", "å", "fra", "etter", "nye", "ved", "også", "seg", "hvordan", "sier", "som", "det"]),
+    ("pl", &["i", "w", "z", "na", "się", "nie", "do", "to", "że", "jest", "od", "po", "dla", "jak", "ale", "za", "przez", "już", "czy", "są", "oraz", "który", "roku"]),
+    ("cs", &["a", "v", "se", "na", "je", "že", "s", "z", "do", "k", "to", "jak", "ale", "pro", "po", "jsou", "by", "který", "podle", "byl", "už", "ani", "před"]),
+    ("tr", &["ve", "bir", "bu", "da", "de", "için", "ile", "çok", "daha", "olarak", "gibi", "mi", "ne", "sonra", "ama", "kadar", "yeni", "olan", "değil"]),
+    ("ro", &["și", "în", "de", "la", "cu", "pe", "din", "care", "nu", "este", "o", "un", "pentru", "mai", "a", "sunt", "după", "să", "ce", "ca", "sau"]),
+    ("hu", &["a", "az", "és", "hogy", "nem", "is", "egy", "meg", "van", "volt", "már", "csak", "mint", "de", "el", "ki", "be", "után", "szerint", "lesz"]),
+    ("id", &["yang", "dan", "di", "ke", "dari", "ini", "itu", "dengan", "untuk", "tidak", "akan", "pada", "ada", "juga", "dalam", "oleh", "atau", "karena", "bisa", "setelah"]),
+];
+
+/// Letters that point to one or a few Latin-script languages.
+const LETTERS: &[(char, &[&str])] = &[
+    ('ß', &["de"]),
+    ('ä', &["de", "sv"]),
+    ('ö', &["de", "sv", "tr", "hu"]),
+    ('ü', &["de", "tr", "hu"]),
+    ('ñ', &["es"]),
+    ('á', &["es", "pt", "cs", "hu"]),
+    ('í', &["es", "pt", "cs", "hu"]),
+    ('ó', &["es", "pt", "pl", "hu"]),
+    ('ú', &["es", "pt", "cs", "hu"]),
+    ('ã', &["pt"]),
+    ('õ', &["pt"]),
+    ('ç', &["fr", "pt", "tr"]),
+    ('ê', &["fr", "pt"]),
+    ('ô', &["fr", "pt"]),
+    ('à', &["fr", "it", "pt"]),
+    ('è', &["fr", "it"]),
+    ('é', &["fr", "it", "pt", "cs", "hu"]),
+    ('ì', &["it"]),
+    ('ò', &["it"]),
+    ('ù', &["fr", "it"]),
+    ('œ', &["fr"]),
+    ('å', &["sv", "no"]),
+    ('ø', &["no"]),
+    ('æ', &["no"]),
+    ('ł', &["pl"]),
+    ('ą', &["pl"]),
+    ('ę', &["pl"]),
+    ('ś', &["pl"]),
+    ('ź', &["pl"]),
+    ('ż', &["pl"]),
+    ('ń', &["pl"]),
+    ('ř', &["cs"]),
+    ('ů', &["cs"]),
+    ('ě', &["cs"]),
+    ('č', &["cs"]),
+    ('š', &["cs"]),
+    ('ğ', &["tr"]),
+    ('ı', &["tr"]),
+    ('ş', &["tr", "ro"]),
+    ('ă', &["ro"]),
+    ('ș', &["ro"]),
+    ('ț', &["ro"]),
+    ('â', &["ro", "fr", "pt"]),
+    ('î', &["ro", "fr"]),
+    ('ő', &["hu"]),
+    ('ű', &["hu"]),
+];
+
+/// English name of a language detected by `detect`.
+pub fn name(code: &str) -> &'static str {
+    match code {
+        "ar" => "Arabic",
+        "bg" => "Bulgarian",
+        "cs" => "Czech",
+        "de" => "German",
+        "el" => "Greek",
+        "en" => "English",
+        "es" => "Spanish",
+        "fr" => "French",
+        "he" => "Hebrew",
+        "hu" => "Hungarian",
+        "id" => "Indonesian",
+        "it" => "Italian",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        "nl" => "Dutch",
+        "no" => "Norwegian",
+        "pl" => "Polish",
+        "pt" => "Portuguese",
+        "ro" => "Romanian",
+        "ru" => "Russian",
+        "sr" => "Serbian",
+        "sv" => "Swedish",
+        "th" => "Thai",
+        "tr" => "Turkish",
+        "uk" => "Ukrainian",
+        "ur" => "Urdu",
+        "zh" => "Chinese",
+        _ => "Unknown",
+    }
+}
+
+/// The ISO 639-1 code of the language `text` is written in, or `None` when
+/// there is too little to go on.
+pub fn detect(text: &str) -> Option<&'static str> {
+    by_script(text).or_else(|| by_words(text))
+}
+
+fn by_script(text: &str) -> Option<&'static str> {
+    let has = |range: &[(u32, u32)]| text.chars().any(|c| range.iter().any(|&(lo, hi)| (lo..=hi).contains(&(c as u32))));
+    let has_any = |chars: &str| text.chars().any(|c| chars.contains(c));
+
+    if has(&[(0x3040, 0x30ff)]) {
+        return Some("ja");
+    }
+    if has(&[(0xac00, 0xd7af), (0x1100, 0x11ff), (0x3130, 0x318f)]) {
+        return Some("ko");
+    }
+    if has(&[(0x4e00, 0x9fff)]) {
+        return Some("zh");
+    }
+    if has(&[(0x0600, 0x06ff), (0x0750, 0x077f), (0xfb50, 0xfdff), (0xfe70, 0xfeff)]) {
+        // Letters Urdu adds to the Arabic alphabet.
+        return Some(if has_any("ٹڈڑںےۓہھ") { "ur" } else { "ar" });
+    }
+    if has(&[(0x0590, 0x05ff)]) {
+        return Some("he");
+    }
+    if has(&[(0x0370, 0x03ff)]) {
+        return Some("el");
+    }
+    if has(&[(0x0e00, 0x0e7f)]) {
+        return Some("th");
+    }
+    if has(&[(0x0400, 0x04ff)]) {
+        let lower = text.to_lowercase();
+        let has_any = |chars: &str| lower.chars().any(|c| chars.contains(c));
+        return Some(if has_any("ђјљњћџ") {
+            "sr"
+        } else if has_any("іїєґ") {
+            "uk"
+        } else if has_any("ъ") && !has_any("ыэ") {
+            "bg"
+        } else {
+            "ru"
+        });
+    }
+    None
+}
+
+fn by_words(text: &str) -> Option<&'static str> {
+    let text = text.to_lowercase();
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS.iter().map(|(code, _)| (*code, 0)).collect();
+    let mut add = |code: &str, points: usize| {
+        if let Some((_, score)) = scores.iter_mut().find(|(c, _)| *c == code) {
+            *score += points;
+        }
+    };
+
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        for (code, words) in STOPWORDS {
+            if words.contains(&word) {
+                add(code, 2);
+            }
+        }
+    }
+    for c in text.chars() {
+        if let Some((_, codes)) = LETTERS.iter().find(|(letter, _)| *letter == c) {
+            for code in codes.iter() {
+                // A letter only one language uses counts as much as a word.
+                add(code, if codes.len() == 1 { 2 } else { 1 });
+            }
+        }
+    }
+
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    match (scores[0], scores[1]) {
+        ((code, best), (_, second)) if best >= 2 && best > second => Some(code),
+        _ => None,
+    }
+}
+
+impl Article {
+    /// The language of the title and description.
+    pub fn language(&self) -> Option<&'static str> {
+        detect(&format!("{}\n{}", self.headline(), self.description.as_deref().unwrap_or_default()))
+    }
+
+    /// Translates the title and description into `to`, unless they're in
+    /// that language already. The title and description stay as they are,
+    /// the translation is kept apart in `translation`. Returns whether
+    /// anything was translated.
+    pub fn translate(&mut self, translator: &dyn Translator, to: &str) -> Result<bool, NewsAPIError> {
+        let from = self.language();
+        if from == Some(to) || !translator.translates() {
+            return Ok(false);
+        }
+        let title = translator.translate(&self.title, from, to)?;
+        let description = match self.description.as_ref().filter(|d| !d.trim().is_empty()) {
+            Some(description) => Some(translator.translate(description, from, to)?),
+            None => None,
+        };
+        self.translation = Some(Translation { title, description });
+        Ok(true)
+    }
+
+    /// The title and description in the language last asked of `translate`.
+    pub fn translation(&self) -> Option<&Translation> {
+        self.translation.as_ref()
+    }
+}
+
+/// The title and description of an article in another language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    pub title: String,
+    pub description: Option<String>
+}
+
+/// Shared between threads, to translate several articles at once.
+pub trait Translator: Sync {
+    /// `text` in the language `to`. `from` is the language it's in, when
+    /// known.
+    fn translate(&self, text: &str, from: Option<&str>, to: &str) -> Result<String, NewsAPIError>;
+
+    /// Whether `translate` does anything.
+    fn translates(&self) -> bool {
+        true
+    }
+}
+
+/// Leaves everything as it is.
+pub struct NoTranslator;
+
+impl Translator for NoTranslator {
+    fn translate(&self, text: &str, _from: Option<&str>, _to: &str) -> Result<String, NewsAPIError> {
+        Ok(text.to_string())
+    }
+
+    fn translates(&self) -> bool {
+        false
+    }
+}
+
+/// A LibreTranslate server, or any other with the same `/translate` API.
+pub struct LibreTranslate {
+    url: String,
+    api_key: Option<String>
+}
+
+impl LibreTranslate {
+    pub fn new(url: &str) -> LibreTranslate {
+        LibreTranslate { url: url.trim_end_matches('/').to_string(), api_key: None }
+    }
+
+    pub fn api_key(&mut self, api_key: Option<String>) -> &mut LibreTranslate {
+        self.api_key = api_key;
+        self
+    }
+}
+
+impl Translator for LibreTranslate {
+    fn translate(&self, text: &str, from: Option<&str>, to: &str) -> Result<String, NewsAPIError> {
+        let mut body = serde_json::json!({
+            "q": text,
+            "source": from.unwrap_or("auto"),
+            "target": to,
+            "format": "text"
+        });
+        if let Some(api_key) = &self.api_key {
+            body["api_key"] = api_key.as_str().into();
+        }
+
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let response: serde_json::Value = match agent.post(&format!("{}/translate", self.url)).send_json(body) {
+            Ok(resp) => resp.into_json()?,
+            // The server explains what went wrong in the body.
+            Err(ureq::Error::Status(code, resp)) => {
+                let error: serde_json::Value = resp.into_json().unwrap_or_default();
+                let message = error["error"].as_str().map(|e| e.to_string()).unwrap_or_else(|| format!("status {}", code));
+                return Err(NewsAPIError::TranslationFailed(message));
+            },
+            Err(e) => return Err(e.into()),
+        };
+        match response["translatedText"].as_str() {
+            Some(translated) => Ok(translated.to_string()),
+            None => Err(NewsAPIError::TranslationFailed("no translatedText in the answer".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::MockServer;
+
+    #[test]
+    fn detects_languages() {
+        let cases = [
+            ("Fed raises interest rates for the third time this year", Some("en")),
+            ("Die Regierung will die Steuern für Familien senken", Some("de")),
+            ("Le gouvernement annonce une baisse des impôts pour les familles", Some("fr")),
+            ("El gobierno anuncia una rebaja de impuestos para las familias", Some("es")),
+            ("Il governo annuncia un taglio delle tasse per le famiglie", Some("it")),
+            ("O governo anuncia uma redução de impostos para as famílias", Some("pt")),
+            ("Het kabinet wil de belastingen voor gezinnen verlagen", Some("nl")),
+            ("Regeringen vill sänka skatten för barnfamiljer och pensionärer", Some("sv")),
+            ("Rząd chce obniżyć podatki dla rodzin z dziećmi", Some("pl")),
+            ("Правительство снизит налоги для семей", Some("ru")),
+            ("Уряд знизить податки для сімей", Some("uk")),
+            ("الحكومة تخفض الضرائب على الأسر", Some("ar")),
+            ("הממשלה תוריד מסים למשפחות", Some("he")),
+            ("Η κυβέρνηση μειώνει τους φόρους", Some("el")),
+            ("政府为家庭减税", Some("zh")),
+            ("政府は家族のために減税する", Some("ja")),
+            ("정부, 가족 감세 발표", Some("ko")),
+            ("Apple", None),
+        ];
+        for (text, language) in cases {
+            assert_eq!(detect(text), language, "{}", text);
+        }
+        assert_eq!(name("de"), "German");
+    }
+
+    #[test]
+    fn articles_ignore_the_source_suffix() {
+        let article: Article = serde_json::from_value(serde_json::json!({
+            "source": { "id": null, "name": "Le Monde" },
+            "title": "Le gouvernement annonce une baisse des impôts - The Times of the World",
+            "url": "https://lemonde.example/1",
+            "description": null
+        })).unwrap();
+        assert_eq!(article.language(), Some("fr"));
+    }
+
+    #[test]
+    fn translates_with_libretranslate() {
+        let server = MockServer::start(vec![(200, r#"{"translatedText": "The government cuts taxes"}"#.to_string())]);
+        let mut translator = LibreTranslate::new(&server.url);
+        translator.api_key(Some("secret".to_string()));
+        let mut article: Article = serde_json::from_value(serde_json::json!({
+            "source": { "id": null, "name": "Le Monde" },
+            "title": "Le gouvernement baisse les impôts",
+            "url": "https://lemonde.example/1",
+            "description": ""
+        })).unwrap();
+
+        assert!(article.translate(&translator, "en").unwrap());
+        assert_eq!(article.translation(), Some(&Translation { title: "The government cuts taxes".to_string(), description: None }));
+        // The original is what gets saved.
+        assert_eq!(article.title(), "Le gouvernement baisse les impôts");
+        assert!(!serde_json::to_string(&article).unwrap().contains("cuts taxes"));
+        let requests = server.requests();
+        assert_eq!((requests.len(), requests[0].path.as_str()), (1, "/translate"));
+        let request: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(request, serde_json::json!({
+            "q": "Le gouvernement baisse les impôts",
+            "source": "fr",
+            "target": "en",
+            "format": "text",
+            "api_key": "secret"
+        }));
+
+        // Nothing to do, and no request made, for articles in the language
+        // asked for or without a translator.
+        assert!(!article.translate(&translator, "fr").unwrap());
+        assert!(!article.translate(&NoTranslator, "en").unwrap());
+        assert_eq!(article.translation().unwrap().title, "The government cuts taxes");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn reports_translation_errors() {
+        let server = MockServer::start(vec![(400, r#"{"error": "es is not supported"}"#.to_string())]);
+        match LibreTranslate::new(&server.url).translate("hola", Some("es"), "en") {
+            Err(NewsAPIError::TranslationFailed(message)) => assert_eq!(message, "es is not supported"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -1,14 +1,30 @@
 #[cfg(feature = "async")]
 use reqwest::Method;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
 use url::Url;
 
+pub mod archive;
+pub mod cluster;
+pub mod export;
+pub mod extract;
+pub mod feed;
+pub mod filter;
+pub mod keywords;
+pub mod language;
+pub mod rank;
+pub mod sentiment;
+pub mod summary;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
+
 const BASE_URL: &str = "https://newsapi.org/v2";
 
 #[derive(Debug, thiserror::Error)]
 pub enum NewsAPIError {
+    /// Boxed, as it's much bigger than the other variants.
     #[error("Failed fetching articles")]
-    RequestFailed(#[from] ureq::Error),
+    RequestFailed(#[from] Box<ureq::Error>),
 
     #[error("Failed to convert response to string")]
     FailedResponseToString(#[from] std::io::Error),
@@ -22,15 +38,36 @@ pub enum NewsAPIError {
     #[error("Request failed: {0}")]
     BadRequest(&'static str),
 
+    #[error("API key rejected: {0}")]
+    ApiKey(&'static str),
+
+    #[error("Could not find the article content")]
+    NoContent,
+
+    #[error("Invalid filter rule")]
+    InvalidFilter(#[from] regex::Error),
+
+    #[error("Translation failed: {0}")]
+    TranslationFailed(String),
+
     #[error("Async Request Failed")]
     #[cfg(feature = "async")]
     AsyncRequestFailed(#[from] reqwest::Error)
 }
 
+impl From<ureq::Error> for NewsAPIError {
+    fn from(e: ureq::Error) -> Self {
+        NewsAPIError::RequestFailed(Box::new(e))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct NewsAPIResponse {
     status: String,
+    #[serde(default)]
     pub articles: Vec<Article>,
+    #[serde(default, rename = "totalResults")]
+    total_results: u32,
     code: Option<String>
 }
 
@@ -38,20 +75,72 @@ impl NewsAPIResponse {
     pub fn articles(&self) -> &Vec<Article> {
         &self.articles
     }
+
+    /// Articles available over all pages.
+    pub fn total_results(&self) -> u32 {
+        self.total_results
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Source {
+    id: Option<String>,
+    name: String
+}
+
+impl Source {
+    pub fn id(&self) -> Option<&String> {
+        self.id.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// `title` without the " - Source" suffix newsapi adds, which is often in
+/// another language than the rest.
+pub fn headline(title: &str) -> &str {
+    match title.rfind(" - ") {
+        Some(i) if i > 0 => &title[..i],
+        _ => title,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Article {
+    #[serde(default)]
+    source: Source,
     title: String,
     url: String,
-    description: Option<String>
+    description: Option<String>,
+    #[serde(rename = "urlToImage")]
+    url_to_image: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default, rename = "publishedAt")]
+    published_at: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    /// Set by `translate`, and never saved along with the article.
+    #[serde(skip)]
+    translation: Option<language::Translation>
 }
 
 impl Article {
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
     pub fn title(&self) -> &str {
         &self.title
     }
 
+    /// The title without the source's name.
+    pub fn headline(&self) -> &str {
+        headline(&self.title)
+    }
+
     pub fn url(&self) -> &str {
         &self.url
     }
@@ -59,44 +148,467 @@ impl Article {
     pub fn description(&self) -> Option<&String> {
         self.description.as_ref()
     }
+
+    pub fn url_to_image(&self) -> Option<&String> {
+        self.url_to_image.as_ref()
+    }
+
+    pub fn author(&self) -> Option<&String> {
+        self.author.as_ref()
+    }
+
+    /// RFC 3339 timestamp, e.g. `2022-06-01T14:30:00Z`.
+    pub fn published_at(&self) -> Option<&String> {
+        self.published_at.as_ref()
+    }
+
+    /// The start of the article text. The API truncates it and appends a
+    /// marker like `[+1234 chars]`.
+    pub fn content(&self) -> Option<&String> {
+        self.content.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceInfo {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub country: String
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SourcesResponse {
+    status: String,
+    #[serde(default)]
+    pub sources: Vec<SourceInfo>,
+    code: Option<String>
+}
+
+impl SourcesResponse {
+    pub fn sources(&self) -> &Vec<SourceInfo> {
+        &self.sources
+    }
+}
+
+#[derive(Clone)]
 pub enum Endpoint {
-    TopHeadlines
+    TopHeadlines,
+    Sources
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::TopHeadlines => "top-headlines",
+            Self::Sources => "top-headlines/sources",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Country {
+    Ae,
+    Ar,
+    At,
+    Au,
+    Be,
+    Bg,
+    Br,
+    Ca,
+    Ch,
+    Cn,
+    Co,
+    Cu,
+    Cz,
+    De,
+    Eg,
+    Fr,
+    Gb,
+    Gr,
+    Hk,
+    Hu,
+    Id,
+    Ie,
+    Il,
+    In,
+    It,
+    Jp,
+    Kr,
+    Lt,
+    Lv,
+    Ma,
+    Mx,
+    My,
+    Ng,
+    Nl,
+    No,
+    Nz,
+    Ph,
+    Pl,
+    Pt,
+    Ro,
+    Rs,
+    Ru,
+    Sa,
+    Se,
+    Sg,
+    Si,
+    Sk,
+    Th,
+    Tr,
+    Tw,
+    Ua,
+    #[default]
+    Us,
+    Ve,
+    Za
 }
 
-impl ToString for Endpoint {
-    fn to_string(&self) -> String {
+impl Country {
+    pub const ALL: [Country; 54] = [
+        Self::Ae,
+        Self::Ar,
+        Self::At,
+        Self::Au,
+        Self::Be,
+        Self::Bg,
+        Self::Br,
+        Self::Ca,
+        Self::Ch,
+        Self::Cn,
+        Self::Co,
+        Self::Cu,
+        Self::Cz,
+        Self::De,
+        Self::Eg,
+        Self::Fr,
+        Self::Gb,
+        Self::Gr,
+        Self::Hk,
+        Self::Hu,
+        Self::Id,
+        Self::Ie,
+        Self::Il,
+        Self::In,
+        Self::It,
+        Self::Jp,
+        Self::Kr,
+        Self::Lt,
+        Self::Lv,
+        Self::Ma,
+        Self::Mx,
+        Self::My,
+        Self::Ng,
+        Self::Nl,
+        Self::No,
+        Self::Nz,
+        Self::Ph,
+        Self::Pl,
+        Self::Pt,
+        Self::Ro,
+        Self::Rs,
+        Self::Ru,
+        Self::Sa,
+        Self::Se,
+        Self::Sg,
+        Self::Si,
+        Self::Sk,
+        Self::Th,
+        Self::Tr,
+        Self::Tw,
+        Self::Ua,
+        Self::Us,
+        Self::Ve,
+        Self::Za
+    ];
+
+    /// English name of the country.
+    pub fn name(&self) -> &'static str {
         match self {
-            Self::TopHeadlines => "top-headlines".to_string(),
+            Self::Ae => "United Arab Emirates",
+            Self::Ar => "Argentina",
+            Self::At => "Austria",
+            Self::Au => "Australia",
+            Self::Be => "Belgium",
+            Self::Bg => "Bulgaria",
+            Self::Br => "Brazil",
+            Self::Ca => "Canada",
+            Self::Ch => "Switzerland",
+            Self::Cn => "China",
+            Self::Co => "Colombia",
+            Self::Cu => "Cuba",
+            Self::Cz => "Czechia",
+            Self::De => "Germany",
+            Self::Eg => "Egypt",
+            Self::Fr => "France",
+            Self::Gb => "United Kingdom",
+            Self::Gr => "Greece",
+            Self::Hk => "Hong Kong",
+            Self::Hu => "Hungary",
+            Self::Id => "Indonesia",
+            Self::Ie => "Ireland",
+            Self::Il => "Israel",
+            Self::In => "India",
+            Self::It => "Italy",
+            Self::Jp => "Japan",
+            Self::Kr => "South Korea",
+            Self::Lt => "Lithuania",
+            Self::Lv => "Latvia",
+            Self::Ma => "Morocco",
+            Self::Mx => "Mexico",
+            Self::My => "Malaysia",
+            Self::Ng => "Nigeria",
+            Self::Nl => "Netherlands",
+            Self::No => "Norway",
+            Self::Nz => "New Zealand",
+            Self::Ph => "Philippines",
+            Self::Pl => "Poland",
+            Self::Pt => "Portugal",
+            Self::Ro => "Romania",
+            Self::Rs => "Serbia",
+            Self::Ru => "Russia",
+            Self::Sa => "Saudi Arabia",
+            Self::Se => "Sweden",
+            Self::Sg => "Singapore",
+            Self::Si => "Slovenia",
+            Self::Sk => "Slovakia",
+            Self::Th => "Thailand",
+            Self::Tr => "Turkey",
+            Self::Tw => "Taiwan",
+            Self::Ua => "Ukraine",
+            Self::Us => "United States",
+            Self::Ve => "Venezuela",
+            Self::Za => "South Africa",
         }
     }
 }
 
-pub enum Country {
-    Us
+impl fmt::Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ae => "ae",
+            Self::Ar => "ar",
+            Self::At => "at",
+            Self::Au => "au",
+            Self::Be => "be",
+            Self::Bg => "bg",
+            Self::Br => "br",
+            Self::Ca => "ca",
+            Self::Ch => "ch",
+            Self::Cn => "cn",
+            Self::Co => "co",
+            Self::Cu => "cu",
+            Self::Cz => "cz",
+            Self::De => "de",
+            Self::Eg => "eg",
+            Self::Fr => "fr",
+            Self::Gb => "gb",
+            Self::Gr => "gr",
+            Self::Hk => "hk",
+            Self::Hu => "hu",
+            Self::Id => "id",
+            Self::Ie => "ie",
+            Self::Il => "il",
+            Self::In => "in",
+            Self::It => "it",
+            Self::Jp => "jp",
+            Self::Kr => "kr",
+            Self::Lt => "lt",
+            Self::Lv => "lv",
+            Self::Ma => "ma",
+            Self::Mx => "mx",
+            Self::My => "my",
+            Self::Ng => "ng",
+            Self::Nl => "nl",
+            Self::No => "no",
+            Self::Nz => "nz",
+            Self::Ph => "ph",
+            Self::Pl => "pl",
+            Self::Pt => "pt",
+            Self::Ro => "ro",
+            Self::Rs => "rs",
+            Self::Ru => "ru",
+            Self::Sa => "sa",
+            Self::Se => "se",
+            Self::Sg => "sg",
+            Self::Si => "si",
+            Self::Sk => "sk",
+            Self::Th => "th",
+            Self::Tr => "tr",
+            Self::Tw => "tw",
+            Self::Ua => "ua",
+            Self::Us => "us",
+            Self::Ve => "ve",
+            Self::Za => "za",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Business,
+    Entertainment,
+    General,
+    Health,
+    Science,
+    Sports,
+    Technology
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Self::Business,
+        Self::Entertainment,
+        Self::General,
+        Self::Health,
+        Self::Science,
+        Self::Sports,
+        Self::Technology
+    ];
+
+    /// Capitalised name, for menus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Business => "Business",
+            Self::Entertainment => "Entertainment",
+            Self::General => "General",
+            Self::Health => "Health",
+            Self::Science => "Science",
+            Self::Sports => "Sports",
+            Self::Technology => "Technology",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Business => "business",
+            Self::Entertainment => "entertainment",
+            Self::General => "general",
+            Self::Health => "health",
+            Self::Science => "science",
+            Self::Sports => "sports",
+            Self::Technology => "technology",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Ar,
+    De,
+    En,
+    Es,
+    Fr,
+    He,
+    It,
+    Nl,
+    No,
+    Pt,
+    Ru,
+    Sv,
+    Ud,
+    Zh
 }
 
-impl ToString for Country {
-    fn to_string(&self) -> String {
+impl Language {
+    pub const ALL: [Language; 14] = [
+        Self::Ar,
+        Self::De,
+        Self::En,
+        Self::Es,
+        Self::Fr,
+        Self::He,
+        Self::It,
+        Self::Nl,
+        Self::No,
+        Self::Pt,
+        Self::Ru,
+        Self::Sv,
+        Self::Ud,
+        Self::Zh
+    ];
+
+    /// English name of the language.
+    pub fn name(&self) -> &'static str {
         match self {
-            Self::Us => "us".to_string(),
+            Self::Ar => "Arabic",
+            Self::De => "German",
+            Self::En => "English",
+            Self::Es => "Spanish",
+            Self::Fr => "French",
+            Self::He => "Hebrew",
+            Self::It => "Italian",
+            Self::Nl => "Dutch",
+            Self::No => "Norwegian",
+            Self::Pt => "Portuguese",
+            Self::Ru => "Russian",
+            Self::Sv => "Swedish",
+            Self::Ud => "Urdu",
+            Self::Zh => "Chinese",
         }
     }
 }
 
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ar => "ar",
+            Self::De => "de",
+            Self::En => "en",
+            Self::Es => "es",
+            Self::Fr => "fr",
+            Self::He => "he",
+            Self::It => "it",
+            Self::Nl => "nl",
+            Self::No => "no",
+            Self::Pt => "pt",
+            Self::Ru => "ru",
+            Self::Sv => "sv",
+            Self::Ud => "ud",
+            Self::Zh => "zh",
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct NewsAPI {
     api_key: String,
+    base_url: String,
     endpoint: Endpoint,
-    country: Country
+    country: Country,
+    category: Option<Category>,
+    language: Option<Language>,
+    sources: Option<String>,
+    page_size: Option<u32>,
+    page: Option<u32>
 }
 
 impl NewsAPI {
     pub fn new(api_key: &str) -> NewsAPI {
         NewsAPI {
             api_key: api_key.to_string(),
+            base_url: BASE_URL.to_string(),
             endpoint: Endpoint::TopHeadlines,
-            country: Country::Us
+            country: Country::Us,
+            category: None,
+            language: None,
+            sources: None,
+            page_size: None,
+            page: None
         }
     }
 
@@ -110,25 +622,113 @@ impl NewsAPI {
         self
     }
 
+    pub fn category(&mut self, category: Option<Category>) -> &mut NewsAPI {
+        self.category = category;
+        self
+    }
+
+    /// Only used to list sources, top headlines can't be narrowed by language.
+    pub fn language(&mut self, language: Option<Language>) -> &mut NewsAPI {
+        self.language = language;
+        self
+    }
+
+    /// Comma separated newsapi source ids. Top headlines can't be narrowed
+    /// down by both sources and country or category, so those are dropped
+    /// from the request when this is set.
+    pub fn sources(&mut self, sources: Option<String>) -> &mut NewsAPI {
+        self.sources = sources;
+        self
+    }
+
+    /// Number of articles per request, newsapi caps this at 100.
+    pub fn page_size(&mut self, page_size: u32) -> &mut NewsAPI {
+        self.page_size = Some(page_size.clamp(1, 100));
+        self
+    }
+
+    /// Which page of `page_size` articles to fetch, starting at 1.
+    pub fn page(&mut self, page: u32) -> &mut NewsAPI {
+        self.page = Some(page.max(1));
+        self
+    }
+
+    /// Points the client at a different server, e.g. a local stand-in in tests.
+    pub fn base_url(&mut self, base_url: &str) -> &mut NewsAPI {
+        self.base_url = base_url.to_string();
+        self
+    }
+
     fn prepare_url(&self) -> Result<String, NewsAPIError> {
-        let mut url = Url::parse(BASE_URL)?;
-        url.path_segments_mut().unwrap().push(&self.endpoint.to_string());
+        self.prepare_url_for(&self.endpoint)
+    }
+
+    fn prepare_url_for(&self, endpoint: &Endpoint) -> Result<String, NewsAPIError> {
+        let mut url = Url::parse(&self.base_url)?;
+        url.path_segments_mut().unwrap().pop_if_empty().extend(endpoint.to_string().split('/'));
         
-        let country = format!("country={}", self.country.to_string());
-        url.set_query(Some(&country));
+        {
+            let mut query = url.query_pairs_mut();
+            match endpoint {
+                Endpoint::TopHeadlines => {
+                    if let Some(sources) = &self.sources {
+                        query.append_pair("sources", sources);
+                    } else {
+                        query.append_pair("country", &self.country.to_string());
+                        if let Some(category) = &self.category {
+                            query.append_pair("category", &category.to_string());
+                        }
+                    }
+                    if let Some(page_size) = self.page_size {
+                        query.append_pair("pageSize", &page_size.to_string());
+                    }
+                    if let Some(page) = self.page {
+                        query.append_pair("page", &page.to_string());
+                    }
+                },
+                Endpoint::Sources => {
+                    query.append_pair("country", &self.country.to_string());
+                    if let Some(category) = &self.category {
+                        query.append_pair("category", &category.to_string());
+                    }
+                    if let Some(language) = &self.language {
+                        query.append_pair("language", &language.to_string());
+                    }
+                }
+            }
+        }
 
         Ok(url.to_string())
     }
 
+    fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, NewsAPIError> {
+        let req = ureq::get(url).set("Authorization", &self.api_key);
+        // newsapi explains failures in the body of 4xx responses
+        match req.call() {
+            Ok(resp) | Err(ureq::Error::Status(_, resp)) => Ok(resp.into_json()?),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub fn fetch(&self) -> Result<NewsAPIResponse, NewsAPIError> {
         let url = self.prepare_url()?;
+        let response: NewsAPIResponse = self.get(&url)?;
 
-        let req = ureq::get(&url).set("Authorization", &self.api_key);
-        let response: NewsAPIResponse = req.call()?.into_json()?;
+        match response.status.as_str() {
+            "ok" => Ok(response),
+            _ => Err(map_response_err(response.code))
+        }
+    }
+
+    /// Lists the sources newsapi knows about for the configured country,
+    /// category and language.
+    pub fn fetch_sources(&self) -> Result<SourcesResponse, NewsAPIError> {
+        let url = self.prepare_url_for(&Endpoint::Sources)?;
+        let response: SourcesResponse = self.get(&url)?;
 
         match response.status.as_str() {
-            "ok" => return Ok(response),
-            _ => return Err(map_response_err(response.code))
+            "ok" => Ok(response),
+            _ => Err(map_response_err(response.code))
         }
     }
 
@@ -150,37 +750,65 @@ impl NewsAPI {
             .map_err(|e| NewsAPIError::AsyncRequestFailed(e))?;
 
         match response.status.as_str() {
-            "ok" => return Ok(response),
-            _ => return Err(map_response_err(response.code))
+            "ok" => Ok(response),
+            _ => Err(map_response_err(response.code))
         }
     }
 
     #[cfg(target_arch = "wasm32")]
-    pub async fn fetch_web(&self) -> Result<NewsAPIResponse, NewsAPIError> {
-        let url = self.prepare_url()?;
-        let req = reqwasm::http::Request::get(&url).header("Authorization", &self.api_key);
+    async fn get_web<T: DeserializeOwned>(&self, url: &str) -> Result<T, NewsAPIError> {
+        let req = reqwasm::http::Request::get(url).header("Authorization", &self.api_key);
 
         let resp = req
             .send()
             .await
             .map_err(|_| NewsAPIError::BadRequest("failed sending request."))?;
 
-        let response: NewsAPIResponse = resp
+        resp
             .json()
             .await
-            .map_err(|_| NewsAPIError::BadRequest("failed converting reponse to json."))?;
+            .map_err(|_| NewsAPIError::BadRequest("failed converting reponse to json."))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn fetch_web(&self) -> Result<NewsAPIResponse, NewsAPIError> {
+        let url = self.prepare_url()?;
+        let response: NewsAPIResponse = self.get_web(&url).await?;
+
+        match response.status.as_str() {
+            "ok" => Ok(response),
+            _ => Err(map_response_err(response.code))
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn fetch_sources_web(&self) -> Result<SourcesResponse, NewsAPIError> {
+        let url = self.prepare_url_for(&Endpoint::Sources)?;
+        let response: SourcesResponse = self.get_web(&url).await?;
 
         match response.status.as_str() {
-            "ok" => return Ok(response),
-            _ => return Err(map_response_err(response.code))
+            "ok" => Ok(response),
+            _ => Err(map_response_err(response.code))
         }
     }
 }
 
+impl NewsAPIError {
+    /// Whether the request failed because of the API key rather than the
+    /// network or the query.
+    pub fn is_api_key_error(&self) -> bool {
+        matches!(self, NewsAPIError::ApiKey(_))
+    }
+}
+
 fn map_response_err(code: Option<String>) -> NewsAPIError {
     if let Some(code) = code {
         match code.as_str() {
-            "apiKeyDisabled" => NewsAPIError::BadRequest("Your API key is disabled"),
+            "apiKeyDisabled" => NewsAPIError::ApiKey("Your API key is disabled"),
+            "apiKeyExhausted" => NewsAPIError::ApiKey("Your API key has no more requests available"),
+            "apiKeyInvalid" => NewsAPIError::ApiKey("Your API key is invalid"),
+            "apiKeyMissing" => NewsAPIError::ApiKey("Your API key is missing"),
+            "rateLimited" => NewsAPIError::BadRequest("You have been rate limited"),
             _ => NewsAPIError::BadRequest("Unknown")
         }
     } else {
@@ -190,6 +818,56 @@ fn map_response_err(code: Option<String>) -> NewsAPIError {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn api_key_errors_are_typed() {
+        let response: NewsAPIResponse = serde_json::from_str(
+            r#"{"status":"error","code":"apiKeyInvalid","message":"Your API key is invalid."}"#
+        ).unwrap();
+        assert!(response.articles().is_empty());
+        assert!(map_response_err(response.code).is_api_key_error());
+        assert!(!map_response_err(Some("rateLimited".to_string())).is_api_key_error());
+    }
+
+    #[test]
+    fn query_parameters_end_up_in_the_url() {
+        let mut newsapi = NewsAPI::new("key");
+        newsapi
+            .country(Country::Gb)
+            .category(Some(Category::Technology))
+            .language(Some(Language::En))
+            .page_size(500);
+
+        assert_eq!(
+            newsapi.prepare_url().unwrap(),
+            "https://newsapi.org/v2/top-headlines?country=gb&category=technology&pageSize=100"
+        );
+        assert_eq!(
+            newsapi.prepare_url_for(&Endpoint::Sources).unwrap(),
+            "https://newsapi.org/v2/top-headlines/sources?country=gb&category=technology&language=en"
+        );
+
+        newsapi.sources(Some("bbc-news,the-verge".to_string()));
+        assert_eq!(
+            newsapi.prepare_url().unwrap(),
+            "https://newsapi.org/v2/top-headlines?sources=bbc-news%2Cthe-verge&pageSize=100"
+        );
+    }
+
+    #[test]
+    fn pages_start_at_one() {
+        let mut newsapi = NewsAPI::new("key");
+        newsapi.page_size(20).page(2);
+        assert_eq!(
+            newsapi.prepare_url().unwrap(),
+            "https://newsapi.org/v2/top-headlines?country=us&pageSize=20&page=2"
+        );
+
+        newsapi.page(0);
+        assert!(newsapi.prepare_url().unwrap().ends_with("&page=1"));
+    }
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
@@ -0,0 +1,400 @@
+//! Orders headlines by how interesting they should be to the reader. Every
+//! article gets a score out of five signals: how recent it is, how much its
+//! source is preferred, the reader's keywords it mentions, how often the
+//! reader opened articles like it, and how many other sources cover the same
+//! story.
+
+use crate::cluster::{cluster, title_words};
+use crate::feed::parse_rfc3339;
+use crate::Article;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortMode {
+    /// As newsapi returns them.
+    #[default]
+    Api,
+    Newest,
+    ForYou
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 3] = [Self::Api, Self::Newest, Self::ForYou];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Api => "API order",
+            Self::Newest => "Newest",
+            Self::ForYou => "For you",
+        }
+    }
+}
+
+impl FromStr for SortMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "api" => Ok(Self::Api),
+            "newest" => Ok(Self::Newest),
+            "for-you" => Ok(Self::ForYou),
+            _ => Err(format!("unknown sort mode {:?}, pick one of api, newest, for-you", s)),
+        }
+    }
+}
+
+/// How much each signal counts in the score.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    pub recency: f32,
+    pub source: f32,
+    pub keywords: f32,
+    pub clicks: f32,
+    pub cluster: f32
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights { recency: 1.0, source: 1.0, keywords: 1.5, clicks: 1.0, cluster: 0.5 }
+    }
+}
+
+/// The reader's tastes, as written in the config file:
+///
+/// ```toml
+/// [ranking]
+/// keywords = ["rust", "climate"]
+/// sources = { "bbc-news" = 1.0, "TMZ" = -1.0 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub weights: Weights,
+    /// Age at which an article is half as recent as a brand new one.
+    pub half_life_hours: f32,
+    /// From -1 to 1, by source id or name, in any case.
+    pub sources: HashMap<String, f32>,
+    pub keywords: Vec<String>
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences { weights: Weights::default(), half_life_hours: 12.0, sources: HashMap::new(), keywords: vec![] }
+    }
+}
+
+/// How many opened articles are remembered for the ranking.
+pub const CLICKS_KEPT: usize = 1000;
+
+/// An article the reader opened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Click {
+    pub url: String,
+    pub title: String,
+    /// Name of the source.
+    pub source: String,
+    /// Unix time.
+    pub at: i64
+}
+
+/// What the ranking looks at in an article.
+pub struct Candidate<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    pub source_id: Option<&'a str>,
+    pub source: &'a str,
+    /// RFC 3339 timestamp.
+    pub published_at: Option<&'a str>
+}
+
+impl<'a> From<&'a Article> for Candidate<'a> {
+    fn from(article: &'a Article) -> Self {
+        Candidate {
+            title: &article.title,
+            description: article.description.as_deref().unwrap_or_default(),
+            source_id: article.source.id.as_deref(),
+            source: &article.source.name,
+            published_at: article.published_at.as_deref()
+        }
+    }
+}
+
+/// The signals of an article, each from 0 to 1 (the source one from -1),
+/// and their weighted sum.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Score {
+    pub recency: f32,
+    pub source: f32,
+    pub keywords: f32,
+    pub clicks: f32,
+    pub cluster: f32,
+    pub total: f32
+}
+
+impl Score {
+    /// The score as the sum of every signal times its weight.
+    pub fn explain(&self, weights: &Weights) -> String {
+        let terms = [
+            ("recency", self.recency, weights.recency),
+            ("source", self.source, weights.source),
+            ("keywords", self.keywords, weights.keywords),
+            ("clicks", self.clicks, weights.clicks),
+            ("cluster", self.cluster, weights.cluster),
+        ];
+        let terms: Vec<String> = terms.iter().map(|(name, signal, weight)| format!("{} {:.2}×{}", name, signal, weight)).collect();
+        format!("{:.2} = {}", self.total, terms.join(" + "))
+    }
+}
+
+pub struct Ranker<'a> {
+    preferences: &'a Preferences,
+    /// Clicks per source name, lowercased.
+    clicks_by_source: HashMap<String, usize>,
+    /// Words of the titles the reader opened.
+    clicked_words: HashSet<String>,
+    now: i64
+}
+
+impl<'a> Ranker<'a> {
+    /// `now` is the Unix time recency is measured from.
+    pub fn new(preferences: &'a Preferences, clicks: &[Click], now: i64) -> Ranker<'a> {
+        let mut clicks_by_source = HashMap::new();
+        let mut clicked_words = HashSet::new();
+        for click in clicks {
+            *clicks_by_source.entry(click.source.to_lowercase()).or_insert(0) += 1;
+            clicked_words.extend(title_words(&click.title));
+        }
+        Ranker { preferences, clicks_by_source, clicked_words, now }
+    }
+
+    pub fn weights(&self) -> &Weights {
+        &self.preferences.weights
+    }
+
+    /// Scores every one of `candidates`, which are ranked together: the
+    /// cluster signal depends on the others.
+    pub fn scores(&self, candidates: &[Candidate]) -> Vec<Score> {
+        let titles: Vec<&str> = candidates.iter().map(|c| c.title).collect();
+        let mut cluster_size = vec![1; candidates.len()];
+        let clusters = cluster(&titles);
+        for members in &clusters {
+            for &i in members {
+                cluster_size[i] = members.len();
+            }
+        }
+        let largest = clusters.iter().map(|c| c.len()).max().unwrap_or(1);
+        let most_clicks = self.clicks_by_source.values().copied().max().unwrap_or(0);
+
+        candidates
+            .iter()
+            .zip(cluster_size)
+            .map(|(candidate, size)| {
+                let weights = &self.preferences.weights;
+                let mut score = Score {
+                    recency: self.recency(candidate),
+                    source: self.source(candidate),
+                    keywords: self.keywords(candidate),
+                    clicks: self.clicks(candidate, most_clicks),
+                    cluster: match largest {
+                        1 => 0.0,
+                        _ => (size - 1) as f32 / (largest - 1) as f32,
+                    },
+                    total: 0.0
+                };
+                score.total = score.recency * weights.recency
+                    + score.source * weights.source
+                    + score.keywords * weights.keywords
+                    + score.clicks * weights.clicks
+                    + score.cluster * weights.cluster;
+                score
+            })
+            .collect()
+    }
+
+    /// Indices of `candidates` in the order of `mode`. Ties keep the API
+    /// order.
+    pub fn order(&self, candidates: &[Candidate], mode: SortMode) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        match mode {
+            SortMode::Api => {},
+            SortMode::Newest => {
+                let published: Vec<i64> = candidates.iter().map(|c| published(c).unwrap_or(i64::MIN)).collect();
+                order.sort_by_key(|&i| std::cmp::Reverse(published[i]));
+            },
+            SortMode::ForYou => {
+                let scores = self.scores(candidates);
+                order.sort_by(|&a, &b| scores[b].total.partial_cmp(&scores[a].total).unwrap_or(std::cmp::Ordering::Equal));
+            },
+        }
+        order
+    }
+
+    /// Halves every `half_life_hours`.
+    fn recency(&self, candidate: &Candidate) -> f32 {
+        match published(candidate) {
+            Some(published) => {
+                let hours = (self.now - published).max(0) as f32 / 3600.0;
+                0.5f32.powf(hours / self.preferences.half_life_hours.max(0.1))
+            },
+            None => 0.0,
+        }
+    }
+
+    /// The preference for the source id if there's one, else for its name.
+    fn source(&self, candidate: &Candidate) -> f32 {
+        candidate
+            .source_id
+            .and_then(|id| self.source_preference(id))
+            .or_else(|| self.source_preference(candidate.source))
+            .map(|weight| weight.clamp(-1.0, 1.0))
+            .unwrap_or(0.0)
+    }
+
+    /// The preference for `name`, as written or else in any case. Of
+    /// several spellings, the first in alphabetical order wins.
+    fn source_preference(&self, name: &str) -> Option<f32> {
+        let sources = &self.preferences.sources;
+        sources.get(name).copied().or_else(|| {
+            sources
+                .iter()
+                .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+                .min_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(_, weight)| *weight)
+        })
+    }
+
+    /// Share of the keywords mentioned, counting three as plenty.
+    fn keywords(&self, candidate: &Candidate) -> f32 {
+        let text = format!("{}\n{}", candidate.title, candidate.description).to_lowercase();
+        let words: HashSet<&str> = text.split(|c: char| !c.is_alphanumeric()).collect();
+        let mentioned = self
+            .preferences
+            .keywords
+            .iter()
+            .filter(|k| {
+                let k = k.to_lowercase();
+                // Phrases are looked for as they are, single words as words.
+                match k.contains(' ') {
+                    true => text.contains(&k),
+                    false => words.contains(k.as_str()),
+                }
+            })
+            .count();
+        mentioned.min(3) as f32 / 3.0
+    }
+
+    /// Half how often the reader opens this source, compared to their
+    /// favourite, and half how many of the title's words were in the titles
+    /// they opened.
+    fn clicks(&self, candidate: &Candidate, most_clicks: usize) -> f32 {
+        let source = match most_clicks {
+            0 => 0.0,
+            _ => self.clicks_by_source.get(&candidate.source.to_lowercase()).copied().unwrap_or(0) as f32 / most_clicks as f32,
+        };
+        let words = title_words(candidate.title);
+        let topic = match words.len() {
+            0 => 0.0,
+            n => words.iter().filter(|w| self.clicked_words.contains(*w)).count() as f32 / n as f32,
+        };
+        (source + topic) / 2.0
+    }
+}
+
+fn published(candidate: &Candidate) -> Option<i64> {
+    candidate.published_at.and_then(parse_rfc3339).map(|t| t as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate<'a>(title: &'a str, source: &'a str, published_at: &'a str) -> Candidate<'a> {
+        Candidate { title, description: "", source_id: None, source, published_at: Some(published_at) }
+    }
+
+    const NOW: i64 = 1654862400; // 2022-06-10T12:00:00Z
+
+    #[test]
+    fn scores_every_signal() {
+        let preferences = Preferences {
+            keywords: vec!["rust".to_string(), "interest rates".to_string()],
+            sources: HashMap::from([("the gazette".to_string(), 0.5), ("Tabloid".to_string(), -2.0)]),
+            ..Preferences::default()
+        };
+        let clicks = vec![
+            Click { url: "https://gazette.example/1".to_string(), title: "Bridge reopens".to_string(), source: "The Gazette".to_string(), at: NOW },
+            Click { url: "https://gazette.example/2".to_string(), title: "Harbour works".to_string(), source: "The Gazette".to_string(), at: NOW },
+        ];
+        let ranker = Ranker::new(&preferences, &clicks, NOW);
+        let candidates = [
+            candidate("Fed raises interest rates again", "Wire", "2022-06-10T12:00:00Z"),
+            candidate("Interest rates: the Fed raises again", "Daily", "2022-06-10T00:00:00Z"),
+            candidate("Rust bridge reopens", "The Gazette", "2022-06-09T12:00:00Z"),
+            candidate("Celebrity wedding", "Tabloid", "2022-06-10T11:00:00Z"),
+        ];
+        let scores = ranker.scores(&candidates);
+
+        assert_eq!(scores[0].recency, 1.0);
+        assert_eq!(scores[1].recency, 0.5);
+        assert_eq!(scores[2].recency, 0.25);
+        assert_eq!(scores[0].cluster, 1.0);
+        assert_eq!(scores[2].cluster, 0.0);
+        assert!((scores[0].keywords - 1.0 / 3.0).abs() < 1e-6);
+        assert!((scores[2].keywords - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(scores[2].source, 0.5);
+        assert_eq!(scores[3].source, -1.0);
+        // Its source is the most opened one, and "bridge" and "reopen" were
+        // in opened titles: 2 of its 3 words.
+        assert!((scores[2].clicks - (1.0 + 2.0 / 3.0) / 2.0).abs() < 1e-6);
+        assert_eq!(scores[0].clicks, 0.0);
+
+        assert_eq!(scores[3].explain(ranker.weights()), format!(
+            "{:.2} = recency {:.2}×1 + source -1.00×1 + keywords 0.00×1.5 + clicks 0.00×1 + cluster 0.00×0.5",
+            scores[3].total,
+            scores[3].recency
+        ));
+    }
+
+    #[test]
+    fn sorts_by_mode() {
+        let preferences = Preferences { keywords: vec!["rust".to_string()], ..Preferences::default() };
+        let ranker = Ranker::new(&preferences, &[], NOW);
+        let candidates = [
+            candidate("Old news about rust", "Wire", "2022-06-09T12:00:00Z"),
+            Candidate { published_at: None, ..candidate("Undated", "Wire", "") },
+            candidate("Fresher news", "Wire", "2022-06-10T00:00:00Z"),
+        ];
+
+        assert_eq!(ranker.order(&candidates, SortMode::Api), vec![0, 1, 2]);
+        assert_eq!(ranker.order(&candidates, SortMode::Newest), vec![2, 0, 1]);
+        assert_eq!(ranker.order(&candidates, SortMode::ForYou), vec![0, 2, 1]);
+        assert_eq!("for-you".parse::<SortMode>(), Ok(SortMode::ForYou));
+        assert!("best".parse::<SortMode>().is_err());
+    }
+
+    #[test]
+    fn source_ids_come_before_names() {
+        let preferences = Preferences {
+            sources: HashMap::from([
+                ("BBC News".to_string(), -1.0),
+                ("bbc-news".to_string(), 1.0),
+                ("wire".to_string(), 0.2),
+                ("Wire".to_string(), 0.4),
+            ]),
+            ..Preferences::default()
+        };
+        let ranker = Ranker::new(&preferences, &[], NOW);
+        let bbc = |id| Candidate { source_id: Some(id), ..candidate("Bridge reopens", "BBC News", "2022-06-10T12:00:00Z") };
+        let scores = ranker.scores(&[bbc("bbc-news"), bbc("bbc")]);
+        assert_eq!((scores[0].source, scores[1].source), (1.0, -1.0));
+
+        // Of two spellings, the one spelt like the source wins, else the
+        // first in alphabetical order.
+        assert_eq!(ranker.source(&candidate("Rates rise", "Wire", "2022-06-10T12:00:00Z")), 0.4);
+        assert_eq!(ranker.source(&candidate("Rates rise", "WIRE", "2022-06-10T12:00:00Z")), 0.4);
+    }
+}
@@ -0,0 +1,231 @@
+//! Tone of headlines, scored from a lexicon the way VADER does it: words
+//! carry a valence from -4 to 4, which boosters ("very"), capitals,
+//! negations ("not") and exclamation marks strengthen or turn around, and
+//! the sum is squashed into a compound score from -1 to 1.
+
+use crate::Article;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Compound score past which a text is positive, or negative below minus it.
+const THRESHOLD: f64 = 0.05;
+/// Added by a booster word, or taken by a dampener.
+const BOOST: f64 = 0.293;
+/// Added by a word in capitals, among words that aren't.
+const CAPS_BOOST: f64 = 0.733;
+/// Valence of negated words is multiplied by this.
+const NEGATION: f64 = -0.74;
+const EXCLAMATION: f64 = 0.292;
+/// Squashes the sum of valences: sum / sqrt(sum² + ALPHA).
+const ALPHA: f64 = 15.0;
+
+const LEXICON: &[(&str, f64)] = &[
+    ("abuse", -3.2), ("accident", -2.1), ("accused", -1.9), ("achievement", 2.2), ("agree", 1.5),
+    ("agreement", 2.2), ("alarm", -1.4), ("amazing", 2.8), ("anger", -2.7), ("angry", -2.3),
+    ("arrest", -1.4), ("arrested", -2.1), ("attack", -2.1), ("attacks", -1.9), ("award", 2.5),
+    ("awarded", 1.7), ("awful", -2.0), ("bad", -2.5), ("ban", -2.6), ("bankrupt", -2.6),
+    ("banned", -2.0), ("beautiful", 2.9), ("benefit", 2.0), ("benefits", 1.6), ("best", 3.2),
+    ("better", 1.9), ("blame", -1.4), ("bomb", -2.2), ("boost", 1.7), ("boosts", 1.3),
+    ("brave", 2.4), ("breakthrough", 2.0), ("brilliant", 2.8), ("celebrate", 2.7), ("celebrates", 2.7),
+    ("celebration", 2.6), ("chaos", -2.7), ("cheer", 2.3), ("collapse", -2.2), ("collapsed", -2.0),
+    ("conflict", -1.3), ("corruption", -2.5), ("crash", -1.7), ("crime", -2.5), ("criminal", -2.4),
+    ("crisis", -3.1), ("cut", -1.1), ("cuts", -1.2), ("damage", -2.2), ("damaged", -1.9),
+    ("danger", -2.4), ("dangerous", -2.1), ("dead", -3.3), ("deadly", -2.9), ("death", -2.9),
+    ("deaths", -2.8), ("defeat", -2.0), ("delight", 2.9), ("destroy", -2.5), ("destroyed", -3.2),
+    ("die", -2.9), ("died", -2.6), ("dies", -2.9), ("disaster", -3.1), ("disease", -1.7),
+    ("emergency", -1.6), ("evil", -3.4), ("excellent", 2.7), ("excited", 2.2), ("excitement", 2.2),
+    ("fail", -2.5), ("failed", -2.3), ("fails", -1.8), ("failure", -2.3), ("fantastic", 2.6),
+    ("fear", -2.2), ("fears", -1.8), ("fight", -1.6), ("fire", -1.4), ("fraud", -2.8),
+    ("free", 2.3), ("freedom", 3.2), ("fun", 2.3), ("generous", 2.3), ("good", 1.9),
+    ("great", 3.1), ("growth", 1.6), ("guilty", -1.8), ("happy", 2.7), ("harm", -2.5),
+    ("hate", -2.7), ("help", 1.7), ("helps", 1.6), ("hero", 2.6), ("heroes", 2.3),
+    ("honor", 2.2), ("hope", 1.9), ("hopeful", 1.6), ("hopes", 1.6), ("horrible", -2.5),
+    ("hurt", -2.4), ("improve", 1.9), ("improved", 2.1), ("improvement", 2.0), ("improves", 1.8),
+    ("injured", -2.1), ("injury", -2.1), ("inspiring", 2.2), ("jail", -2.2), ("joy", 2.8),
+    ("kill", -3.7), ("killed", -3.5), ("killing", -3.4), ("kills", -2.5), ("kind", 2.4),
+    ("lose", -1.7), ("loses", -1.3), ("loss", -1.3), ("losses", -1.7), ("lost", -1.3),
+    ("love", 3.2), ("murder", -3.6), ("optimism", 2.5), ("optimistic", 2.4), ("pain", -2.3),
+    ("panic", -2.3), ("peace", 2.5), ("peaceful", 2.2), ("poor", -2.1), ("positive", 2.6),
+    ("praise", 2.6), ("praised", 2.2), ("problem", -1.7), ("problems", -1.7), ("profit", 1.9),
+    ("profits", 1.9), ("progress", 1.8), ("protest", -1.0), ("protests", -0.9), ("racist", -3.1),
+    ("recession", -1.8), ("recover", 1.6), ("recovery", 1.4), ("reject", -1.7), ("rejected", -2.4),
+    ("relief", 2.1), ("rescue", 2.3), ("rescued", 1.5), ("risk", -1.1), ("sad", -2.1),
+    ("safe", 1.9), ("safety", 1.8), ("scandal", -1.9), ("shame", -2.1), ("shocking", -1.7),
+    ("shooting", -1.4), ("sick", -2.3), ("strong", 2.3), ("success", 2.7), ("successful", 2.8),
+    ("suffer", -2.1), ("support", 1.7), ("supports", 1.5), ("terrible", -2.1), ("terror", -3.0),
+    ("terrorism", -3.6), ("terrorist", -3.7), ("threat", -2.4), ("threaten", -1.6), ("threats", -1.8),
+    ("toxic", -2.8), ("tragedy", -3.4), ("tragic", -3.2), ("unemployment", -1.9), ("victim", -1.8),
+    ("victims", -1.7), ("victory", 2.8), ("violence", -3.1), ("violent", -2.9), ("war", -2.9),
+    ("warning", -1.4), ("wars", -2.6), ("welcome", 2.0), ("welcomed", 1.9), ("win", 2.8),
+    ("winning", 2.4), ("wins", 2.7), ("won", 2.7), ("wonderful", 2.7), ("worried", -1.2),
+    ("worry", -1.9), ("worse", -2.1), ("worst", -3.1), ("wrong", -2.1)
+];
+
+const BOOSTERS: &[&str] = &[
+    "absolutely", "completely", "deeply", "enormously", "entirely", "especially",
+    "exceptionally", "extremely", "greatly", "highly", "hugely", "incredibly",
+    "intensely", "particularly", "really", "remarkably", "so", "substantially",
+    "thoroughly", "totally", "tremendously", "truly", "unbelievably", "utterly", "very"
+];
+
+const DAMPENERS: &[&str] = &[
+    "almost", "barely", "hardly", "less", "little", "marginally", "partly",
+    "scarcely", "slightly", "somewhat"
+];
+
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "none", "nobody", "nothing", "neither", "nor",
+    "nowhere", "cannot", "without", "ain't", "aren't", "can't", "couldn't",
+    "didn't", "doesn't", "don't", "hasn't", "haven't", "isn't", "wasn't",
+    "weren't", "won't", "wouldn't", "shouldn't"
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tone {
+    Positive,
+    Negative,
+    Neutral
+}
+
+impl Tone {
+    pub const ALL: [Tone; 3] = [Self::Positive, Self::Negative, Self::Neutral];
+
+    /// The tone of a compound score.
+    pub fn of(score: f64) -> Tone {
+        if score >= THRESHOLD {
+            Self::Positive
+        } else if score <= -THRESHOLD {
+            Self::Negative
+        } else {
+            Self::Neutral
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Positive => "positive",
+            Self::Negative => "negative",
+            Self::Neutral => "neutral",
+        }
+    }
+}
+
+impl FromStr for Tone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "positive" => Ok(Self::Positive),
+            "negative" => Ok(Self::Negative),
+            "neutral" => Ok(Self::Neutral),
+            _ => Err(format!("unknown sentiment {:?}, pick one of positive, negative, neutral", s)),
+        }
+    }
+}
+
+fn valence(word: &str) -> Option<f64> {
+    LEXICON.binary_search_by(|(w, _)| w.cmp(&word)).ok().map(|i| LEXICON[i].1)
+}
+
+fn is_negation(word: &str) -> bool {
+    NEGATIONS.contains(&word) || word.ends_with("n't")
+}
+
+/// Compound score of `text`, from -1 (most negative) to 1 (most positive).
+pub fn score(text: &str) -> f64 {
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\''))
+        .filter(|w| !w.is_empty())
+        .collect();
+    let lower: Vec<String> = words.iter().map(|w| w.to_lowercase().replace('’', "'")).collect();
+    let shouting = |w: &str| w.chars().any(char::is_alphabetic) && !w.chars().any(char::is_lowercase);
+    // Capitals only stand out when not everything is in capitals.
+    let mixed_case = words.iter().any(|w| !shouting(w));
+    let but = lower.iter().position(|w| w == "but");
+
+    let mut sum = 0.0;
+    for (i, word) in lower.iter().enumerate() {
+        let mut v = match valence(word) {
+            Some(v) => v,
+            None => continue,
+        };
+        if mixed_case && shouting(words[i]) {
+            v += CAPS_BOOST * v.signum();
+        }
+        for distance in 1..=3 {
+            let before = match i.checked_sub(distance) {
+                Some(j) => lower[j].as_str(),
+                None => break,
+            };
+            // Further away, a booster counts a little less.
+            let fade = [1.0, 0.95, 0.9][distance - 1];
+            if BOOSTERS.contains(&before) {
+                v += BOOST * fade * v.signum();
+            } else if DAMPENERS.contains(&before) {
+                v -= BOOST * fade * v.signum();
+            }
+            if is_negation(before) {
+                v *= NEGATION;
+            }
+        }
+        // What comes after a "but" is what counts.
+        match but {
+            Some(b) if i < b => v *= 0.5,
+            Some(b) if i > b => v *= 1.5,
+            _ => {},
+        }
+        sum += v;
+    }
+
+    if sum != 0.0 {
+        let exclamations = text.matches('!').count().min(4) as f64;
+        sum += exclamations * EXCLAMATION * sum.signum();
+    }
+    (sum / (sum * sum + ALPHA).sqrt()).clamp(-1.0, 1.0)
+}
+
+impl Article {
+    /// Compound sentiment of the title and description, from -1 to 1.
+    pub fn sentiment(&self) -> f64 {
+        score(&format!("{}\n{}", self.headline(), self.description.as_deref().unwrap_or_default()))
+    }
+
+    pub fn tone(&self) -> Tone {
+        Tone::of(self.sentiment())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexicon_is_sorted() {
+        assert!(LEXICON.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn scores_like_vader() {
+        assert_eq!(score("Council meets on Tuesday"), 0.0);
+        let good = score("Rescue team praised after a successful mission");
+        assert!(good > 0.5, "{}", good);
+        let bad = score("Storm kills three, leaves a trail of damage");
+        assert!(bad < -0.5, "{}", bad);
+
+        // Boosters, capitals and exclamation marks strengthen, negations
+        // turn around.
+        assert!(score("a very good day") > score("a good day"));
+        assert!(score("a GOOD day") > score("a good day"));
+        assert!(score("a good day!") > score("a good day"));
+        assert!(score("not a good day") < 0.0);
+        assert!(score("the plan isn't bad") > 0.0);
+        assert!(score("Talks failed, but a deal brings relief") > 0.0);
+
+        assert_eq!(Tone::of(good), Tone::Positive);
+        assert_eq!(Tone::of(bad), Tone::Negative);
+        assert_eq!(Tone::of(0.01), Tone::Neutral);
+        assert_eq!("Negative".parse::<Tone>(), Ok(Tone::Negative));
+    }
+}
@@ -0,0 +1,174 @@
+//! Extractive summaries. Sentences are ranked with TextRank: every sentence
+//! is a node, linked to the others by how many words they share, and the
+//! best connected sentences are taken to be the most representative ones.
+
+use std::collections::HashSet;
+
+const DAMPING: f64 = 0.85;
+const ITERATIONS: usize = 50;
+/// Sentences shorter than this are headings, captions or bylines rather than
+/// prose, and make poor summaries.
+const MIN_WORDS: usize = 6;
+
+pub(crate) const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at",
+    "be", "been", "before", "but", "by", "can", "could", "did", "do", "does",
+    "for", "from", "had", "has", "have", "he", "her", "his", "how", "i", "if",
+    "in", "into", "is", "it", "its", "more", "most", "not", "of", "on", "one",
+    "or", "our", "out", "over", "said", "she", "so", "some", "than", "that",
+    "the", "their", "them", "then", "there", "these", "they", "this", "to",
+    "up", "was", "we", "were", "what", "when", "which", "who", "will", "with",
+    "would", "you", "your"
+];
+
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "inc",
+    "ltd", "co", "corp", "gov", "gen", "sen", "rep", "jan", "feb", "mar",
+    "apr", "jun", "jul", "aug", "sep", "sept", "oct", "nov", "dec", "no"
+];
+
+/// Splits text into sentences on `.`, `!` and `?`, leaving abbreviations
+/// and initials like "Mr." or "U.S." alone. Line breaks always end a
+/// sentence, so headings don't run into the paragraph below them.
+pub fn sentences(text: &str) -> Vec<String> {
+    text.lines().flat_map(line_sentences).collect()
+}
+
+fn line_sentences(line: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    for (i, word) in words.iter().enumerate() {
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+
+        let trimmed = word.trim_end_matches(['"', '\'', ')', '”', '’']);
+        let ends = trimmed.ends_with(['.', '!', '?']);
+        let next_starts = words
+            .get(i + 1)
+            .and_then(|w| w.trim_start_matches(|c: char| !c.is_alphanumeric()).chars().next())
+            .map(|c| c.is_uppercase() || c.is_numeric())
+            .unwrap_or(true);
+
+        if ends && next_starts && !is_abbreviation(trimmed) {
+            out.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+fn is_abbreviation(word: &str) -> bool {
+    let stem = word.trim_end_matches('.').trim_start_matches(|c: char| !c.is_alphanumeric());
+    // Initials such as "U.S." or "J."
+    let initials = stem.split('.').all(|part| part.chars().count() == 1 && part.chars().all(char::is_uppercase));
+    initials || ABBREVIATIONS.contains(&stem.to_lowercase().as_str())
+}
+
+fn content_words(sentence: &str) -> HashSet<String> {
+    sentence
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.to_lowercase())
+        .filter(|w| w.len() > 1 && !STOPWORDS.contains(&w.as_str()))
+        .collect()
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.len() < 2 || b.len() < 2 {
+        return 0.;
+    }
+    let shared = a.intersection(b).count() as f64;
+    shared / ((a.len() as f64).ln() + (b.len() as f64).ln())
+}
+
+/// Scores every sentence with TextRank.
+fn rank(words: &[HashSet<String>]) -> Vec<f64> {
+    let n = words.len();
+    let weights: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 0. } else { similarity(&words[i], &words[j]) }).collect())
+        .collect();
+    let out_weight: Vec<f64> = weights.iter().map(|row| row.iter().sum()).collect();
+
+    let mut scores = vec![1.; n];
+    for _ in 0..ITERATIONS {
+        scores = (0..n)
+            .map(|i| {
+                let incoming: f64 = (0..n)
+                    .filter(|&j| out_weight[j] > 0.)
+                    .map(|j| weights[j][i] / out_weight[j] * scores[j])
+                    .sum();
+                (1. - DAMPING) + DAMPING * incoming
+            })
+            .collect();
+    }
+    scores
+}
+
+/// Picks the `count` most representative sentences of `text`, in the order
+/// they appear. Ties go to the earlier sentence, so the result is the same
+/// on every run.
+pub fn summarize(text: &str, count: usize) -> Vec<String> {
+    if count == 0 {
+        return vec![];
+    }
+    let candidates: Vec<String> = sentences(text)
+        .into_iter()
+        .filter(|s| s.split_whitespace().count() >= MIN_WORDS)
+        .collect();
+    if candidates.len() <= count {
+        return candidates;
+    }
+
+    let words: Vec<_> = candidates.iter().map(|s| content_words(s)).collect();
+    let scores = rank(&words);
+
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(&b)));
+    let mut picked: Vec<usize> = order.into_iter().take(count).collect();
+    picked.sort_unstable();
+
+    picked.into_iter().map(|i| candidates[i].to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::extract;
+
+    #[test]
+    fn splits_sentences_around_abbreviations() {
+        let text = "Mr. Smith went to Washington. He met the U.S. president at 9 a.m. on Monday! Was it worth it? \"Yes,\" he said.";
+        assert_eq!(sentences(text), vec![
+            "Mr. Smith went to Washington.",
+            "He met the U.S. president at 9 a.m. on Monday!",
+            "Was it worth it?",
+            "\"Yes,\" he said.",
+        ]);
+    }
+
+    #[test]
+    fn summarizes_fixture_articles() {
+        let doc = extract(include_str!("../tests/fixtures/news_site.html"), None).unwrap();
+        assert_eq!(summarize(&doc.text(), 2), vec![
+            "A powerful storm swept along the coast on Tuesday, tearing down power lines, flooding streets and leaving thousands of homes without electricity, officials said.",
+            "Power companies said most customers should be reconnected by the weekend, although some rural areas could wait longer, depending on the damage to the network.",
+        ]);
+
+        let doc = extract(include_str!("../tests/fixtures/blog_post.html"), None).unwrap();
+        assert_eq!(summarize(&doc.text(), 1), vec![
+            "It runs after type checking, and it looks at how references are created, used and dropped, so that no reference outlives the value it points to.",
+        ]);
+    }
+
+    #[test]
+    fn short_texts_are_kept_whole() {
+        let text = "The council approved the new budget on Monday evening. Taxes will not go up this year.";
+        assert_eq!(summarize(text, 3), sentences(text));
+        assert!(summarize(text, 0).is_empty());
+    }
+}